@@ -0,0 +1,347 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Alternative policy evaluation backends.
+
+use anyhow::{bail, Result};
+
+/// A policy evaluation backend. The default backend interprets Rego policy
+/// text using the regorus engine; the "wasm" backend runs a pre-compiled OPA
+/// WASM bundle instead, avoiding the policy compilation cost at startup.
+///
+/// Backends evaluate boolean policy rules only - the policy state and
+/// metadata operations supported by the default regorus engine are not
+/// available through this trait.
+pub trait PolicyBackend: std::fmt::Debug + Send + Sync {
+    /// Load a policy. The policy bytes are Rego text for the regorus
+    /// backend, or a compiled OPA WASM bundle for the wasm backend.
+    fn add_policy(&mut self, name: String, policy: Vec<u8>) -> Result<()>;
+
+    /// Set the input document for the next query evaluation.
+    fn set_input_json(&mut self, input_json: &str) -> Result<()>;
+
+    /// Evaluate a boolean query - e.g.,
+    /// "data.agent_policy.CreateContainerRequest".
+    fn eval_bool_query(&mut self, query: String) -> Result<bool>;
+
+    /// Return the print() messages accumulated while evaluating queries.
+    fn take_prints(&mut self) -> Vec<String>;
+}
+
+/// Create the policy evaluation backend with the given name.
+pub fn new_backend(name: &str) -> Result<Box<dyn PolicyBackend>> {
+    match name {
+        "regorus" => Ok(Box::new(RegorusBackend::new())),
+        #[cfg(feature = "wasm-policy")]
+        "wasm" => Ok(Box::new(wasm::WasmBackend::new())),
+        _ => bail!("unsupported policy backend {name}"),
+    }
+}
+
+/// Policy backend interpreting Rego policy text using the regorus engine.
+#[derive(Debug)]
+pub struct RegorusBackend {
+    engine: regorus::Engine,
+}
+
+impl RegorusBackend {
+    pub fn new() -> Self {
+        let mut engine = regorus::Engine::new();
+        engine.set_strict_builtin_errors(false);
+        engine.set_gather_prints(true);
+        Self { engine }
+    }
+}
+
+impl Default for RegorusBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PolicyBackend for RegorusBackend {
+    fn add_policy(&mut self, name: String, policy: Vec<u8>) -> Result<()> {
+        self.engine.add_policy(name, String::from_utf8(policy)?)?;
+        Ok(())
+    }
+
+    fn set_input_json(&mut self, input_json: &str) -> Result<()> {
+        self.engine.set_input_json(input_json)
+    }
+
+    fn eval_bool_query(&mut self, query: String) -> Result<bool> {
+        self.engine.eval_bool_query(query, false)
+    }
+
+    fn take_prints(&mut self) -> Vec<String> {
+        self.engine.take_prints().unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "wasm-policy")]
+mod wasm {
+    use super::PolicyBackend;
+    use anyhow::{anyhow, bail, Result};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// JSON output format of the one-shot opa_eval() entry point.
+    const OPA_EVAL_FORMAT_JSON: i32 = 0;
+
+    /// Policy backend running a compiled OPA WASM bundle - e.g., the output
+    /// of "opa build -t wasm -e agent_policy <policy.rego>".
+    pub struct WasmBackend {
+        /// print() messages accumulated through the opa_println import.
+        prints: Arc<Mutex<Vec<String>>>,
+
+        /// Input document for the next query evaluation.
+        input: String,
+
+        /// The instantiated WASM bundle, created by add_policy().
+        instance: Option<WasmInstance>,
+    }
+
+    /// An instantiated OPA WASM bundle, following the OPA WASM ABI - see
+    /// https://www.openpolicyagent.org/docs/latest/wasm/.
+    struct WasmInstance {
+        store: wasmtime::Store<()>,
+        memory: wasmtime::Memory,
+        opa_malloc: wasmtime::TypedFunc<i32, i32>,
+        opa_json_parse: wasmtime::TypedFunc<(i32, i32), i32>,
+        opa_json_dump: wasmtime::TypedFunc<i32, i32>,
+        opa_heap_ptr_get: wasmtime::TypedFunc<(), i32>,
+        opa_heap_ptr_set: wasmtime::TypedFunc<i32, ()>,
+        opa_eval: wasmtime::TypedFunc<(i32, i32, i32, i32, i32, i32, i32), i32>,
+
+        /// Entrypoint identifiers exported by the bundle, indexed by
+        /// entrypoint path - e.g., "agent_policy/CreateContainerRequest".
+        entrypoints: HashMap<String, i32>,
+
+        /// Address of the parsed external data document.
+        data_addr: i32,
+
+        /// Heap position right after add_policy() finished setting up the
+        /// bundle. Each evaluation rolls the heap back to this position, so
+        /// the per-evaluation allocations don't accumulate.
+        heap_base: i32,
+    }
+
+    impl std::fmt::Debug for WasmBackend {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("WasmBackend")
+                .field("instance", &self.instance.is_some())
+                .finish()
+        }
+    }
+
+    impl WasmBackend {
+        pub fn new() -> Self {
+            Self {
+                prints: Arc::new(Mutex::new(Vec::new())),
+                input: "{}".to_string(),
+                instance: None,
+            }
+        }
+
+        fn instance(&mut self) -> Result<&mut WasmInstance> {
+            self.instance
+                .as_mut()
+                .ok_or_else(|| anyhow!("no WASM policy bundle has been loaded"))
+        }
+    }
+
+    impl Default for WasmBackend {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl PolicyBackend for WasmBackend {
+        fn add_policy(&mut self, _name: String, policy: Vec<u8>) -> Result<()> {
+            let engine = wasmtime::Engine::default();
+            let module = wasmtime::Module::from_binary(&engine, &policy)?;
+            let mut store = wasmtime::Store::new(&engine, ());
+            let mut linker = wasmtime::Linker::new(&engine);
+
+            let memory = wasmtime::Memory::new(&mut store, wasmtime::MemoryType::new(10, None))?;
+            linker.define(&mut store, "env", "memory", memory)?;
+
+            let prints = self.prints.clone();
+            linker.func_wrap(
+                "env",
+                "opa_println",
+                move |caller: wasmtime::Caller<'_, ()>, addr: i32| {
+                    let message = read_cstring(memory.data(&caller), addr);
+                    prints.lock().unwrap().push(message);
+                },
+            )?;
+            linker.func_wrap(
+                "env",
+                "opa_abort",
+                move |caller: wasmtime::Caller<'_, ()>, addr: i32| -> Result<()> {
+                    bail!("opa_abort: {}", read_cstring(memory.data(&caller), addr))
+                },
+            )?;
+
+            // The builtin functions that were not compiled into the bundle
+            // are not supported by this backend.
+            linker.func_wrap("env", "opa_builtin0", |_: i32, _: i32| -> Result<i32> {
+                bail!("OPA SDK builtins are not supported")
+            })?;
+            linker.func_wrap(
+                "env",
+                "opa_builtin1",
+                |_: i32, _: i32, _: i32| -> Result<i32> {
+                    bail!("OPA SDK builtins are not supported")
+                },
+            )?;
+            linker.func_wrap(
+                "env",
+                "opa_builtin2",
+                |_: i32, _: i32, _: i32, _: i32| -> Result<i32> {
+                    bail!("OPA SDK builtins are not supported")
+                },
+            )?;
+            linker.func_wrap(
+                "env",
+                "opa_builtin3",
+                |_: i32, _: i32, _: i32, _: i32, _: i32| -> Result<i32> {
+                    bail!("OPA SDK builtins are not supported")
+                },
+            )?;
+            linker.func_wrap(
+                "env",
+                "opa_builtin4",
+                |_: i32, _: i32, _: i32, _: i32, _: i32, _: i32| -> Result<i32> {
+                    bail!("OPA SDK builtins are not supported")
+                },
+            )?;
+
+            let wasm_instance = linker.instantiate(&mut store, &module)?;
+
+            let mut instance = WasmInstance {
+                opa_malloc: wasm_instance.get_typed_func(&mut store, "opa_malloc")?,
+                opa_json_parse: wasm_instance.get_typed_func(&mut store, "opa_json_parse")?,
+                opa_json_dump: wasm_instance.get_typed_func(&mut store, "opa_json_dump")?,
+                opa_heap_ptr_get: wasm_instance.get_typed_func(&mut store, "opa_heap_ptr_get")?,
+                opa_heap_ptr_set: wasm_instance.get_typed_func(&mut store, "opa_heap_ptr_set")?,
+                opa_eval: wasm_instance.get_typed_func(&mut store, "opa_eval")?,
+                store,
+                memory,
+                entrypoints: HashMap::new(),
+                data_addr: 0,
+                heap_base: 0,
+            };
+
+            // The backend does not support external data documents, so the
+            // data document stays empty.
+            let (data_addr, data_len) = instance.write_bytes(b"{}")?;
+            instance.data_addr = instance
+                .opa_json_parse
+                .call(&mut instance.store, (data_addr, data_len))?;
+
+            let entrypoints: wasmtime::TypedFunc<(), i32> =
+                wasm_instance.get_typed_func(&mut instance.store, "entrypoints")?;
+            let entrypoints_addr = entrypoints.call(&mut instance.store, ())?;
+            let entrypoints_json = instance.read_json(entrypoints_addr)?;
+            instance.entrypoints = serde_json::from_str(&entrypoints_json)?;
+
+            instance.heap_base = instance.opa_heap_ptr_get.call(&mut instance.store, ())?;
+
+            self.instance = Some(instance);
+            Ok(())
+        }
+
+        fn set_input_json(&mut self, input_json: &str) -> Result<()> {
+            self.input = input_json.to_string();
+            Ok(())
+        }
+
+        fn eval_bool_query(&mut self, query: String) -> Result<bool> {
+            let input = self.input.clone();
+            let instance = self.instance()?;
+
+            // Map e.g. "data.agent_policy.CreateContainerRequest" to the
+            // "agent_policy/CreateContainerRequest" entrypoint path.
+            let entrypoint_path = query
+                .strip_prefix("data.")
+                .unwrap_or(&query)
+                .replace('.', "/");
+            let entrypoint_id = *instance
+                .entrypoints
+                .get(&entrypoint_path)
+                .ok_or_else(|| anyhow!("the WASM bundle has no {entrypoint_path} entrypoint"))?;
+
+            // Roll the bundle's heap back to its post-setup position,
+            // releasing the input and result allocations of the previous
+            // evaluation - the heap would otherwise grow without bound over
+            // the lifetime of the agent.
+            instance
+                .opa_heap_ptr_set
+                .call(&mut instance.store, instance.heap_base)?;
+
+            let (input_addr, input_len) = instance.write_bytes(input.as_bytes())?;
+            let heap_ptr = instance.opa_heap_ptr_get.call(&mut instance.store, ())?;
+            let data_addr = instance.data_addr;
+            let result_addr = instance.opa_eval.call(
+                &mut instance.store,
+                (
+                    0,
+                    entrypoint_id,
+                    data_addr,
+                    input_addr,
+                    input_len,
+                    heap_ptr,
+                    OPA_EVAL_FORMAT_JSON,
+                ),
+            )?;
+
+            let result_json = read_cstring(instance.memory.data(&instance.store), result_addr);
+            let results: Vec<serde_json::Value> = serde_json::from_str(&result_json)?;
+            match results.as_slice() {
+                [] => Ok(false),
+                [result] => result["result"]
+                    .as_bool()
+                    .ok_or_else(|| anyhow!("the {entrypoint_path} result is not a boolean")),
+                _ => bail!("unexpected {entrypoint_path} result set {result_json}"),
+            }
+        }
+
+        fn take_prints(&mut self) -> Vec<String> {
+            std::mem::take(&mut self.prints.lock().unwrap())
+        }
+    }
+
+    impl WasmInstance {
+        /// Copy the bytes into the WASM bundle's memory and return their
+        /// address and length.
+        fn write_bytes(&mut self, bytes: &[u8]) -> Result<(i32, i32)> {
+            let addr = self.opa_malloc.call(&mut self.store, bytes.len() as i32)?;
+            self.memory.write(&mut self.store, addr as usize, bytes)?;
+            Ok((addr, bytes.len() as i32))
+        }
+
+        /// Serialize the OPA value at the given address to JSON text.
+        fn read_json(&mut self, value_addr: i32) -> Result<String> {
+            let json_addr = self.opa_json_dump.call(&mut self.store, value_addr)?;
+            Ok(read_cstring(self.memory.data(&self.store), json_addr))
+        }
+    }
+
+    /// Read the NUL-terminated string at the given address of the WASM
+    /// bundle's memory. A misbehaving bundle can return an out-of-range
+    /// address, so that case produces a placeholder string instead of
+    /// panicking the agent.
+    fn read_cstring(memory: &[u8], addr: i32) -> String {
+        let Some(bytes) = usize::try_from(addr)
+            .ok()
+            .and_then(|addr| memory.get(addr..))
+        else {
+            return format!("<invalid WASM memory address {addr}>");
+        };
+        let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..len]).to_string()
+    }
+}
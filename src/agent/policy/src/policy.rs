@@ -6,13 +6,24 @@
 
 //! Policy evaluation for the kata-agent.
 
+use crate::backend::PolicyBackend;
 use anyhow::{bail, Result};
+use sha2::Digest;
 use slog::{debug, error, info, warn};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::time::Duration;
 use tokio::io::AsyncWriteExt;
 
 static POLICY_LOG_FILE: &str = "/tmp/policy.txt";
 static POLICY_DEFAULT_FILE: &str = "/etc/kata-opa/default-policy.rego";
 
+/// Key of the OPA "data" namespace where the data bundles fetched from a
+/// bundle server get added.
+static BUNDLE_DATA_KEY: &str = "bundle";
+
 /// Convenience macro to obtain the scope logger
 macro_rules! sl {
     () => {
@@ -29,8 +40,131 @@ pub struct AgentPolicy {
     /// "/tmp/policy.txt" log file for policy activity.
     log_file: Option<tokio::fs::File>,
 
-    /// Regorus engine
-    engine: regorus::Engine,
+    /// Regorus engine, created on first use.
+    engine: Option<regorus::Engine>,
+
+    /// Version of the regorus crate used to evaluate the policy.
+    regorus_version: String,
+
+    /// External data documents added to the engine through add_data(). These
+    /// documents get added again after the policy is replaced by set_policy().
+    data_documents: Vec<(String, serde_json::Value)>,
+
+    /// Names of the rules defined by the current policy, used for coverage
+    /// reporting.
+    rule_names: HashSet<String>,
+
+    /// Names of the rules evaluated since coverage collection got enabled,
+    /// or None when coverage collection is disabled.
+    coverage: Option<HashSet<String>>,
+
+    /// Optional engine loaded with a shadow policy, used for comparing the
+    /// decisions of two policies before switching to a new one. The shadow
+    /// policy decisions are never enforced.
+    shadow_engine: Option<regorus::Engine>,
+
+    /// Optional engine loaded with a second, independently authored policy,
+    /// for defense-in-depth deployments. Unlike the shadow policy decisions,
+    /// the secondary policy decisions are enforced, as specified by the
+    /// conflict_resolution field.
+    secondary_engine: Option<regorus::Engine>,
+
+    /// How to combine the primary and secondary policy decisions when they
+    /// disagree.
+    conflict_resolution: ConflictResolution,
+
+    /// The currently loaded policy text, recorded for periodic
+    /// re-verification by check_policy_hash().
+    policy_text: String,
+
+    /// Format version of the currently loaded policy document: 0 for bare
+    /// Rego text, or the version of the policy envelope it came from.
+    policy_version: u32,
+
+    /// Hash of the currently loaded policy text, computed with
+    /// hash_algorithm and truncated to hash_truncation bytes.
+    policy_hash: Vec<u8>,
+
+    /// Hash algorithm used for the policy hash, negotiated based on the
+    /// TEE firmware capabilities.
+    hash_algorithm: PolicyHashAlgorithm,
+
+    /// Optional number of leading policy hash bytes kept, for TEEs whose
+    /// REPORT_DATA or HOST_DATA fields are smaller than the hash size.
+    hash_truncation: Option<usize>,
+
+    /// Alternative policy evaluation backend, selected by the
+    /// policy_backend agent configuration setting. When set, this backend
+    /// evaluates the requests instead of the regorus engine above.
+    backend: Option<Box<dyn PolicyBackend>>,
+
+    /// Receiver for the data bundles fetched from the bundle server
+    /// configured by configure_bundle_server(). A pending bundle gets
+    /// applied to the engine before evaluating the next request.
+    bundle_rx: Option<tokio::sync::watch::Receiver<Option<String>>>,
+
+    /// Bearer token used by configure_bundle_server() to authenticate
+    /// against the bundle server.
+    bundle_server_token: Option<String>,
+
+    /// Custom sink for the OPA print() output, set by set_print_handler().
+    /// When unset, the print() output gets embedded into denial messages.
+    print_handler: Option<PrintHandler>,
+}
+
+/// How to resolve a disagreement between the primary and the secondary
+/// policy decisions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+pub enum ConflictResolution {
+    /// Treat any disagreement as a policy error.
+    #[default]
+    Strict,
+
+    /// Allow the request if either policy allows it.
+    AllowIfEither,
+
+    /// Deny the request if either policy denies it.
+    DenyIfEither,
+}
+
+/// Hash algorithm used for the policy hash. SHA-256 produces 32 bytes,
+/// fitting e.g. the SEV-SNP HOST_DATA field exactly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PolicyHashAlgorithm {
+    #[default]
+    Sha256,
+
+    /// Faster than SHA-256, while still producing 32 bytes natively.
+    Blake2s256,
+}
+
+/// Policy document persisted to disk by save_to_disk() and read back by
+/// restore_from_disk().
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct PersistedPolicy {
+    version: u32,
+    policy: String,
+    hash: Vec<u8>,
+
+    #[serde(default)]
+    algorithm: PolicyHashAlgorithm,
+
+    #[serde(default)]
+    truncation: Option<usize>,
+}
+
+/// Versioned policy document envelope. Bare Rego policy text - i.e.,
+/// version 0 - is still accepted for backward compatibility.
+#[derive(serde::Deserialize, Debug)]
+struct PolicyEnvelope {
+    version: u32,
+    policy: String,
+
+    /// Additional information about the policy, not interpreted by the
+    /// agent.
+    #[serde(default)]
+    #[allow(dead_code)]
+    metadata: serde_json::Value,
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -39,16 +173,84 @@ struct MetadataResponse {
     ops: Option<json_patch::Patch>,
 }
 
+/// Report of the rules that have been evaluated while coverage collection
+/// was enabled.
+#[derive(Debug, serde::Serialize)]
+pub struct CoverageReport {
+    /// Rules evaluated at least once.
+    pub evaluated: Vec<String>,
+
+    /// Rules that have never been evaluated.
+    pub not_evaluated: Vec<String>,
+}
+
+/// Custom sink for the OPA print() output - e.g., a structured logger or a
+/// metrics collector.
+struct PrintHandler(Box<dyn Fn(String) + Send>);
+
+impl std::fmt::Debug for PrintHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PrintHandler")
+    }
+}
+
 impl AgentPolicy {
     /// Create AgentPolicy object.
     pub fn new() -> Self {
         Self {
             allow_failures: false,
-            engine: Self::new_engine(),
+            regorus_version: Self::regorus_version(),
             ..Default::default()
         }
     }
 
+    /// Get the regorus engine, creating it on the first use.
+    fn engine(&mut self) -> Result<&mut regorus::Engine> {
+        if self.engine.is_none() {
+            let mut engine = Self::new_engine();
+            for (key, value) in &self.data_documents {
+                Self::add_data_to_engine(&mut engine, key, value)?;
+            }
+            self.engine = Some(engine);
+        }
+        Ok(self.engine.as_mut().unwrap())
+    }
+
+    /// Check whether the regorus engine has been created already.
+    pub fn is_initialized(&self) -> bool {
+        self.engine.is_some()
+    }
+
+    /// Obtain the version of the regorus crate from its opa.runtime()
+    /// builtin. Policy evaluation behavior can change from one regorus
+    /// version to another, so this version is being logged and reported
+    /// to the host for debugging purposes.
+    fn regorus_version() -> String {
+        let mut engine = Self::new_engine();
+        if let Ok(results) = engine.eval_query("opa.runtime()".to_string(), false) {
+            if let Some(regorus::Value::Object(runtime)) = results
+                .result
+                .first()
+                .and_then(|r| r.expressions.first())
+                .map(|e| e.value.clone())
+            {
+                if let Some(regorus::Value::String(version)) =
+                    runtime.get(&regorus::Value::String("regorus-version".into()))
+                {
+                    return version.to_string();
+                }
+            }
+        }
+
+        warn!(sl!(), "policy: failed to obtain the regorus version");
+        "unknown".to_string()
+    }
+
+    /// Version of the regorus crate used to evaluate the policy.
+    pub fn get_regorus_version(&self) -> &str {
+        &self.regorus_version
+    }
+
     fn new_engine() -> regorus::Engine {
         let mut engine = regorus::Engine::new();
         engine.set_strict_builtin_errors(false);
@@ -91,6 +293,7 @@ impl AgentPolicy {
                     .await?,
             );
             debug!(sl!(), "policy: log file: {}", log_file_path);
+            self.collect_coverage(true);
         }
 
         // Check if policy file has been set via AgentConfig
@@ -100,25 +303,30 @@ impl AgentPolicy {
             default_policy_file = POLICY_DEFAULT_FILE.to_string();
         }
         info!(sl!(), "default policy: {default_policy_file}");
+        info!(sl!(), "policy: regorus version: {}", self.regorus_version);
 
-        self.engine.add_policy_from_file(default_policy_file)?;
+        let default_policy = tokio::fs::read_to_string(&default_policy_file).await?;
+        let (version, default_policy) = Self::extract_policy_text(&default_policy)?;
+        self.rule_names = Self::get_rule_names(&default_policy);
+        self.record_policy_hash(version, &default_policy);
+        self.engine()?
+            .add_policy(default_policy_file, default_policy)?;
         self.update_allow_failures_flag().await?;
         Ok(())
     }
 
-    async fn apply_patch_to_state(&mut self, patch: json_patch::Patch) -> Result<()> {
+    fn apply_patch_to_state(engine: &mut regorus::Engine, patch: json_patch::Patch) -> Result<()> {
         // Convert the current engine data to a JSON value
-        let mut state = serde_json::to_value(self.engine.get_data())?;
+        let mut state = serde_json::to_value(engine.get_data())?;
 
         // Apply the patch to the state
         json_patch::patch(&mut state, &patch)?;
 
         // Clear the existing data in the engine
-        self.engine.clear_data();
+        engine.clear_data();
 
         // Add the patched state back to the engine
-        self.engine
-            .add_data(regorus::Value::from_json_str(&state.to_string())?)?;
+        engine.add_data(regorus::Value::from_json_str(&state.to_string())?)?;
 
         Ok(())
     }
@@ -127,17 +335,33 @@ impl AgentPolicy {
     pub async fn allow_request(&mut self, ep: &str, ep_input: &str) -> Result<(bool, String)> {
         debug!(sl!(), "policy check: {ep}");
         self.log_eval_input(ep, ep_input).await;
+        self.apply_pending_bundle();
+
+        if self.backend.is_some() {
+            return self.backend_allow_request(ep, ep_input);
+        }
+
+        // When policy failures are ignored anyway, don't create the engine
+        // just for evaluating a request before any policy has been set.
+        if self.engine.is_none() && self.allow_failures {
+            return Ok((true, String::new()));
+        }
 
         let query = format!("data.agent_policy.{ep}");
-        self.engine.set_input_json(ep_input)?;
+        let engine = self.engine()?;
+        engine.set_input_json(ep_input)?;
 
-        let results = self.engine.eval_query(query, false)?;
+        let results = engine.eval_query(query, false)?;
 
-        let prints = match self.engine.take_prints() {
-            Ok(p) => p.join(" "),
+        let prints = match engine.take_prints() {
+            Ok(p) => self.handle_prints(p),
             Err(e) => format!("Failed to get policy log: {e}"),
         };
 
+        if let Some(coverage) = &mut self.coverage {
+            coverage.insert(ep.to_string());
+        }
+
         if results.result.len() != 1 {
             // Results are empty when AllowRequestsFailingPolicy is used to allow a Request that hasn't been defined in the policy
             if self.allow_failures {
@@ -169,7 +393,7 @@ impl AgentPolicy {
 
                 if metadata_response.allowed {
                     if let Some(ops) = metadata_response.ops {
-                        self.apply_patch_to_state(ops).await?;
+                        Self::apply_patch_to_state(self.engine()?, ops)?;
                     }
                 }
                 metadata_response.allowed
@@ -184,6 +408,43 @@ impl AgentPolicy {
             }
         };
 
+        if let Some(shadow_engine) = &mut self.shadow_engine {
+            let shadow_allow = Self::shadow_allows(shadow_engine, ep, ep_input);
+            if shadow_allow != Some(allow) {
+                let mut hasher = DefaultHasher::new();
+                ep_input.hash(&mut hasher);
+                warn!(
+                    sl!(),
+                    "policy: shadow policy decision {:?} diverges from {} for {ep}, input hash {:x}",
+                    shadow_allow,
+                    allow,
+                    hasher.finish()
+                );
+            }
+        }
+
+        if let Some(secondary_engine) = &mut self.secondary_engine {
+            // A secondary policy that cannot produce a decision for this
+            // input denies the request.
+            let secondary_allow =
+                Self::shadow_allows(secondary_engine, ep, ep_input).unwrap_or(false);
+            if secondary_allow != allow {
+                warn!(
+                    sl!(),
+                    "policy: secondary policy decision {secondary_allow} diverges from {allow} for {ep}"
+                );
+                allow = match self.conflict_resolution {
+                    ConflictResolution::Strict => {
+                        bail!(
+                            "policy check: primary and secondary policy decisions diverge for {ep}"
+                        )
+                    }
+                    ConflictResolution::AllowIfEither => true,
+                    ConflictResolution::DenyIfEither => false,
+                };
+            }
+        }
+
         if !allow && self.allow_failures {
             warn!(sl!(), "policy: ignoring error for {ep}");
             allow = true;
@@ -192,12 +453,637 @@ impl AgentPolicy {
         Ok((allow, prints))
     }
 
-    /// Replace the Policy in regorus.
+    /// Evaluate the request using the alternative policy evaluation backend.
+    fn backend_allow_request(&mut self, ep: &str, ep_input: &str) -> Result<(bool, String)> {
+        let backend = self.backend.as_mut().unwrap();
+        backend.set_input_json(ep_input)?;
+        let mut allow = backend.eval_bool_query(format!("data.agent_policy.{ep}"))?;
+        let prints = backend.take_prints();
+        let prints = self.handle_prints(prints);
+
+        if let Some(coverage) = &mut self.coverage {
+            coverage.insert(ep.to_string());
+        }
+
+        if !allow && self.allow_failures {
+            warn!(sl!(), "policy: ignoring error for {ep}");
+            allow = true;
+        }
+
+        Ok((allow, prints))
+    }
+
+    /// Replace the regorus engine with the named policy evaluation backend,
+    /// loading the policy file in that backend's policy format - e.g., a
+    /// compiled OPA WASM bundle for the "wasm" backend. Backends evaluate
+    /// boolean policy rules only - the policy state and metadata operations
+    /// supported by the regorus engine are not available.
+    pub async fn initialize_backend(
+        &mut self,
+        backend_name: &str,
+        policy_file: &str,
+    ) -> Result<()> {
+        info!(sl!(), "policy: using the {backend_name} backend");
+
+        let policy = tokio::fs::read(policy_file).await?;
+        let mut backend = crate::backend::new_backend(backend_name)?;
+        backend.add_policy("agent_policy".to_string(), policy)?;
+        self.backend = Some(backend);
+        Ok(())
+    }
+
+    /// Replace the Policy in regorus. The new policy gets staged in a
+    /// separate engine and the current engine gets swapped out only after
+    /// all of the staging steps succeeded, so an invalid new policy cannot
+    /// leave the agent without an active policy.
     pub async fn set_policy(&mut self, policy: &str) -> Result<()> {
-        self.engine = Self::new_engine();
-        self.engine
-            .add_policy("agent_policy".to_string(), policy.to_string())?;
-        self.update_allow_failures_flag().await?;
+        let (version, policy) = Self::extract_policy_text(policy)?;
+        self.install_policy(version, &policy)
+    }
+
+    /// Stage the policy in a new engine and swap out the current engine
+    /// after all of the staging steps succeeded.
+    fn install_policy(&mut self, version: u32, policy: &str) -> Result<()> {
+        let mut engine = Self::new_engine();
+        for (key, value) in &self.data_documents {
+            Self::add_data_to_engine(&mut engine, key, value)?;
+        }
+        engine.add_policy("agent_policy".to_string(), policy.to_string())?;
+
+        // Evaluate AllowRequestsFailingPolicy while the new policy is still
+        // staged, so that the current policy remains active if the new
+        // policy fails to evaluate.
+        let allow_failures =
+            Self::shadow_allows(&mut engine, "AllowRequestsFailingPolicy", "{}").unwrap_or(false);
+        if allow_failures {
+            warn!(
+                sl!(),
+                "policy: AllowRequestsFailingPolicy is enabled - will ignore errors"
+            );
+        }
+
+        self.engine = Some(engine);
+        self.rule_names = Self::get_rule_names(policy);
+        self.record_policy_hash(version, policy);
+        self.allow_failures = allow_failures;
+        Ok(())
+    }
+
+    /// Extract the policy format version and the Rego policy text from a
+    /// policy document - either a versioned JSON envelope, or bare Rego
+    /// text that gets interpreted as format version 0.
+    fn extract_policy_text(policy: &str) -> Result<(u32, String)> {
+        let trimmed = policy.trim_start();
+        if trimmed.starts_with('{') {
+            // A Rego module cannot start with '{', so this document must be
+            // a policy envelope. A malformed envelope - e.g., with a typo'd
+            // version field - gets rejected instead of silently loaded as
+            // bare Rego text.
+            let envelope: PolicyEnvelope = serde_json::from_str(trimmed)
+                .map_err(|e| anyhow::anyhow!("invalid policy envelope: {e}"))?;
+
+            // Migration steps for future version upgrades belong here.
+            if envelope.version != 1 {
+                bail!("unsupported policy envelope version {}", envelope.version);
+            }
+            return Ok((envelope.version, envelope.policy));
+        }
+        Ok((0, policy.to_string()))
+    }
+
+    /// Record the currently loaded policy format version, text and hash, for
+    /// later re-verification by check_policy_hash().
+    fn record_policy_hash(&mut self, version: u32, policy: &str) {
+        self.policy_version = version;
+        self.policy_text = policy.to_string();
+        self.policy_hash =
+            Self::policy_hash(self.hash_algorithm, self.hash_truncation, version, policy);
+    }
+
+    fn policy_hash(
+        algorithm: PolicyHashAlgorithm,
+        truncation: Option<usize>,
+        version: u32,
+        policy: &str,
+    ) -> Vec<u8> {
+        let mut hash = match algorithm {
+            PolicyHashAlgorithm::Sha256 => {
+                let mut hasher = sha2::Sha256::new();
+                hasher.update(version.to_le_bytes());
+                hasher.update(policy.as_bytes());
+                hasher.finalize().to_vec()
+            }
+            PolicyHashAlgorithm::Blake2s256 => {
+                let mut hasher = blake2::Blake2s256::new();
+                hasher.update(version.to_le_bytes());
+                hasher.update(policy.as_bytes());
+                hasher.finalize().to_vec()
+            }
+        };
+        if let Some(length) = truncation {
+            hash.truncate(length);
+        }
+        hash
+    }
+
+    /// Select the policy hash algorithm and the optional hash truncation
+    /// length, negotiated based on the TEE firmware capabilities - e.g.,
+    /// truncating the hash to fit a REPORT_DATA field smaller than 32 bytes.
+    /// The hash of the currently loaded policy gets re-recorded with the new
+    /// options.
+    pub fn set_hash_options(&mut self, algorithm: PolicyHashAlgorithm, truncation: Option<usize>) {
+        self.hash_algorithm = algorithm;
+        self.hash_truncation = truncation;
+        self.policy_hash = Self::policy_hash(
+            algorithm,
+            truncation,
+            self.policy_version,
+            &self.policy_text,
+        );
+    }
+
+    /// Re-verify the currently loaded policy against an external reference
+    /// copy - the file persisted by save_to_disk(). The hash of the loaded
+    /// policy text gets recomputed and compared with the hash of the freshly
+    /// re-read reference copy, so the check fails when either the in-memory
+    /// policy or the reference file changed since the policy was installed -
+    /// instead of just recomputing the hash of the same in-memory text,
+    /// which could only fail on memory corruption.
+    pub fn check_policy_hash(&self, reference: &Path) -> Result<()> {
+        let persisted: PersistedPolicy =
+            serde_json::from_str(&std::fs::read_to_string(reference)?)?;
+        let reference_hash = Self::policy_hash(
+            self.hash_algorithm,
+            self.hash_truncation,
+            persisted.version,
+            &persisted.policy,
+        );
+        let hash = Self::policy_hash(
+            self.hash_algorithm,
+            self.hash_truncation,
+            self.policy_version,
+            &self.policy_text,
+        );
+        if hash != reference_hash {
+            bail!(
+                "the loaded policy diverged from the reference copy {}: \
+                reference {reference_hash:02x?}, loaded {hash:02x?}",
+                reference.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Re-verify the loaded policy against the reference copy on behalf of a
+    /// long-running container. The container's initial attestation can
+    /// become stale while it keeps running - e.g., after a firmware update
+    /// changes the node's TEE measurement - so the loaded policy gets
+    /// re-checked periodically.
+    pub fn verify_runtime_attestation(&self, container_id: &str, reference: &Path) -> Result<()> {
+        if let Err(e) = self.check_policy_hash(reference) {
+            bail!("runtime attestation failed for container {container_id}: {e}");
+        }
+        Ok(())
+    }
+
+    /// True when policy errors are ignored, for debug purposes.
+    pub fn get_allow_failures(&self) -> bool {
+        self.allow_failures
+    }
+
+    /// List the names of the top level rules of the currently loaded policy
+    /// modules, each paired with the path of the module defining it.
+    pub fn list_rules(&mut self) -> Result<Vec<(String, String)>> {
+        let mut rules: Vec<(String, String)> = Vec::new();
+        for source in self.engine()?.get_policies()? {
+            let module = source.get_path().clone();
+            for line in source.get_contents().lines() {
+                if let Some(name) = top_level_rule_name(line) {
+                    if !rules
+                        .iter()
+                        .any(|(rule, rule_module)| *rule == name && *rule_module == module)
+                    {
+                        rules.push((name, module.clone()));
+                    }
+                }
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Explain why the policy denies a request: re-evaluate each top level
+    /// rule of the currently loaded policy with the input of the denied
+    /// request, and report the rules that evaluated to false, together with
+    /// their print() output.
+    pub fn explain_denial(&mut self, ep: &str, input_json: &str) -> Result<String> {
+        let rules = self.list_rules()?;
+        let engine = self.engine()?;
+        engine.set_input_json(input_json)?;
+
+        let mut lines = Vec::new();
+        for (rule, _module) in rules {
+            let results = match engine.eval_query(format!("data.agent_policy.{rule}"), false) {
+                Ok(results) => results,
+                Err(e) => {
+                    lines.push(format!("Rule `{rule}` failed to evaluate: {e}"));
+                    continue;
+                }
+            };
+            let prints = match engine.take_prints() {
+                Ok(p) => p.join(" "),
+                Err(_) => String::new(),
+            };
+
+            let value = results
+                .result
+                .first()
+                .and_then(|r| r.expressions.first())
+                .map(|e| e.value.clone());
+            let failed = match value {
+                Some(regorus::Value::Bool(b)) => !b,
+                Some(regorus::Value::Object(obj)) => serde_json::to_string(&obj)
+                    .ok()
+                    .and_then(|json_str| serde_json::from_str::<MetadataResponse>(&json_str).ok())
+                    .is_some_and(|metadata_response| !metadata_response.allowed),
+                // Parameterized rules and rules of other value types can't be
+                // explained this way.
+                _ => rule == ep,
+            };
+
+            if failed {
+                if prints.is_empty() {
+                    lines.push(format!("Rule `{rule}` failed"));
+                } else {
+                    lines.push(format!("Rule `{rule}` failed: {prints}"));
+                }
+            }
+        }
+
+        if lines.is_empty() {
+            Ok(format!("the policy does not deny {ep} for this input"))
+        } else {
+            Ok(lines.join("\n"))
+        }
+    }
+
+    /// Verify that the policy engine can still evaluate a trivial query,
+    /// for liveness probes. Returns the version declared by the policy
+    /// through an optional PolicyVersion rule, falling back to the format
+    /// version of the loaded policy document.
+    pub fn check_health(&mut self) -> Result<String> {
+        if self.engine.is_none() {
+            bail!("policy health check: no policy has been loaded");
+        }
+
+        let engine = self.engine()?;
+        engine.set_input_json("{}")?;
+        let results = engine.eval_query("data.agent_policy.PolicyVersion".to_string(), false)?;
+        let _ = engine.take_prints();
+
+        let value = results
+            .result
+            .first()
+            .and_then(|r| r.expressions.first())
+            .map(|e| e.value.clone());
+        match value {
+            Some(regorus::Value::String(version)) => Ok(version.to_string()),
+            _ => Ok(self.policy_version.to_string()),
+        }
+    }
+
+    /// Persist the current policy text and format version to a JSON file, so
+    /// that the next agent start can restore the policy through
+    /// restore_from_disk() without fetching it from the host again.
+    ///
+    /// The file includes the policy hash, but because the hash is stored
+    /// next to the policy text it only detects accidental corruption of the
+    /// file - an attacker able to rewrite the policy can rewrite the hash
+    /// too. Tamper-proofing requires anchoring the expected hash outside of
+    /// the file, e.g. in a TEE measurement.
+    pub fn save_to_disk(&self, path: &Path) -> Result<()> {
+        let persisted = PersistedPolicy {
+            version: self.policy_version,
+            policy: self.policy_text.clone(),
+            hash: self.policy_hash.clone(),
+            algorithm: self.hash_algorithm,
+            truncation: self.hash_truncation,
+        };
+        std::fs::write(path, serde_json::to_string(&persisted)?)?;
+        Ok(())
+    }
+
+    /// Restore the policy persisted by save_to_disk(). The persisted policy
+    /// gets loaded only when the hash recorded in the file still matches the
+    /// persisted policy text. The hash lives in the same file as the policy,
+    /// so this check detects corruption only - see save_to_disk() - and the
+    /// file must be stored somewhere the host cannot write to, like the
+    /// guest-internal /run/kata-containers directory.
+    pub async fn restore_from_disk(&mut self, path: &Path) -> Result<()> {
+        let persisted: PersistedPolicy = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let hash = Self::policy_hash(
+            persisted.algorithm,
+            persisted.truncation,
+            persisted.version,
+            &persisted.policy,
+        );
+        if hash != persisted.hash {
+            bail!("the hash of the persisted policy does not match its text");
+        }
+        self.install_policy(persisted.version, &persisted.policy)
+    }
+
+    /// Load a shadow policy, for comparing its decisions with those of the
+    /// current policy. The shadow policy decisions are not enforced - any
+    /// divergence between the two policies just gets logged as a warning.
+    pub fn set_shadow_policy(&mut self, policy: &str) -> Result<()> {
+        let mut engine = Self::new_engine();
+        for (key, value) in &self.data_documents {
+            Self::add_data_to_engine(&mut engine, key, value)?;
+        }
+        engine.add_policy("agent_policy".to_string(), policy.to_string())?;
+        self.shadow_engine = Some(engine);
+        Ok(())
+    }
+
+    /// Remove the shadow policy.
+    pub fn clear_shadow_policy(&mut self) {
+        self.shadow_engine = None;
+    }
+
+    /// Load a secondary policy, for defense-in-depth deployments that
+    /// require two independently authored policies to agree on each
+    /// decision. Disagreements get logged and resolved as specified by
+    /// set_conflict_resolution().
+    pub fn set_secondary_policy(&mut self, policy: &str) -> Result<()> {
+        let mut engine = Self::new_engine();
+        for (key, value) in &self.data_documents {
+            Self::add_data_to_engine(&mut engine, key, value)?;
+        }
+        engine.add_policy("agent_policy".to_string(), policy.to_string())?;
+        self.secondary_engine = Some(engine);
+        Ok(())
+    }
+
+    /// Remove the secondary policy.
+    pub fn clear_secondary_policy(&mut self) {
+        self.secondary_engine = None;
+    }
+
+    /// Configure how to resolve a disagreement between the primary and the
+    /// secondary policy decisions.
+    pub fn set_conflict_resolution(&mut self, conflict_resolution: ConflictResolution) {
+        self.conflict_resolution = conflict_resolution;
+    }
+
+    /// Evaluate the endpoint input against the shadow policy. Returns None
+    /// when the shadow policy cannot produce a decision for this input.
+    fn shadow_allows(engine: &mut regorus::Engine, ep: &str, ep_input: &str) -> Option<bool> {
+        let query = format!("data.agent_policy.{ep}");
+        engine.set_input_json(ep_input).ok()?;
+
+        let results = engine.eval_query(query, false).ok()?;
+        let _ = engine.take_prints();
+
+        if results.result.len() != 1 || results.result[0].expressions.len() != 1 {
+            return None;
+        }
+
+        match &results.result[0].expressions[0].value {
+            regorus::Value::Bool(b) => Some(*b),
+            regorus::Value::Object(obj) => {
+                let json_str = serde_json::to_string(obj).ok()?;
+                let metadata_response: MetadataResponse = serde_json::from_str(&json_str).ok()?;
+                if metadata_response.allowed {
+                    if let Some(ops) = metadata_response.ops {
+                        Self::apply_patch_to_state(engine, ops).ok()?;
+                    }
+                }
+                Some(metadata_response.allowed)
+            }
+            _ => None,
+        }
+    }
+
+    /// Redirect the OPA print() output to a custom sink - e.g., a
+    /// structured logger - instead of embedding it into the denial
+    /// messages.
+    pub fn set_print_handler(&mut self, handler: Box<dyn Fn(String) + Send>) {
+        self.print_handler = Some(PrintHandler(handler));
+    }
+
+    /// Remove the custom print() sink, restoring the default behavior of
+    /// embedding the print() output into the denial messages.
+    pub fn clear_print_handler(&mut self) {
+        self.print_handler = None;
+    }
+
+    /// Route the print() output of an evaluation to the custom print
+    /// handler when one is set, or return it for embedding into the denial
+    /// message.
+    fn handle_prints(&self, prints: Vec<String>) -> String {
+        if let Some(PrintHandler(handler)) = &self.print_handler {
+            for print in prints {
+                handler(print);
+            }
+            String::new()
+        } else {
+            prints.join(" ")
+        }
+    }
+
+    /// Enable or disable collecting the names of the evaluated rules.
+    pub fn collect_coverage(&mut self, enable: bool) {
+        if enable {
+            self.coverage = Some(HashSet::new());
+        } else {
+            self.coverage = None;
+        }
+    }
+
+    /// Report which of the current policy rules have been evaluated since
+    /// coverage collection got enabled.
+    pub fn coverage_report(&self) -> CoverageReport {
+        let empty = HashSet::new();
+        let evaluated_rules = self.coverage.as_ref().unwrap_or(&empty);
+
+        let mut evaluated: Vec<String> = evaluated_rules.iter().cloned().collect();
+        evaluated.sort();
+
+        let mut not_evaluated: Vec<String> = self
+            .rule_names
+            .difference(evaluated_rules)
+            .cloned()
+            .collect();
+        not_evaluated.sort();
+
+        CoverageReport {
+            evaluated,
+            not_evaluated,
+        }
+    }
+
+    /// Write the coverage report to the policy log file.
+    pub async fn log_coverage_report(&mut self) {
+        if self.coverage.is_none() {
+            return;
+        }
+
+        let report = match serde_json::to_string(&self.coverage_report()) {
+            Ok(report) => report,
+            Err(e) => {
+                warn!(sl!(), "policy: failed to serialize coverage report: {}", e);
+                return;
+            }
+        };
+
+        if let Some(log_file) = &mut self.log_file {
+            let log_entry = format!("[\"coverage\":{report}],\n\n");
+
+            if let Err(e) = log_file.write_all(log_entry.as_bytes()).await {
+                warn!(
+                    sl!(),
+                    "policy: log_coverage_report: write_all failed: {}", e
+                );
+            } else if let Err(e) = log_file.flush().await {
+                warn!(sl!(), "policy: log_coverage_report: flush failed: {}", e);
+            }
+        }
+    }
+
+    /// Collect the names of the rules defined by the policy text - e.g.,
+    /// "CopyFileRequest" from a "default CopyFileRequest := false" line.
+    fn get_rule_names(policy: &str) -> HashSet<String> {
+        policy
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("default "))
+            .filter_map(|rule| rule.split_whitespace().next())
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    /// Add an external data document under the given key of the OPA "data"
+    /// namespace, for the policy rules to reference - e.g., as
+    /// data.agent_config for the "agent_config" key.
+    pub fn add_data(&mut self, key: &str, value: serde_json::Value) -> Result<()> {
+        if self.engine.is_some() {
+            Self::add_data_to_engine(self.engine()?, key, &value)?;
+        }
+        self.data_documents.push((key.to_string(), value));
+        Ok(())
+    }
+
+    fn add_data_to_engine(
+        engine: &mut regorus::Engine,
+        key: &str,
+        value: &serde_json::Value,
+    ) -> Result<()> {
+        let mut document = serde_json::Map::new();
+        document.insert(key.to_string(), value.clone());
+        engine.add_data(regorus::Value::from_json_str(
+            &serde_json::Value::Object(document).to_string(),
+        )?)
+    }
+
+    /// Authenticate future configure_bundle_server() requests with this
+    /// bearer token.
+    pub fn set_bundle_server_token(&mut self, token: &str) {
+        self.bundle_server_token = Some(token.to_string());
+    }
+
+    /// Start a background task that periodically fetches an OPA data bundle
+    /// from an external bundle server. Each fetched bundle gets added to the
+    /// engine under the "bundle" key of the OPA "data" namespace, allowing
+    /// policy data - e.g., allowed image lists - to be updated dynamically,
+    /// without changing the policy rules themselves.
+    pub fn configure_bundle_server(&mut self, url: &str, polling_interval: Duration) -> Result<()> {
+        if self.bundle_rx.is_some() {
+            bail!("a bundle server has been configured already");
+        }
+
+        info!(
+            sl!(),
+            "policy: fetching data bundles from {url} every {polling_interval:?}"
+        );
+
+        let (tx, rx) = tokio::sync::watch::channel(None);
+        let url = url.to_string();
+        let token = self.bundle_server_token.clone();
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            loop {
+                tokio::time::sleep(polling_interval).await;
+                match Self::fetch_bundle(&client, &url, token.as_deref()).await {
+                    Ok(bundle) => {
+                        if tx.send(Some(bundle)).is_err() {
+                            // The policy object dropped its receiver.
+                            break;
+                        }
+                    }
+                    Err(e) => warn!(sl!(), "policy: failed to fetch data bundle: {e}"),
+                }
+            }
+        });
+
+        self.bundle_rx = Some(rx);
+        Ok(())
+    }
+
+    async fn fetch_bundle(
+        client: &reqwest::Client,
+        url: &str,
+        token: Option<&str>,
+    ) -> Result<String> {
+        let mut request = client.get(url);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            bail!("the bundle server returned {}", response.status());
+        }
+
+        Ok(response.text().await?)
+    }
+
+    /// Apply the most recent data bundle fetched from the bundle server, if
+    /// it hasn't been applied yet. A bundle that can't be applied gets
+    /// logged and ignored, keeping the data of the previous bundle active.
+    fn apply_pending_bundle(&mut self) {
+        let Some(bundle_rx) = &mut self.bundle_rx else {
+            return;
+        };
+        if !bundle_rx.has_changed().unwrap_or(false) {
+            return;
+        }
+        let Some(bundle) = bundle_rx.borrow_and_update().clone() else {
+            return;
+        };
+
+        if let Err(e) = self.apply_bundle(&bundle) {
+            warn!(sl!(), "policy: failed to apply data bundle: {e}");
+        }
+    }
+
+    fn apply_bundle(&mut self, bundle: &str) -> Result<()> {
+        let value: serde_json::Value = serde_json::from_str(bundle)?;
+
+        // Replace the data document of any previously fetched bundle, both
+        // in the engine and in the documents replayed by set_policy().
+        self.data_documents
+            .retain(|(key, _)| key.as_str() != BUNDLE_DATA_KEY);
+        if let Some(engine) = &mut self.engine {
+            let mut state = serde_json::to_value(engine.get_data())?;
+            if let Some(state_object) = state.as_object_mut() {
+                state_object.insert(BUNDLE_DATA_KEY.to_string(), value.clone());
+            }
+            engine.clear_data();
+            engine.add_data(regorus::Value::from_json_str(&state.to_string())?)?;
+        }
+        self.data_documents
+            .push((BUNDLE_DATA_KEY.to_string(), value));
+
+        debug!(sl!(), "policy: applied a new data bundle");
         Ok(())
     }
 
@@ -212,6 +1098,12 @@ impl AgentPolicy {
                     //   large, so we're not logging them here, for SetPolicyRequest.
                     //   The Policy text can be obtained directly from the pod YAML.
                 }
+                "GuestDetailsRequest" if self.allow_failures => {
+                    // GuestDetailsRequest is called relatively often by debug
+                    // configurations that enable AllowRequestsFailingPolicy, so
+                    // skip logging it in that case. Secure configurations still
+                    // log this request and evaluate it against the Policy.
+                }
                 _ => {
                     let log_entry = format!("[\"ep\":\"{ep}\",{input}],\n\n");
 
@@ -241,3 +1133,107 @@ impl AgentPolicy {
         Ok(())
     }
 }
+
+/// Parse the name of a top level rule defined by a policy source line,
+/// if any.
+fn top_level_rule_name(line: &str) -> Option<String> {
+    if line.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let line = line.strip_prefix("default ").unwrap_or(line).trim_start();
+    let name_len = line
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(line.len());
+    let (name, rest) = line.split_at(name_len);
+    if name.is_empty() || name == "package" || name == "import" {
+        return None;
+    }
+
+    let rest = rest.trim_start();
+    if rest.starts_with("if")
+        || rest.starts_with(":=")
+        || rest.starts_with('=')
+        || rest.starts_with('{')
+        || rest.starts_with('(')
+    {
+        Some(name.to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AgentPolicy, PolicyHashAlgorithm};
+
+    #[test]
+    fn extract_policy_text_accepts_bare_rego() {
+        let policy = "package agent_policy\n\ndefault CopyFileRequest := false\n";
+        let (version, text) = AgentPolicy::extract_policy_text(policy).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(text, policy);
+    }
+
+    #[test]
+    fn extract_policy_text_accepts_version_1_envelope() {
+        let envelope = r#"{
+            "version": 1,
+            "policy": "package agent_policy\n",
+            "metadata": { "generator": "genpolicy" }
+        }"#;
+        let (version, text) = AgentPolicy::extract_policy_text(envelope).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(text, "package agent_policy\n");
+    }
+
+    #[test]
+    fn extract_policy_text_rejects_malformed_envelopes() {
+        // A document starting with '{' cannot be a Rego module, so it must
+        // parse as an envelope instead of getting loaded as bare Rego text.
+        let e = AgentPolicy::extract_policy_text(r#"{ "verson": 1, "policy": "" }"#).unwrap_err();
+        assert!(e.to_string().contains("invalid policy envelope"));
+
+        let e = AgentPolicy::extract_policy_text("{ not json").unwrap_err();
+        assert!(e.to_string().contains("invalid policy envelope"));
+    }
+
+    #[test]
+    fn extract_policy_text_rejects_unsupported_envelope_versions() {
+        let envelope = r#"{ "version": 2, "policy": "package agent_policy\n" }"#;
+        let e = AgentPolicy::extract_policy_text(envelope).unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("unsupported policy envelope version 2"));
+    }
+
+    #[test]
+    fn policy_hash_covers_the_format_version() {
+        let policy = "package agent_policy\n";
+        let v0 = AgentPolicy::policy_hash(PolicyHashAlgorithm::Sha256, None, 0, policy);
+        let v1 = AgentPolicy::policy_hash(PolicyHashAlgorithm::Sha256, None, 1, policy);
+        assert_eq!(v0.len(), 32);
+        assert_ne!(v0, v1);
+        assert_eq!(
+            v0,
+            AgentPolicy::policy_hash(PolicyHashAlgorithm::Sha256, None, 0, policy)
+        );
+    }
+
+    #[test]
+    fn policy_hash_algorithms_produce_distinct_32_byte_hashes() {
+        let policy = "package agent_policy\n";
+        let sha = AgentPolicy::policy_hash(PolicyHashAlgorithm::Sha256, None, 0, policy);
+        let blake = AgentPolicy::policy_hash(PolicyHashAlgorithm::Blake2s256, None, 0, policy);
+        assert_eq!(sha.len(), 32);
+        assert_eq!(blake.len(), 32);
+        assert_ne!(sha, blake);
+    }
+
+    #[test]
+    fn policy_hash_truncation_keeps_the_hash_prefix() {
+        let policy = "package agent_policy\n";
+        let full = AgentPolicy::policy_hash(PolicyHashAlgorithm::Sha256, None, 0, policy);
+        let truncated = AgentPolicy::policy_hash(PolicyHashAlgorithm::Sha256, Some(16), 0, policy);
+        assert_eq!(truncated, full[..16]);
+    }
+}
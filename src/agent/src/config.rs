@@ -34,6 +34,8 @@ const CONFIG_FILE: &str = "agent.config_file";
 const GUEST_COMPONENTS_REST_API_OPTION: &str = "agent.guest_components_rest_api";
 const GUEST_COMPONENTS_PROCS_OPTION: &str = "agent.guest_components_procs";
 const SECURE_STORAGE_INTEGRITY_OPTION: &str = "agent.secure_storage_integrity";
+const RPC_RATE_LIMIT_OPTION: &str = "agent.rpc_rate_limit";
+const RPC_AUDIT_OPTION: &str = "agent.rpc_audit";
 
 // Configure the proxy settings for HTTPS requests in the guest,
 // to solve the problem of not being able to access the specified image in some cases.
@@ -67,6 +69,10 @@ const DEFAULT_CDH_API_TIMEOUT: time::Duration = time::Duration::from_secs(50);
 const DEFAULT_IMAGE_PULL_TIMEOUT: time::Duration = time::Duration::from_secs(1200);
 const DEFAULT_CDI_TIMEOUT: time::Duration = time::Duration::from_secs(100);
 const DEFAULT_CONTAINER_PIPE_SIZE: i32 = 0;
+#[cfg(feature = "agent-policy")]
+const DEFAULT_POLICY_REATTEST_INTERVAL: time::Duration = time::Duration::from_secs(300);
+#[cfg(feature = "agent-policy")]
+const DEFAULT_POLICY_BACKEND: &str = "regorus";
 const VSOCK_ADDR: &str = "vsock://-1";
 
 // Environment variables used for development and testing
@@ -121,6 +127,21 @@ pub enum GuestComponentsProcs {
     ConfidentialDataHub,
 }
 
+/// Action applied to the running containers when the periodic policy
+/// re-attestation check fails. The agent also switches to a deny-all
+/// policy, so no new requests get allowed regardless of the action.
+#[cfg(feature = "agent-policy")]
+#[derive(Clone, Copy, Debug, Default, Display, Deserialize, EnumString, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum PolicyReattestAction {
+    /// Just log a warning.
+    #[default]
+    Log,
+    PauseContainers,
+    KillContainers,
+}
+
 #[derive(Debug)]
 pub struct AgentConfig {
     pub debug_console: bool,
@@ -143,8 +164,21 @@ pub struct AgentConfig {
     pub guest_components_rest_api: GuestComponentsFeatures,
     pub guest_components_procs: GuestComponentsProcs,
     pub secure_storage_integrity: bool,
+    /// Maximum number of requests per second accepted for each ttrpc
+    /// endpoint, or 0 to disable rate limiting.
+    pub rpc_rate_limit: u32,
+    /// Audit-log the admission decision of each ttrpc request.
+    pub rpc_audit: bool,
     #[cfg(feature = "agent-policy")]
     pub policy_file: String,
+    #[cfg(feature = "agent-policy")]
+    pub data_files: Vec<std::path::PathBuf>,
+    #[cfg(feature = "agent-policy")]
+    pub policy_reattest_interval: time::Duration,
+    #[cfg(feature = "agent-policy")]
+    pub policy_reattest_action: PolicyReattestAction,
+    #[cfg(feature = "agent-policy")]
+    pub policy_backend: String,
     pub mem_agent: Option<MemAgentConfig>,
 }
 
@@ -175,8 +209,18 @@ pub struct AgentConfigBuilder {
     pub guest_components_rest_api: Option<GuestComponentsFeatures>,
     pub guest_components_procs: Option<GuestComponentsProcs>,
     pub secure_storage_integrity: Option<bool>,
+    pub rpc_rate_limit: Option<u32>,
+    pub rpc_audit: Option<bool>,
     #[cfg(feature = "agent-policy")]
     pub policy_file: Option<String>,
+    #[cfg(feature = "agent-policy")]
+    pub data_files: Option<Vec<std::path::PathBuf>>,
+    #[cfg(feature = "agent-policy")]
+    pub policy_reattest_interval: Option<time::Duration>,
+    #[cfg(feature = "agent-policy")]
+    pub policy_reattest_action: Option<PolicyReattestAction>,
+    #[cfg(feature = "agent-policy")]
+    pub policy_backend: Option<String>,
     pub mem_agent_enable: Option<bool>,
     pub mem_agent_memcg_disable: Option<bool>,
     pub mem_agent_memcg_swap: Option<bool>,
@@ -270,8 +314,18 @@ impl Default for AgentConfig {
             guest_components_rest_api: GuestComponentsFeatures::default(),
             guest_components_procs: GuestComponentsProcs::default(),
             secure_storage_integrity: false,
+            rpc_rate_limit: 0,
+            rpc_audit: false,
             #[cfg(feature = "agent-policy")]
             policy_file: String::from(""),
+            #[cfg(feature = "agent-policy")]
+            data_files: Vec::new(),
+            #[cfg(feature = "agent-policy")]
+            policy_reattest_interval: DEFAULT_POLICY_REATTEST_INTERVAL,
+            #[cfg(feature = "agent-policy")]
+            policy_reattest_action: PolicyReattestAction::default(),
+            #[cfg(feature = "agent-policy")]
+            policy_backend: DEFAULT_POLICY_BACKEND.to_string(),
             mem_agent: None,
         }
     }
@@ -314,10 +368,24 @@ impl FromStr for AgentConfig {
         );
         config_override!(agent_config_builder, agent_config, guest_components_procs);
         config_override!(agent_config_builder, agent_config, secure_storage_integrity);
+        config_override!(agent_config_builder, agent_config, rpc_rate_limit);
+        config_override!(agent_config_builder, agent_config, rpc_audit);
 
         #[cfg(feature = "agent-policy")]
         config_override!(agent_config_builder, agent_config, policy_file);
 
+        #[cfg(feature = "agent-policy")]
+        config_override!(agent_config_builder, agent_config, data_files);
+
+        #[cfg(feature = "agent-policy")]
+        config_override!(agent_config_builder, agent_config, policy_reattest_interval);
+
+        #[cfg(feature = "agent-policy")]
+        config_override!(agent_config_builder, agent_config, policy_reattest_action);
+
+        #[cfg(feature = "agent-policy")]
+        config_override!(agent_config_builder, agent_config, policy_backend);
+
         if agent_config_builder.mem_agent_enable.unwrap_or(false) {
             let mut mac = MemAgentConfig::default();
 
@@ -542,6 +610,13 @@ impl AgentConfig {
                 config.secure_storage_integrity,
                 get_bool_value
             );
+            parse_cmdline_param!(
+                param,
+                RPC_RATE_LIMIT_OPTION,
+                config.rpc_rate_limit,
+                get_number_value
+            );
+            parse_cmdline_param!(param, RPC_AUDIT_OPTION, config.rpc_audit, get_bool_value);
 
             parse_cmdline_param!(param, MEM_AGENT_ENABLE, mem_agent_enable, get_bool_value);
 
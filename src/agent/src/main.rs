@@ -46,6 +46,7 @@ mod features;
 mod initdata;
 mod linux_abi;
 mod metrics;
+mod middleware;
 mod mount;
 mod namespace;
 mod netlink;
@@ -121,11 +122,6 @@ lazy_static! {
         AgentConfig::from_cmdline("/proc/cmdline", env::args().collect()).unwrap();
 }
 
-#[cfg(feature = "agent-policy")]
-lazy_static! {
-    static ref AGENT_POLICY: Mutex<AgentPolicy> = Mutex::new(AgentPolicy::new());
-}
-
 #[derive(Parser)]
 // The default clap version info doesn't match our form, so we need to override it
 #[clap(disable_version_flag = true)]
@@ -382,7 +378,10 @@ async fn start_sandbox(
     }
 
     #[cfg(feature = "agent-policy")]
-    if let Err(e) = initialize_policy().await {
+    let agent_policy = policy::AgentPolicyHandle::new();
+
+    #[cfg(feature = "agent-policy")]
+    if let Err(e) = initialize_policy(&agent_policy).await {
         error!(logger, "Failed to initialize agent policy: {:?}", e);
         // Continuing execution without a security policy could be dangerous.
         std::process::abort();
@@ -390,6 +389,16 @@ async fn start_sandbox(
 
     let sandbox = Arc::new(Mutex::new(s));
 
+    #[cfg(feature = "agent-policy")]
+    {
+        tokio::spawn(
+            agent_policy
+                .clone()
+                .attestation_poller(logger.clone(), sandbox.clone()),
+        );
+        tokio::spawn(agent_policy.clone().persist_on_sigterm(logger.clone()));
+    }
+
     let signal_handler_task = tokio::spawn(setup_signal_handler(
         logger.clone(),
         sandbox.clone(),
@@ -422,12 +431,20 @@ async fn start_sandbox(
     if let Some(initdata_return_value) = initdata_return_value {
         if let Some(policy) = &initdata_return_value._policy {
             info!(logger, "using policy from initdata");
-            AGENT_POLICY
-                .lock()
-                .await
+            let mut agent_policy = agent_policy.write().await;
+            agent_policy
                 .set_policy(policy)
                 .await
                 .context("Failed to set policy from initdata")?;
+
+            // Refresh the reference copy verified by the attestation
+            // poller, so a policy persisted by an earlier agent instance
+            // cannot flag the initdata policy as divergence.
+            if let Err(e) =
+                agent_policy.save_to_disk(std::path::Path::new(policy::POLICY_PERSIST_FILE))
+            {
+                warn!(logger, "failed to persist the initdata policy: {e}");
+            }
         }
     }
 
@@ -445,15 +462,34 @@ async fn start_sandbox(
         _ort = Some(rt);
     }
 
+    let mut service_builder = rpc::AgentServiceBuilder::new()
+        .sandbox(sandbox.clone())
+        .init_mode(init_mode)
+        .oma(oma);
+    if config.rpc_rate_limit > 0 {
+        service_builder =
+            service_builder.middleware(Box::new(middleware::RateLimitMiddleware::new(
+                config.rpc_rate_limit,
+                middleware::DEFAULT_RATE_WINDOW,
+            )));
+    }
+    if config.rpc_audit {
+        service_builder = service_builder.middleware(Box::new(middleware::AuditLogMiddleware));
+    }
+    #[cfg(feature = "agent-policy")]
+    let service_builder = service_builder.policy(agent_policy.clone());
+
     // vsock:///dev/vsock, port
-    let mut server =
-        rpc::start(sandbox.clone(), config.server_addr.as_str(), init_mode, oma).await?;
+    let mut server = rpc::start(service_builder, config.server_addr.as_str()).await?;
 
     server.start().await?;
 
     rx.await?;
     server.shutdown().await?;
 
+    #[cfg(feature = "agent-policy")]
+    agent_policy.write().await.log_coverage_report().await;
+
     Ok(())
 }
 
@@ -680,16 +716,59 @@ fn init_agent_as_init(logger: &Logger, unified_cgroup_hierarchy: bool) -> Result
 }
 
 #[cfg(feature = "agent-policy")]
-async fn initialize_policy() -> Result<()> {
-    AGENT_POLICY
-        .lock()
-        .await
-        .initialize(
-            AGENT_CONFIG.log_level.as_usize(),
-            AGENT_CONFIG.policy_file.clone(),
-            None,
-        )
-        .await
+async fn initialize_policy(agent_policy: &policy::AgentPolicyHandle) -> Result<()> {
+    let mut policy = agent_policy.write().await;
+
+    if AGENT_CONFIG.policy_backend == "regorus" {
+        policy
+            .initialize(
+                AGENT_CONFIG.log_level.as_usize(),
+                AGENT_CONFIG.policy_file.clone(),
+                None,
+            )
+            .await?;
+    } else {
+        // An alternative backend evaluates the policy file in its own
+        // policy format - e.g., a compiled OPA WASM bundle.
+        policy
+            .initialize_backend(&AGENT_CONFIG.policy_backend, &AGENT_CONFIG.policy_file)
+            .await?;
+    }
+
+    // Allow the policy rules to reference a few agent settings as
+    // data.agent_config.
+    policy::set_agent_config(&mut policy, &AGENT_CONFIG)?;
+
+    // Add any external data documents listed in the agent configuration.
+    for data_file in &AGENT_CONFIG.data_files {
+        let data: serde_json::Value = serde_json::from_str(&fs::read_to_string(data_file)?)?;
+        let serde_json::Value::Object(document) = data else {
+            bail!(
+                "policy data file {} is not a JSON object",
+                data_file.display()
+            );
+        };
+        for (key, value) in document {
+            policy.add_data(&key, value)?;
+        }
+    }
+
+    // Restore the policy persisted by an earlier agent instance, if any.
+    // The restored policy replaces the default policy only when the hash
+    // recorded in the persisted file still matches the persisted policy
+    // text. Restore failures are not fatal - the default policy loaded
+    // above remains active.
+    let persist_path = std::path::Path::new(policy::POLICY_PERSIST_FILE);
+    if persist_path.exists() {
+        if let Err(e) = policy.restore_from_disk(persist_path).await {
+            warn!(
+                slog_scope::logger(),
+                "failed to restore the persisted policy: {e}"
+            );
+        }
+    }
+
+    Ok(())
 }
 
 // The Rust standard library had suppressed the default SIGPIPE behavior,
@@ -706,9 +785,6 @@ fn reset_sigpipe() {
 use crate::config::AgentConfig;
 use std::os::unix::io::{FromRawFd, RawFd};
 
-#[cfg(feature = "agent-policy")]
-use kata_agent_policy::policy::AgentPolicy;
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,211 @@
+// Copyright (c) 2024 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Middleware chain for the cross-cutting concerns of the ttrpc request
+//! handlers - policy checks, rate limiting and audit logging - so that the
+//! handlers run all of them through a single before_request() call.
+
+use async_trait::async_trait;
+use slog::{debug, info};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::rpc::ttrpc_error;
+
+// Convenience function to obtain the scope logger.
+fn sl() -> slog::Logger {
+    slog_scope::logger()
+}
+
+/// Length of the rate limiting window.
+pub const DEFAULT_RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Endpoints exempt from rate limiting. The stream RPCs are driven by the
+/// I/O of the workload itself, so their request rate is not an indication
+/// of a runaway client.
+const RATE_LIMIT_EXEMPT_ENDPOINTS: &[&str] = &["ReadStreamRequest", "WriteStreamRequest"];
+
+/// Endpoints audit-logged at debug level instead of info, because healthy
+/// workloads send them continuously.
+const AUDIT_HIGH_FREQUENCY_ENDPOINTS: &[&str] = &[
+    "ReadStreamRequest",
+    "WriteStreamRequest",
+    "StatsContainerRequest",
+];
+
+/// A cross-cutting concern applied to every request routed through the
+/// middleware chain.
+#[async_trait]
+pub trait RpcMiddleware: Send + Sync {
+    /// Inspect a request before its handler runs. Returning an error fails
+    /// the request without running its handler.
+    async fn before_request(&self, ep: &str, req_bytes: &[u8]) -> ttrpc::Result<()>;
+
+    /// Report whether this middleware reads the request bytes passed to
+    /// before_request(). When no middleware in the chain does, the callers
+    /// skip serializing the request and pass empty bytes instead.
+    fn needs_request_body(&self) -> bool {
+        false
+    }
+
+    /// Observe whether the chain admitted a request. This runs right after
+    /// the before_request() checks - i.e., before the request handler - so
+    /// it reports the admission decision, not the handler outcome.
+    async fn after_request(&self, ep: &str, allowed: bool);
+}
+
+/// Ordered list of middlewares applied to every request. The first
+/// middleware that fails a request stops the chain.
+#[derive(Default)]
+pub struct MiddlewareChain {
+    middlewares: Vec<Box<dyn RpcMiddleware>>,
+}
+
+impl std::fmt::Debug for MiddlewareChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MiddlewareChain({} middlewares)", self.middlewares.len())
+    }
+}
+
+impl MiddlewareChain {
+    /// Append a middleware to the chain.
+    pub fn push(&mut self, middleware: Box<dyn RpcMiddleware>) {
+        self.middlewares.push(middleware);
+    }
+
+    /// Run the before_request() check of each middleware, in the order the
+    /// middlewares were added.
+    pub async fn before_request(&self, ep: &str, req_bytes: &[u8]) -> ttrpc::Result<()> {
+        for middleware in &self.middlewares {
+            middleware.before_request(ep, req_bytes).await?;
+        }
+        Ok(())
+    }
+
+    /// Report the admission decision of the chain to each middleware.
+    pub async fn after_request(&self, ep: &str, allowed: bool) {
+        for middleware in &self.middlewares {
+            middleware.after_request(ep, allowed).await;
+        }
+    }
+
+    /// Return true when at least one middleware in the chain reads the
+    /// request bytes.
+    pub fn needs_request_body(&self) -> bool {
+        self.middlewares.iter().any(|m| m.needs_request_body())
+    }
+}
+
+/// Middleware evaluating each request against the agent policy.
+#[cfg(feature = "agent-policy")]
+pub struct PolicyCheckMiddleware {
+    policy: crate::policy::AgentPolicyHandle,
+}
+
+#[cfg(feature = "agent-policy")]
+impl PolicyCheckMiddleware {
+    pub fn new(policy: crate::policy::AgentPolicyHandle) -> Self {
+        Self { policy }
+    }
+}
+
+#[cfg(feature = "agent-policy")]
+#[async_trait]
+impl RpcMiddleware for PolicyCheckMiddleware {
+    async fn before_request(&self, ep: &str, req_bytes: &[u8]) -> ttrpc::Result<()> {
+        let request = std::str::from_utf8(req_bytes).map_err(|e| {
+            ttrpc_error(
+                ttrpc::Code::INVALID_ARGUMENT,
+                format!("{ep}: the request input is not valid UTF-8: {e}"),
+            )
+        })?;
+        self.policy.is_allowed_json(ep, request).await
+    }
+
+    fn needs_request_body(&self) -> bool {
+        true
+    }
+
+    async fn after_request(&self, _ep: &str, _allowed: bool) {}
+}
+
+/// Middleware rejecting requests beyond a fixed rate, to protect the agent
+/// against a runaway client. Each endpoint gets its own counting window, so
+/// one noisy endpoint doesn't starve the others, and the stream RPCs are
+/// exempt altogether.
+pub struct RateLimitMiddleware {
+    limit: u32,
+    window: Duration,
+    state: Mutex<HashMap<String, RateLimitState>>,
+}
+
+struct RateLimitState {
+    window_start: Instant,
+    count: u32,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            state: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl RpcMiddleware for RateLimitMiddleware {
+    async fn before_request(&self, ep: &str, _req_bytes: &[u8]) -> ttrpc::Result<()> {
+        if RATE_LIMIT_EXEMPT_ENDPOINTS.contains(&ep) {
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().await;
+        let state = state
+            .entry(ep.to_string())
+            .or_insert_with(|| RateLimitState {
+                window_start: Instant::now(),
+                count: 0,
+            });
+        if state.window_start.elapsed() >= self.window {
+            state.window_start = Instant::now();
+            state.count = 0;
+        }
+
+        state.count += 1;
+        if state.count > self.limit {
+            return Err(ttrpc_error(
+                ttrpc::Code::RESOURCE_EXHAUSTED,
+                format!("{ep} is rate limited"),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn after_request(&self, _ep: &str, _allowed: bool) {}
+}
+
+/// Middleware logging each request and whether the middleware chain
+/// admitted it. The log lines record the admission decision, made before
+/// the request handler runs - not whether the handler itself succeeded.
+pub struct AuditLogMiddleware;
+
+#[async_trait]
+impl RpcMiddleware for AuditLogMiddleware {
+    async fn before_request(&self, ep: &str, _req_bytes: &[u8]) -> ttrpc::Result<()> {
+        debug!(sl(), "audit: request received"; "ep" => ep);
+        Ok(())
+    }
+
+    async fn after_request(&self, ep: &str, allowed: bool) {
+        if AUDIT_HIGH_FREQUENCY_ENDPOINTS.contains(&ep) {
+            debug!(sl(), "audit: request admission decided"; "ep" => ep, "allowed" => allowed);
+        } else {
+            info!(sl(), "audit: request admission decided"; "ep" => ep, "allowed" => allowed);
+        }
+    }
+}
@@ -3,12 +3,56 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use protobuf::MessageDyn;
+use anyhow::Result;
+use slog::{crit, info, warn};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
+use crate::config::AgentConfig;
 use crate::rpc::ttrpc_error;
-use crate::AGENT_POLICY;
+use crate::AGENT_CONFIG;
 use kata_agent_policy::policy::AgentPolicy;
 
+/// Cloneable handle to an agent policy instance. Each agent instance owns one
+/// handle, created in main and threaded through the components that evaluate
+/// or update the policy - replacing the previous process-wide policy
+/// singleton, so that tests can create isolated policy instances.
+#[derive(Clone, Debug, Default)]
+pub struct AgentPolicyHandle {
+    policy: Arc<RwLock<AgentPolicy>>,
+
+    /// Sequence number of the latest policy update started through this
+    /// handle. Used for aborting a policy update when another update starts
+    /// concurrently, instead of applying the two updates in an
+    /// unpredictable order.
+    update_seq: Arc<AtomicU64>,
+}
+
+/// The agent configuration settings that the policy rules can reference as
+/// data.agent_config - e.g., data.agent_config.hotplug_timeout.
+#[derive(serde::Serialize)]
+struct PolicyAgentConfig {
+    dev_mode: bool,
+    /// The hotplug timeout, in seconds.
+    hotplug_timeout: u64,
+    container_pipe_size: i32,
+    secure_storage_integrity: bool,
+}
+
+/// Make the relevant agent configuration settings, read from the kernel
+/// command line and/or the agent configuration file, available to the policy
+/// rules as the data.agent_config document.
+pub fn set_agent_config(policy: &mut AgentPolicy, config: &AgentConfig) -> Result<()> {
+    let agent_config = PolicyAgentConfig {
+        dev_mode: config.dev_mode,
+        hotplug_timeout: config.hotplug_timeout.as_secs(),
+        container_pipe_size: config.container_pipe_size,
+        secure_storage_integrity: config.secure_storage_integrity,
+    };
+    policy.add_data("agent_config", serde_json::to_value(&agent_config)?)
+}
+
 async fn allow_request(policy: &mut AgentPolicy, ep: &str, request: &str) -> ttrpc::Result<()> {
     match policy.allow_request(ep, request).await {
         Ok((allowed, prints)) => {
@@ -28,18 +72,426 @@ async fn allow_request(policy: &mut AgentPolicy, ep: &str, request: &str) -> ttr
     }
 }
 
-pub async fn is_allowed(req: &(impl MessageDyn + serde::Serialize)) -> ttrpc::Result<()> {
-    let request = serde_json::to_string(req).unwrap();
-    let mut policy = AGENT_POLICY.lock().await;
-    allow_request(&mut policy, req.descriptor_dyn().name(), &request).await
+/// The CreateSandboxRequest fields checked by the policy. The sandbox_id field
+/// is not included because its value is not known during policy generation.
+#[derive(serde::Serialize)]
+struct PolicyCreateSandboxRequest<'a> {
+    hostname: &'a str,
+    dns: &'a [String],
+    storages: &'a [protocols::agent::Storage],
+    sandbox_pidns: bool,
+    guest_hook_path: &'a str,
+    kernel_modules: &'a [protocols::agent::KernelModule],
+}
+
+/// The SetGuestDateTimeRequest fields checked by the policy.
+#[derive(serde::Serialize)]
+struct PolicySetGuestDateTimeRequest {
+    seconds: i64,
+    /// The microseconds portion of the requested time.
+    microseconds: i64,
+}
+
+/// The WaitProcessRequest fields checked by the policy.
+#[derive(serde::Serialize)]
+struct PolicyWaitProcessRequest<'a> {
+    container_id: &'a str,
+    exec_id: &'a str,
+}
+
+/// The Route fields checked by the policy.
+#[derive(serde::Serialize)]
+struct PolicyRoute<'a> {
+    dest: &'a str,
+    gateway: &'a str,
+    device: &'a str,
+    source: &'a str,
+}
+
+/// The UpdateRoutesRequest fields checked by the policy, preserving the
+/// nested shape of the proto message.
+#[derive(serde::Serialize)]
+struct PolicyUpdateRoutesRequest<'a> {
+    routes: PolicyRoutes<'a>,
+}
+
+#[derive(serde::Serialize)]
+struct PolicyRoutes<'a> {
+    #[serde(rename = "Routes")]
+    routes: Vec<PolicyRoute<'a>>,
+}
+
+/// The Interface fields checked by the policy.
+#[derive(serde::Serialize)]
+struct PolicyInterface<'a> {
+    name: &'a str,
+    mtu: u64,
+    #[serde(rename = "hwAddr")]
+    hw_addr: &'a str,
+    #[serde(rename = "IPAddresses")]
+    ip_addresses: Vec<String>,
+    raw_flags: u32,
+}
+
+/// The UpdateInterfaceRequest fields checked by the policy, preserving the
+/// nested shape of the proto message.
+#[derive(serde::Serialize)]
+struct PolicyUpdateInterfaceRequest<'a> {
+    interface: PolicyInterface<'a>,
+}
+
+/// The OnlineCPUMemRequest fields checked by the policy.
+#[derive(serde::Serialize)]
+struct PolicyOnlineCPUMemRequest {
+    wait: bool,
+    nb_cpus: u32,
+}
+
+/// The MemHotplugByProbeRequest fields checked by the policy - just the probe
+/// address, to avoid serializing any other hotplug data into the policy input.
+#[derive(serde::Serialize)]
+struct PolicyMemHotplugRequest {
+    probe_address: u64,
+}
+
+/// Serialize just the CreateSandboxRequest fields checked by the policy, as
+/// the middleware chain input for that endpoint.
+pub fn trim_create_sandbox(req: &protocols::agent::CreateSandboxRequest) -> String {
+    let policy_req = PolicyCreateSandboxRequest {
+        hostname: &req.hostname,
+        dns: &req.dns,
+        storages: &req.storages,
+        sandbox_pidns: req.sandbox_pidns,
+        guest_hook_path: &req.guest_hook_path,
+        kernel_modules: &req.kernel_modules,
+    };
+    serde_json::to_string(&policy_req).unwrap()
+}
+
+/// Serialize just the SetGuestDateTimeRequest fields checked by the policy.
+pub fn trim_set_datetime(req: &protocols::agent::SetGuestDateTimeRequest) -> String {
+    let policy_req = PolicySetGuestDateTimeRequest {
+        seconds: req.Sec,
+        microseconds: req.Usec,
+    };
+    serde_json::to_string(&policy_req).unwrap()
+}
+
+/// Serialize just the WaitProcessRequest fields checked by the policy.
+pub fn trim_wait_process(req: &protocols::agent::WaitProcessRequest) -> String {
+    let policy_req = PolicyWaitProcessRequest {
+        container_id: &req.container_id,
+        exec_id: &req.exec_id,
+    };
+    serde_json::to_string(&policy_req).unwrap()
+}
+
+/// Serialize just the UpdateRoutesRequest fields checked by the policy.
+pub fn trim_update_routes(req: &protocols::agent::UpdateRoutesRequest) -> String {
+    let policy_req = PolicyUpdateRoutesRequest {
+        routes: PolicyRoutes {
+            routes: req
+                .routes
+                .Routes
+                .iter()
+                .map(|route| PolicyRoute {
+                    dest: &route.dest,
+                    gateway: &route.gateway,
+                    device: &route.device,
+                    source: &route.source,
+                })
+                .collect(),
+        },
+    };
+    serde_json::to_string(&policy_req).unwrap()
 }
 
-pub async fn do_set_policy(req: &protocols::agent::SetPolicyRequest) -> ttrpc::Result<()> {
-    let request = serde_json::to_string(req).unwrap();
-    let mut policy = AGENT_POLICY.lock().await;
-    allow_request(&mut policy, "SetPolicyRequest", &request).await?;
-    policy
-        .set_policy(&req.policy)
-        .await
-        .map_err(|e| ttrpc_error(ttrpc::Code::INVALID_ARGUMENT, e))
+/// Serialize just the UpdateInterfaceRequest fields checked by the policy.
+pub fn trim_update_interface(req: &protocols::agent::UpdateInterfaceRequest) -> String {
+    let policy_req = PolicyUpdateInterfaceRequest {
+        interface: PolicyInterface {
+            name: &req.interface.name,
+            mtu: req.interface.mtu,
+            hw_addr: &req.interface.hwAddr,
+            ip_addresses: req
+                .interface
+                .IPAddresses
+                .iter()
+                .map(|ip| format!("{}/{}", ip.address, ip.mask))
+                .collect(),
+            raw_flags: req.interface.raw_flags,
+        },
+    };
+    serde_json::to_string(&policy_req).unwrap()
+}
+
+/// Serialize just the OnlineCPUMemRequest fields checked by the policy.
+pub fn trim_online_cpu_mem(req: &protocols::agent::OnlineCPUMemRequest) -> String {
+    let policy_req = PolicyOnlineCPUMemRequest {
+        wait: req.wait,
+        nb_cpus: req.nb_cpus,
+    };
+    serde_json::to_string(&policy_req).unwrap()
+}
+
+/// Serialize one MemHotplugByProbeRequest probe address as the policy input.
+/// The policy checks the probe addresses one at a time.
+pub fn trim_mem_hotplug(probe_address: u64) -> String {
+    let policy_req = PolicyMemHotplugRequest { probe_address };
+    serde_json::to_string(&policy_req).unwrap()
+}
+
+/// File where the current policy gets persisted when the agent receives
+/// SIGTERM, and restored from during the next agent start.
+pub const POLICY_PERSIST_FILE: &str = "/run/kata-containers/agent-policy.json";
+
+impl AgentPolicyHandle {
+    pub fn new() -> Self {
+        Self {
+            policy: Arc::new(RwLock::new(AgentPolicy::new())),
+            update_seq: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Lock the policy instance for evaluation or updates.
+    pub async fn write(&self) -> RwLockWriteGuard<'_, AgentPolicy> {
+        self.policy.write().await
+    }
+
+    /// Lock the policy instance for read-only access.
+    pub async fn read(&self) -> RwLockReadGuard<'_, AgentPolicy> {
+        self.policy.read().await
+    }
+
+    /// Evaluate a request that has already been serialized to the JSON
+    /// format expected by the policy - e.g., by the policy check middleware.
+    pub async fn is_allowed_json(&self, ep: &str, request: &str) -> ttrpc::Result<()> {
+        let mut policy = self.write().await;
+        allow_request(&mut policy, ep, request).await
+    }
+
+    /// Version of the regorus crate used by the policy engine, reported to
+    /// the host through GetGuestDetailsRequest.
+    pub async fn get_regorus_version(&self) -> Option<String> {
+        let policy = self.read().await;
+        Some(policy.get_regorus_version().to_string())
+    }
+
+    pub async fn do_set_policy(
+        &self,
+        req: &protocols::agent::SetPolicyRequest,
+    ) -> ttrpc::Result<()> {
+        let request = serde_json::to_string(req).unwrap();
+        let seq = self.update_seq.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut policy = self.write().await;
+        allow_request(&mut policy, "SetPolicyRequest", &request).await?;
+
+        // Another update incremented the sequence number while this update
+        // was waiting for the policy lock.
+        if self.update_seq.load(Ordering::SeqCst) != seq {
+            return Err(ttrpc_error(
+                ttrpc::Code::ABORTED,
+                "policy update aborted: another policy update started concurrently".to_string(),
+            ));
+        }
+
+        policy
+            .set_policy(&req.policy)
+            .await
+            .map_err(|e| ttrpc_error(ttrpc::Code::INVALID_ARGUMENT, e))?;
+
+        // Refresh the reference copy that the attestation poller verifies
+        // the loaded policy against, so the newly installed policy doesn't
+        // get flagged as divergence. Persist failures just get logged -
+        // without a reference copy the poller skips its check.
+        if let Err(e) = policy.save_to_disk(std::path::Path::new(POLICY_PERSIST_FILE)) {
+            warn!(
+                slog_scope::logger(),
+                "policy: failed to persist the policy: {e}"
+            );
+        }
+        Ok(())
+    }
+
+    /// List the top level rules of the currently loaded policy modules.
+    /// Listing is permitted only while allow_failures is set, because the
+    /// rule names reveal the policy structure.
+    pub async fn do_list_policy_rules(
+        &self,
+    ) -> ttrpc::Result<protocols::agent::ListPolicyRulesResponse> {
+        let mut policy = self.write().await;
+        if !policy.get_allow_failures() {
+            return Err(ttrpc_error(
+                ttrpc::Code::PERMISSION_DENIED,
+                "listing policy rules requires the AllowRequestsFailingPolicy debug flag"
+                    .to_string(),
+            ));
+        }
+
+        let rules = policy
+            .list_rules()
+            .map_err(|e| ttrpc_error(ttrpc::Code::INTERNAL, e))?;
+        let mut response = protocols::agent::ListPolicyRulesResponse::new();
+        for (name, module) in rules {
+            let mut rule = protocols::agent::PolicyRule::new();
+            rule.name = name;
+            rule.module = module;
+            response.rules.push(rule);
+        }
+        Ok(response)
+    }
+
+    /// Explain which policy rules deny the input of a request. Explaining is
+    /// permitted only while allow_failures is set, because the explanation
+    /// reveals the policy structure.
+    pub async fn do_explain_policy_decision(
+        &self,
+        req: &protocols::agent::ExplainPolicyDecisionRequest,
+    ) -> ttrpc::Result<protocols::agent::ExplainPolicyDecisionResponse> {
+        let mut policy = self.write().await;
+        if !policy.get_allow_failures() {
+            return Err(ttrpc_error(
+                ttrpc::Code::PERMISSION_DENIED,
+                "explaining policy decisions requires the AllowRequestsFailingPolicy debug flag"
+                    .to_string(),
+            ));
+        }
+
+        let explanation = policy
+            .explain_denial(&req.endpoint, &req.input)
+            .map_err(|e| ttrpc_error(ttrpc::Code::INTERNAL, e))?;
+        let mut response = protocols::agent::ExplainPolicyDecisionResponse::new();
+        response.explanation = explanation;
+        Ok(response)
+    }
+
+    /// Verify that the policy engine can still evaluate a trivial query,
+    /// for liveness probes.
+    pub async fn do_check_policy_health(
+        &self,
+    ) -> ttrpc::Result<protocols::agent::CheckPolicyHealthResponse> {
+        let mut policy = self.write().await;
+        let policy_version = policy
+            .check_health()
+            .map_err(|e| ttrpc_error(ttrpc::Code::INTERNAL, e))?;
+        let mut response = protocols::agent::CheckPolicyHealthResponse::new();
+        response.policy_version = policy_version;
+        Ok(response)
+    }
+
+    /// Periodically re-verify the loaded policy against the reference copy
+    /// persisted to POLICY_PERSIST_FILE, switching to a deny-all policy when
+    /// the verification fails. The containers still running under the stale
+    /// policy get the policy_reattest_action applied to them. The
+    /// verification is skipped while policy errors are ignored for
+    /// debugging, and while no reference copy exists - e.g., before any
+    /// policy got installed and persisted.
+    pub async fn attestation_poller(
+        self,
+        logger: slog::Logger,
+        sandbox: Arc<tokio::sync::Mutex<crate::sandbox::Sandbox>>,
+    ) {
+        let interval = AGENT_CONFIG.policy_reattest_interval;
+        loop {
+            tokio::time::sleep(interval).await;
+
+            let mut policy = self.write().await;
+            if policy.get_allow_failures() {
+                continue;
+            }
+
+            let reference = std::path::Path::new(POLICY_PERSIST_FILE);
+            if !reference.exists() {
+                continue;
+            }
+            if policy.check_policy_hash(reference).is_ok() {
+                continue;
+            }
+
+            let mut sandbox = sandbox.lock().await;
+            let container_ids: Vec<String> = sandbox.containers.keys().cloned().collect();
+            for container_id in &container_ids {
+                let Err(e) = policy.verify_runtime_attestation(container_id, reference) else {
+                    continue;
+                };
+                match AGENT_CONFIG.policy_reattest_action {
+                    crate::config::PolicyReattestAction::Log => {
+                        warn!(logger, "policy: {e}");
+                    }
+                    crate::config::PolicyReattestAction::PauseContainers => {
+                        crit!(logger, "policy: {e} - pausing the container");
+                        if let Some(container) = sandbox.get_container(container_id) {
+                            if let Err(e) = container.pause() {
+                                warn!(
+                                    logger,
+                                    "policy: failed to pause container {container_id}: {e}"
+                                );
+                            }
+                        }
+                    }
+                    crate::config::PolicyReattestAction::KillContainers => {
+                        crit!(logger, "policy: {e} - killing the container");
+                        match sandbox.find_container_process(container_id, "") {
+                            Ok(process) => {
+                                if let Err(e) = process.signal(libc::SIGKILL) {
+                                    warn!(
+                                        logger,
+                                        "policy: failed to kill container {container_id}: {e}"
+                                    );
+                                }
+                            }
+                            Err(e) => {
+                                warn!(
+                                    logger,
+                                    "policy: no init process for container {container_id}: {e}"
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            drop(sandbox);
+
+            crit!(
+                logger,
+                "policy: re-attestation failed - switching to a deny-all policy"
+            );
+            if let Err(e) = policy.set_policy("package agent_policy\n").await {
+                crit!(logger, "policy: failed to set the deny-all policy: {e}");
+            }
+        }
+    }
+
+    /// Persist the current policy to POLICY_PERSIST_FILE each time the agent
+    /// receives SIGTERM. Persistence failures just get logged - shutting
+    /// down without a persisted policy is always safe because the next agent
+    /// start falls back to its default policy.
+    pub async fn persist_on_sigterm(self, logger: slog::Logger) {
+        let mut sigterm_stream =
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!(logger, "policy: failed to handle SIGTERM: {e}");
+                    return;
+                }
+            };
+
+        while sigterm_stream.recv().await.is_some() {
+            info!(logger, "handling signal"; "signal" => "SIGTERM");
+            self.save_policy_to_disk(&logger).await;
+        }
+    }
+
+    /// Persist the current policy to POLICY_PERSIST_FILE.
+    async fn save_policy_to_disk(&self, logger: &slog::Logger) {
+        let policy = self.read().await;
+        if let Err(e) = policy.save_to_disk(std::path::Path::new(POLICY_PERSIST_FILE)) {
+            warn!(logger, "policy: failed to persist the policy: {e}");
+        } else {
+            info!(
+                logger,
+                "policy: persisted the policy to {POLICY_PERSIST_FILE}"
+            );
+        }
+    }
 }
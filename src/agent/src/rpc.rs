@@ -66,6 +66,7 @@ use crate::device::network_device_handler::wait_for_pci_net_interface;
 use crate::device::{add_devices, handle_cdi_devices, update_env_pci};
 use crate::features::get_build_features;
 use crate::metrics::get_metrics;
+use crate::middleware::{MiddlewareChain, RpcMiddleware};
 use crate::mount::baremount;
 use crate::namespace::{NSTYPEIPC, NSTYPEPID, NSTYPEUTS};
 use crate::network::setup_guest_dns;
@@ -83,7 +84,7 @@ use crate::trace_rpc_call;
 use crate::tracer::extract_carrier_from_ttrpc;
 
 #[cfg(feature = "agent-policy")]
-use crate::policy::{do_set_policy, is_allowed};
+use crate::policy::{self, AgentPolicyHandle};
 
 use opentelemetry::global;
 use tracing::span;
@@ -141,9 +142,170 @@ pub fn ttrpc_error(code: ttrpc::Code, err: impl Debug) -> ttrpc::Error {
     get_rpc_status(code, format!("{:?}", err))
 }
 
+/// Policy checks, routed through the middleware chain. The requests with a
+/// trimmed policy input - i.e., just the fields checked by the policy - get
+/// trimmed here, before running the chain.
+#[cfg(feature = "agent-policy")]
+impl AgentService {
+    async fn get_regorus_version(&self) -> Option<String> {
+        self.policy.get_regorus_version().await
+    }
+
+    async fn is_allowed_create_sandbox(
+        &self,
+        req: &protocols::agent::CreateSandboxRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware("CreateSandboxRequest", &policy::trim_create_sandbox(req))
+            .await
+    }
+
+    async fn is_allowed_mem_hotplug(
+        &self,
+        req: &protocols::agent::MemHotplugByProbeRequest,
+    ) -> ttrpc::Result<()> {
+        // The policy checks the probe addresses one at a time.
+        for probe_address in &req.memHotplugProbeAddr {
+            self.run_middleware(
+                "MemHotplugByProbeRequest",
+                &policy::trim_mem_hotplug(*probe_address),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn is_allowed_online_cpu_mem(
+        &self,
+        req: &protocols::agent::OnlineCPUMemRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware("OnlineCPUMemRequest", &policy::trim_online_cpu_mem(req))
+            .await
+    }
+
+    async fn is_allowed_set_datetime(
+        &self,
+        req: &protocols::agent::SetGuestDateTimeRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware("SetGuestDateTimeRequest", &policy::trim_set_datetime(req))
+            .await
+    }
+
+    async fn is_allowed_update_interface(
+        &self,
+        req: &protocols::agent::UpdateInterfaceRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware(
+            "UpdateInterfaceRequest",
+            &policy::trim_update_interface(req),
+        )
+        .await
+    }
+
+    async fn is_allowed_update_routes(
+        &self,
+        req: &protocols::agent::UpdateRoutesRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware("UpdateRoutesRequest", &policy::trim_update_routes(req))
+            .await
+    }
+
+    async fn is_allowed_wait_process(
+        &self,
+        req: &protocols::agent::WaitProcessRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware("WaitProcessRequest", &policy::trim_wait_process(req))
+            .await
+    }
+
+    async fn do_set_policy(&self, req: &protocols::agent::SetPolicyRequest) -> ttrpc::Result<()> {
+        self.policy.do_set_policy(req).await
+    }
+
+    async fn do_list_policy_rules(
+        &self,
+    ) -> ttrpc::Result<protocols::agent::ListPolicyRulesResponse> {
+        self.policy.do_list_policy_rules().await
+    }
+
+    async fn do_explain_policy_decision(
+        &self,
+        req: &protocols::agent::ExplainPolicyDecisionRequest,
+    ) -> ttrpc::Result<protocols::agent::ExplainPolicyDecisionResponse> {
+        self.policy.do_explain_policy_decision(req).await
+    }
+
+    async fn do_check_policy_health(
+        &self,
+    ) -> ttrpc::Result<protocols::agent::CheckPolicyHealthResponse> {
+        self.policy.do_check_policy_health().await
+    }
+}
+
+/// Middleware checks without a policy, for builds without the agent-policy
+/// feature. The other middlewares - e.g., rate limiting and audit logging -
+/// still apply to every request, but the requests only get serialized when
+/// a middleware in the chain reads request bodies.
 #[cfg(not(feature = "agent-policy"))]
-async fn is_allowed(_req: &impl serde::Serialize) -> ttrpc::Result<()> {
-    Ok(())
+impl AgentService {
+    async fn get_regorus_version(&self) -> Option<String> {
+        None
+    }
+
+    async fn is_allowed_create_sandbox(
+        &self,
+        req: &protocols::agent::CreateSandboxRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware_serialized("CreateSandboxRequest", req)
+            .await
+    }
+
+    async fn is_allowed_mem_hotplug(
+        &self,
+        req: &protocols::agent::MemHotplugByProbeRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware_serialized("MemHotplugByProbeRequest", req)
+            .await
+    }
+
+    async fn is_allowed_online_cpu_mem(
+        &self,
+        req: &protocols::agent::OnlineCPUMemRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware_serialized("OnlineCPUMemRequest", req)
+            .await
+    }
+
+    async fn is_allowed_set_datetime(
+        &self,
+        req: &protocols::agent::SetGuestDateTimeRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware_serialized("SetGuestDateTimeRequest", req)
+            .await
+    }
+
+    async fn is_allowed_update_interface(
+        &self,
+        req: &protocols::agent::UpdateInterfaceRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware_serialized("UpdateInterfaceRequest", req)
+            .await
+    }
+
+    async fn is_allowed_update_routes(
+        &self,
+        req: &protocols::agent::UpdateRoutesRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware_serialized("UpdateRoutesRequest", req)
+            .await
+    }
+
+    async fn is_allowed_wait_process(
+        &self,
+        req: &protocols::agent::WaitProcessRequest,
+    ) -> ttrpc::Result<()> {
+        self.run_middleware_serialized("WaitProcessRequest", req)
+            .await
+    }
 }
 
 fn same<E>(e: E) -> E {
@@ -181,9 +343,123 @@ pub struct AgentService {
     sandbox: Arc<Mutex<Sandbox>>,
     init_mode: bool,
     oma: Option<mem_agent::agent::MemAgent>,
+    middleware: Arc<MiddlewareChain>,
+    #[cfg(feature = "agent-policy")]
+    policy: AgentPolicyHandle,
+}
+
+/// Builder for AgentService, threading the dependencies of the ttrpc
+/// service implementations - e.g., the policy handle - through the service
+/// without process-wide singletons.
+#[derive(Default)]
+pub struct AgentServiceBuilder {
+    sandbox: Option<Arc<Mutex<Sandbox>>>,
+    init_mode: bool,
+    oma: Option<mem_agent::agent::MemAgent>,
+    middlewares: Vec<Box<dyn RpcMiddleware>>,
+    #[cfg(feature = "agent-policy")]
+    policy: Option<AgentPolicyHandle>,
+}
+
+impl AgentServiceBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn sandbox(mut self, sandbox: Arc<Mutex<Sandbox>>) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    pub fn init_mode(mut self, init_mode: bool) -> Self {
+        self.init_mode = init_mode;
+        self
+    }
+
+    pub fn oma(mut self, oma: Option<mem_agent::agent::MemAgent>) -> Self {
+        self.oma = oma;
+        self
+    }
+
+    /// Append a middleware to the chain applied to every request, after
+    /// the policy check middleware.
+    pub fn middleware(mut self, middleware: Box<dyn RpcMiddleware>) -> Self {
+        self.middlewares.push(middleware);
+        self
+    }
+
+    #[cfg(feature = "agent-policy")]
+    pub fn policy(mut self, policy: AgentPolicyHandle) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    pub fn build(self) -> Result<AgentService> {
+        #[cfg(feature = "agent-policy")]
+        let policy = self
+            .policy
+            .ok_or_else(|| anyhow!("AgentService requires a policy handle"))?;
+
+        // The policy check middleware always runs first, followed by the
+        // middlewares added through the builder.
+        let mut middleware = MiddlewareChain::default();
+        #[cfg(feature = "agent-policy")]
+        middleware.push(Box::new(crate::middleware::PolicyCheckMiddleware::new(
+            policy.clone(),
+        )));
+        for m in self.middlewares {
+            middleware.push(m);
+        }
+
+        Ok(AgentService {
+            sandbox: self
+                .sandbox
+                .ok_or_else(|| anyhow!("AgentService requires a sandbox"))?,
+            init_mode: self.init_mode,
+            oma: self.oma,
+            middleware: Arc::new(middleware),
+            #[cfg(feature = "agent-policy")]
+            policy,
+        })
+    }
 }
 
 impl AgentService {
+    /// Run the middleware chain - policy check, rate limiting, audit
+    /// logging - for a request, before its handler runs. after_request()
+    /// reports whether the chain admitted the request; the handler has not
+    /// run yet at that point.
+    async fn run_middleware(&self, ep: &str, request: &str) -> ttrpc::Result<()> {
+        let result = self.middleware.before_request(ep, request.as_bytes()).await;
+        self.middleware.after_request(ep, result.is_ok()).await;
+        result
+    }
+
+    /// Run the middleware chain for a request, serializing the request to
+    /// JSON only when a middleware in the chain reads request bodies.
+    async fn run_middleware_serialized(
+        &self,
+        ep: &str,
+        req: &impl serde::Serialize,
+    ) -> ttrpc::Result<()> {
+        let request = if self.middleware.needs_request_body() {
+            serde_json::to_string(req).unwrap()
+        } else {
+            String::new()
+        };
+        self.run_middleware(ep, &request).await
+    }
+
+    /// Run the middleware chain for a request whose policy input is its
+    /// full JSON serialization.
+    async fn is_allowed(
+        &self,
+        req: &(impl protobuf::MessageDyn + serde::Serialize),
+    ) -> ttrpc::Result<()> {
+        let ep = req.descriptor_dyn().name().to_string();
+        self.run_middleware_serialized(&ep, req).await
+    }
+
     #[instrument]
     async fn do_create_container(
         &self,
@@ -747,7 +1023,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::CreateContainerRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "create_container", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
         self.do_create_container(req).await.map_ttrpc_err(same)?;
         Ok(Empty::new())
     }
@@ -758,7 +1034,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::StartContainerRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "start_container", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
         self.do_start_container(req).await.map_ttrpc_err(same)?;
         Ok(Empty::new())
     }
@@ -769,7 +1045,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::RemoveContainerRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "remove_container", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
         self.do_remove_container(req).await.map_ttrpc_err(same)?;
         Ok(Empty::new())
     }
@@ -780,7 +1056,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::ExecProcessRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "exec_process", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
         self.do_exec_process(req).await.map_ttrpc_err(same)?;
         Ok(Empty::new())
     }
@@ -791,7 +1067,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::SignalProcessRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "signal_process", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
         self.do_signal_process(req).await.map_ttrpc_err(same)?;
         Ok(Empty::new())
     }
@@ -802,7 +1078,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::WaitProcessRequest,
     ) -> ttrpc::Result<WaitProcessResponse> {
         trace_rpc_call!(ctx, "wait_process", req);
-        is_allowed(&req).await?;
+        self.is_allowed_wait_process(&req).await?;
         self.do_wait_process(req).await.map_ttrpc_err(same)
     }
 
@@ -812,7 +1088,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::UpdateContainerRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "update_container", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         let mut sandbox = self.sandbox.lock().await;
         let ctr = sandbox
@@ -832,7 +1108,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::StatsContainerRequest,
     ) -> ttrpc::Result<StatsContainerResponse> {
         trace_rpc_call!(ctx, "stats_container", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         let mut sandbox = self.sandbox.lock().await;
         let ctr = sandbox
@@ -847,7 +1123,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::PauseContainerRequest,
     ) -> ttrpc::Result<protocols::empty::Empty> {
         trace_rpc_call!(ctx, "pause_container", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         let mut sandbox = self.sandbox.lock().await;
         let ctr = sandbox
@@ -863,7 +1139,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::ResumeContainerRequest,
     ) -> ttrpc::Result<protocols::empty::Empty> {
         trace_rpc_call!(ctx, "resume_container", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         let mut sandbox = self.sandbox.lock().await;
         let ctr = sandbox
@@ -879,7 +1155,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::RemoveStaleVirtiofsShareMountsRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "remove_stale_virtiofs_share_mounts", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
         let mount_infos = parse_mount_table("/proc/self/mountinfo").map_ttrpc_err(same)?;
         for m in &mount_infos {
             if m.mount_point.starts_with(KATA_GUEST_SHARE_DIR) {
@@ -900,7 +1176,7 @@ impl agent_ttrpc::AgentService for AgentService {
         _ctx: &TtrpcContext,
         req: protocols::agent::WriteStreamRequest,
     ) -> ttrpc::Result<WriteStreamResponse> {
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
         self.do_write_stream(req).await.map_ttrpc_err(same)
     }
 
@@ -910,7 +1186,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::ReadStreamRequest,
     ) -> ttrpc::Result<ReadStreamResponse> {
         let mut response = self.do_read_stream(&req, true).await.map_ttrpc_err(same)?;
-        if is_allowed(&req).await.is_err() {
+        if self.is_allowed(&req).await.is_err() {
             // Policy does not allow reading logs, so we redact the log messages.
             response.clear_data();
         }
@@ -923,7 +1199,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::ReadStreamRequest,
     ) -> ttrpc::Result<ReadStreamResponse> {
         let mut response = self.do_read_stream(&req, false).await.map_ttrpc_err(same)?;
-        if is_allowed(&req).await.is_err() {
+        if self.is_allowed(&req).await.is_err() {
             // Policy does not allow reading logs, so we redact the log messages.
             response.clear_data();
         }
@@ -939,7 +1215,7 @@ impl agent_ttrpc::AgentService for AgentService {
         // so this rpc will not be called anymore by runtime-rs.
 
         trace_rpc_call!(ctx, "close_stdin", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         let cid = req.container_id;
         let eid = req.exec_id;
@@ -965,7 +1241,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::TtyWinResizeRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "tty_win_resize", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         let mut sandbox = self.sandbox.lock().await;
         let p = sandbox
@@ -1001,7 +1277,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::UpdateInterfaceRequest,
     ) -> ttrpc::Result<Interface> {
         trace_rpc_call!(ctx, "update_interface", req);
-        is_allowed(&req).await?;
+        self.is_allowed_update_interface(&req).await?;
 
         let interface = req.interface.into_option().map_ttrpc_err(
             ttrpc::Code::INVALID_ARGUMENT,
@@ -1048,7 +1324,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::UpdateRoutesRequest,
     ) -> ttrpc::Result<Routes> {
         trace_rpc_call!(ctx, "update_routes", req);
-        is_allowed(&req).await?;
+        self.is_allowed_update_routes(&req).await?;
 
         let new_routes = req
             .routes
@@ -1082,7 +1358,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::UpdateEphemeralMountsRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "update_mounts", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         update_ephemeral_mounts(sl(), &req.storages, &self.sandbox)
             .await
@@ -1096,7 +1372,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: GetIPTablesRequest,
     ) -> ttrpc::Result<GetIPTablesResponse> {
         trace_rpc_call!(ctx, "get_iptables", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         info!(sl(), "get_ip_tables: request received");
 
@@ -1132,7 +1408,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: SetIPTablesRequest,
     ) -> ttrpc::Result<SetIPTablesResponse> {
         trace_rpc_call!(ctx, "set_iptables", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         info!(sl(), "set_ip_tables request received");
 
@@ -1228,7 +1504,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::ListInterfacesRequest,
     ) -> ttrpc::Result<Interfaces> {
         trace_rpc_call!(ctx, "list_interfaces", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         let list = self
             .sandbox
@@ -1251,7 +1527,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::ListRoutesRequest,
     ) -> ttrpc::Result<Routes> {
         trace_rpc_call!(ctx, "list_routes", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         let list = self
             .sandbox
@@ -1274,7 +1550,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::CreateSandboxRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "create_sandbox", req);
-        is_allowed(&req).await?;
+        self.is_allowed_create_sandbox(&req).await?;
 
         {
             let mut s = self.sandbox.lock().await;
@@ -1332,7 +1608,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::DestroySandboxRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "destroy_sandbox", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         let mut sandbox = self.sandbox.lock().await;
         // destroy all containers, clean up, notify agent to exit etc.
@@ -1360,7 +1636,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::AddARPNeighborsRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "add_arp_neighbors", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         let neighs = req
             .neighbors
@@ -1388,7 +1664,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::OnlineCPUMemRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "online_cpu_mem", req);
-        is_allowed(&req).await?;
+        self.is_allowed_online_cpu_mem(&req).await?;
         let sandbox = self.sandbox.lock().await;
 
         sandbox.online_cpu_memory(&req).map_ttrpc_err(same)?;
@@ -1402,7 +1678,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::ReseedRandomDevRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "reseed_random_dev", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         random::reseed_rng(req.data.as_slice()).map_ttrpc_err(same)?;
 
@@ -1415,7 +1691,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::GuestDetailsRequest,
     ) -> ttrpc::Result<GuestDetailsResponse> {
         trace_rpc_call!(ctx, "get_guest_details", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         info!(sl(), "get guest details!");
         let mut resp = GuestDetailsResponse::new();
@@ -1432,7 +1708,12 @@ impl agent_ttrpc::AgentService for AgentService {
         resp.support_mem_hotplug_probe = v;
 
         // to get agent details
-        let detail = get_agent_details();
+        let mut detail = get_agent_details();
+        if let Some(regorus_version) = self.get_regorus_version().await {
+            detail
+                .extra_features
+                .push(format!("regorus-{regorus_version}"));
+        }
         resp.agent_details = MessageField::some(detail);
 
         Ok(resp)
@@ -1444,7 +1725,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::MemHotplugByProbeRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "mem_hotplug_by_probe", req);
-        is_allowed(&req).await?;
+        self.is_allowed_mem_hotplug(&req).await?;
 
         do_mem_hotplug_by_probe(&req.memHotplugProbeAddr).map_ttrpc_err(same)?;
 
@@ -1457,7 +1738,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::SetGuestDateTimeRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "set_guest_date_time", req);
-        is_allowed(&req).await?;
+        self.is_allowed_set_datetime(&req).await?;
 
         do_set_guest_date_time(req.Sec, req.Usec).map_ttrpc_err(same)?;
 
@@ -1470,7 +1751,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::CopyFileRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "copy_file", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         do_copy_file(&req).map_ttrpc_err(same)?;
 
@@ -1483,7 +1764,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::GetMetricsRequest,
     ) -> ttrpc::Result<Metrics> {
         trace_rpc_call!(ctx, "get_metrics", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         let s = get_metrics(&req).map_ttrpc_err(same)?;
         let mut metrics = Metrics::new();
@@ -1496,7 +1777,7 @@ impl agent_ttrpc::AgentService for AgentService {
         _ctx: &TtrpcContext,
         req: protocols::agent::GetOOMEventRequest,
     ) -> ttrpc::Result<OOMEvent> {
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
         let s = self.sandbox.lock().await;
         let event_rx = &s.event_rx.clone();
         let mut event_rx = event_rx.lock().await;
@@ -1520,7 +1801,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: VolumeStatsRequest,
     ) -> ttrpc::Result<VolumeStatsResponse> {
         trace_rpc_call!(ctx, "get_volume_stats", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         info!(sl(), "get volume stats!");
         let mut resp = VolumeStatsResponse::new();
@@ -1555,7 +1836,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::AddSwapRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "add_swap", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         do_add_swap(&self.sandbox, &req).await.map_ttrpc_err(same)?;
 
@@ -1568,7 +1849,7 @@ impl agent_ttrpc::AgentService for AgentService {
         req: protocols::agent::AddSwapPathRequest,
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "add_swap_path", req);
-        is_allowed(&req).await?;
+        self.is_allowed(&req).await?;
 
         do_add_swap_path(&req).await.map_ttrpc_err(same)?;
 
@@ -1583,11 +1864,44 @@ impl agent_ttrpc::AgentService for AgentService {
     ) -> ttrpc::Result<Empty> {
         trace_rpc_call!(ctx, "set_policy", req);
 
-        do_set_policy(&req).await?;
+        self.do_set_policy(&req).await?;
 
         Ok(Empty::new())
     }
 
+    #[cfg(feature = "agent-policy")]
+    async fn list_policy_rules(
+        &self,
+        ctx: &TtrpcContext,
+        req: protocols::agent::ListPolicyRulesRequest,
+    ) -> ttrpc::Result<protocols::agent::ListPolicyRulesResponse> {
+        trace_rpc_call!(ctx, "list_policy_rules", req);
+
+        self.do_list_policy_rules().await
+    }
+
+    #[cfg(feature = "agent-policy")]
+    async fn explain_policy_decision(
+        &self,
+        ctx: &TtrpcContext,
+        req: protocols::agent::ExplainPolicyDecisionRequest,
+    ) -> ttrpc::Result<protocols::agent::ExplainPolicyDecisionResponse> {
+        trace_rpc_call!(ctx, "explain_policy_decision", req);
+
+        self.do_explain_policy_decision(&req).await
+    }
+
+    #[cfg(feature = "agent-policy")]
+    async fn check_policy_health(
+        &self,
+        ctx: &TtrpcContext,
+        req: protocols::agent::CheckPolicyHealthRequest,
+    ) -> ttrpc::Result<protocols::agent::CheckPolicyHealthResponse> {
+        trace_rpc_call!(ctx, "check_policy_health", req);
+
+        self.do_check_policy_health().await
+    }
+
     async fn mem_agent_memcg_set(
         &self,
         _ctx: &::ttrpc::r#async::TtrpcContext,
@@ -1774,17 +2088,8 @@ async fn read_stream(reader: &Mutex<ReadHalf<PipeStream>>, l: usize) -> Result<V
     Ok(content)
 }
 
-pub async fn start(
-    s: Arc<Mutex<Sandbox>>,
-    server_address: &str,
-    init_mode: bool,
-    oma: Option<mem_agent::agent::MemAgent>,
-) -> Result<TtrpcServer> {
-    let agent_service = Box::new(AgentService {
-        sandbox: s,
-        init_mode,
-        oma,
-    });
+pub async fn start(builder: AgentServiceBuilder, server_address: &str) -> Result<TtrpcServer> {
+    let agent_service = Box::new(builder.build()?);
     let aservice = agent_ttrpc::create_agent_service(Arc::new(*agent_service));
 
     let health_service = Box::new(HealthService {});
@@ -2507,11 +2812,12 @@ mod tests {
         let logger = slog::Logger::root(slog::Discard, o!());
         let sandbox = Sandbox::new(&logger).unwrap();
 
-        let agent_service = Box::new(AgentService {
-            sandbox: Arc::new(Mutex::new(sandbox)),
-            init_mode: true,
-            oma: None,
-        });
+        let builder = AgentServiceBuilder::new()
+            .sandbox(Arc::new(Mutex::new(sandbox)))
+            .init_mode(true);
+        #[cfg(feature = "agent-policy")]
+        let builder = builder.policy(AgentPolicyHandle::new());
+        let agent_service = Box::new(builder.build().unwrap());
 
         let req = protocols::agent::UpdateInterfaceRequest::default();
         let ctx = mk_ttrpc_context();
@@ -2525,11 +2831,12 @@ mod tests {
     async fn test_update_routes() {
         let logger = slog::Logger::root(slog::Discard, o!());
         let sandbox = Sandbox::new(&logger).unwrap();
-        let agent_service = Box::new(AgentService {
-            sandbox: Arc::new(Mutex::new(sandbox)),
-            init_mode: true,
-            oma: None,
-        });
+        let builder = AgentServiceBuilder::new()
+            .sandbox(Arc::new(Mutex::new(sandbox)))
+            .init_mode(true);
+        #[cfg(feature = "agent-policy")]
+        let builder = builder.policy(AgentPolicyHandle::new());
+        let agent_service = Box::new(builder.build().unwrap());
 
         let req = protocols::agent::UpdateRoutesRequest::default();
         let ctx = mk_ttrpc_context();
@@ -2543,11 +2850,12 @@ mod tests {
     async fn test_add_arp_neighbors() {
         let logger = slog::Logger::root(slog::Discard, o!());
         let sandbox = Sandbox::new(&logger).unwrap();
-        let agent_service = Box::new(AgentService {
-            sandbox: Arc::new(Mutex::new(sandbox)),
-            init_mode: true,
-            oma: None,
-        });
+        let builder = AgentServiceBuilder::new()
+            .sandbox(Arc::new(Mutex::new(sandbox)))
+            .init_mode(true);
+        #[cfg(feature = "agent-policy")]
+        let builder = builder.policy(AgentPolicyHandle::new());
+        let agent_service = Box::new(builder.build().unwrap());
 
         let req = protocols::agent::AddARPNeighborsRequest::default();
         let ctx = mk_ttrpc_context();
@@ -2677,11 +2985,12 @@ mod tests {
                 sandbox.add_container(linux_container);
             }
 
-            let agent_service = Box::new(AgentService {
-                sandbox: Arc::new(Mutex::new(sandbox)),
-                init_mode: true,
-                oma: None,
-            });
+            let builder = AgentServiceBuilder::new()
+                .sandbox(Arc::new(Mutex::new(sandbox)))
+                .init_mode(true);
+            #[cfg(feature = "agent-policy")]
+            let builder = builder.policy(AgentPolicyHandle::new());
+            let agent_service = Box::new(builder.build().unwrap());
 
             let result = agent_service
                 .do_write_stream(protocols::agent::WriteStreamRequest {
@@ -3168,11 +3477,12 @@ OtherField:other
 
         let logger = slog::Logger::root(slog::Discard, o!());
         let sandbox = Sandbox::new(&logger).unwrap();
-        let agent_service = Box::new(AgentService {
-            sandbox: Arc::new(Mutex::new(sandbox)),
-            init_mode: true,
-            oma: None,
-        });
+        let builder = AgentServiceBuilder::new()
+            .sandbox(Arc::new(Mutex::new(sandbox)))
+            .init_mode(true);
+        #[cfg(feature = "agent-policy")]
+        let builder = builder.policy(AgentPolicyHandle::new());
+        let agent_service = Box::new(builder.build().unwrap());
 
         let ctx = mk_ttrpc_context();
 
@@ -19,12 +19,13 @@ default CreateSandboxRequest := false
 default DestroySandboxRequest := true
 default ExecProcessRequest := false
 default GetOOMEventRequest := true
-default GuestDetailsRequest := true
+default GuestDetailsRequest := false
 default ListInterfacesRequest := false
 default ListRoutesRequest := false
 default MemHotplugByProbeRequest := false
-default OnlineCPUMemRequest := true
+default OnlineCPUMemRequest := false
 default PauseContainerRequest := false
+default PsRequest := false
 default ReadStreamRequest := false
 default RemoveContainerRequest := true
 default RemoveStaleVirtiofsShareMountsRequest := true
@@ -42,7 +43,7 @@ default UpdateContainerRequest := false
 default UpdateEphemeralMountsRequest := false
 default UpdateInterfaceRequest := false
 default UpdateRoutesRequest := false
-default WaitProcessRequest := true
+default WaitProcessRequest := false
 default WriteStreamRequest := false
 
 # AllowRequestsFailingPolicy := true configures the Agent to *allow any
@@ -64,6 +65,9 @@ CreateContainerRequest := {"ops": ops, "allowed": true} if {
     i_storages := input.storages
     i_devices := input.devices
 
+    allow_psa_level(i_oci)
+    allow_sa_token_mounts(i_oci)
+
     # array of possible state operations
     ops_builder := []
 
@@ -76,6 +80,13 @@ CreateContainerRequest := {"ops": ops, "allowed": true} if {
     some idx, p_container in policy_data.containers
     print("======== CreateContainerRequest: trying next policy container")
 
+    # K8s v1.30+ sidecar containers - i.e., init containers with
+    # restartPolicy Always - are marked by p_container.is_sidecar, so that
+    # custom rules can apply different lifecycle checks to them.
+    print("CreateContainerRequest: p is_sidecar =", p_container.is_sidecar)
+
+    allow_image_platform(p_container)
+
     p_pidns := p_container.sandbox_pidns
     i_pidns := input.sandbox_pidns
     print("CreateContainerRequest: p_pidns =", p_pidns, "i_pidns =", i_pidns)
@@ -97,6 +108,7 @@ CreateContainerRequest := {"ops": ops, "allowed": true} if {
     p_oci.Root.Readonly == i_oci.Root.Readonly
 
     allow_anno(p_oci, i_oci)
+    allow_dra_devices(i_oci)
 
     p_storages := p_container.storages
     allow_by_anno(p_oci, i_oci, p_storages, i_storages)
@@ -129,7 +141,12 @@ allow_create_container_input if {
     is_null(i_oci.Solaris)
     is_null(i_oci.Windows)
 
+    # Just Linux containers are supported - e.g., a Windows container image
+    # accidentally referenced by a pod with "spec.os.name: windows" must be
+    # rejected, even if a future runtime version would know how to run it.
     i_linux := i_oci.Linux
+    not is_null(i_linux)
+
     count(i_linux.GIDMappings) == 0
     count(i_linux.MountLabel) == 0
     count(i_linux.Resources.Devices) == 0
@@ -148,18 +165,100 @@ allow_create_container_input if {
     print("allow_create_container_input: true")
 }
 
+# Enforce that just linux container images, built for the target architecture
+# of the policy, get executed. The image platform got recorded in the policy
+# data from each image's config, so e.g. a multi-arch manifest accidentally
+# resolved to the wrong architecture fails this check.
+allow_image_platform(p_container) if {
+    p_image := p_container.image
+    print("allow_image_platform: p_image =", p_image)
+
+    p_image.os == "linux"
+    p_image.architecture == policy_data.common.target_arch
+
+    print("allow_image_platform: true")
+}
+
+# Enforce the Pod Security Admission level from the settings file, if any.
+# See https://kubernetes.io/docs/concepts/security/pod-security-standards/
+allow_psa_level(i_oci) if {
+    not policy_data.common.psa_level
+
+    print("allow_psa_level 1: true")
+}
+allow_psa_level(i_oci) if {
+    policy_data.common.psa_level == "privileged"
+
+    print("allow_psa_level 2: true")
+}
+allow_psa_level(i_oci) if {
+    policy_data.common.psa_level == "baseline"
+
+    # Privileged containers get CAP_SYS_ADMIN in their bounding set.
+    not "CAP_SYS_ADMIN" in i_oci.Process.Capabilities.Bounding
+
+    print("allow_psa_level 3: true")
+}
+allow_psa_level(i_oci) if {
+    policy_data.common.psa_level == "restricted"
+
+    not "CAP_SYS_ADMIN" in i_oci.Process.Capabilities.Bounding
+    i_oci.Process.NoNewPrivileges
+    i_oci.Process.User.UID != 0
+
+    print("allow_psa_level 4: true")
+}
+
+# Projected service account tokens must be mounted at the mount point
+# configured in the settings file.
+allow_sa_token_mounts(i_oci) if {
+    p_token_path := policy_data.common.service_account_token_path
+    every i_mount in i_oci.Mounts {
+        allow_sa_token_mount(p_token_path, i_mount)
+    }
+
+    print("allow_sa_token_mounts: true")
+}
+
+allow_sa_token_mount(p_token_path, i_mount) if {
+    not contains(i_mount.destination, "kubernetes.io/serviceaccount")
+}
+allow_sa_token_mount(p_token_path, i_mount) if {
+    print("allow_sa_token_mount 2: i_mount destination =", i_mount.destination)
+
+    i_mount.destination == p_token_path
+
+    print("allow_sa_token_mount 2: true")
+}
+
 allow_namespace(p_namespace, i_namespace) = add_namespace if {
     p_namespace == i_namespace
+    allow_sandbox_namespace(i_namespace)
     add_namespace := state_allows("namespace", i_namespace)
     print("allow_namespace 1: input namespace matches policy data")
 }
 
 allow_namespace(p_namespace, i_namespace) = add_namespace if {
     p_namespace == ""
+    allow_sandbox_namespace(i_namespace)
     print("allow_namespace 2: no namespace found on policy data")
     add_namespace := state_allows("namespace", i_namespace)
 }
 
+allow_sandbox_namespace(i_namespace) if {
+    not policy_data.sandbox.namespace
+
+    print("allow_sandbox_namespace 1: true")
+}
+allow_sandbox_namespace(i_namespace) if {
+    p_namespace := policy_data.sandbox.namespace
+    print("allow_sandbox_namespace 2: p_namespace =", p_namespace, "i_namespace =", i_namespace)
+
+    p_namespace == i_namespace
+
+    print("allow_sandbox_namespace 2: true")
+}
+
 # key hasn't been seen before, save key, value pair to state
 state_allows(key, value) = action if {
   state := get_state()
@@ -260,6 +359,43 @@ allow_anno_key(i_key, p_oci) if {
     print("allow_anno_key 2: true")
 }
 
+allow_dra_devices(i_oci) if {
+    # The settings don't restrict the Dynamic Resource Allocation drivers.
+    count(policy_data.common.dra_allowed_drivers) == 0
+
+    print("allow_dra_devices 1: true")
+}
+allow_dra_devices(i_oci) if {
+    not i_oci.Annotations
+
+    print("allow_dra_devices 2: true")
+}
+allow_dra_devices(i_oci) if {
+    count(policy_data.common.dra_allowed_drivers) > 0
+
+    every i_key, i_value in i_oci.Annotations {
+        allow_dra_annotation(i_key, i_value)
+    }
+
+    print("allow_dra_devices 3: true")
+}
+
+allow_dra_annotation(i_key, i_value) if {
+    not startswith(i_key, "cdi.k8s.io/")
+}
+allow_dra_annotation(i_key, i_value) if {
+    startswith(i_key, "cdi.k8s.io/")
+
+    # CDI device values are formatted as <driver name>/<class>=<device>.
+    i_driver := split(i_value, "/")[0]
+    print("allow_dra_annotation: i_driver =", i_driver)
+
+    some p_driver in policy_data.common.dra_allowed_drivers
+    i_driver == p_driver
+
+    print("allow_dra_annotation: true")
+}
+
 # Get the value of the S_NAME_KEY annotation and
 # correlate it with other annotations and process fields.
 allow_by_anno(p_oci, i_oci, p_storages, i_storages) if {
@@ -634,12 +770,27 @@ allow_linux_devices(p_devices, i_devices) if {
     print("allow_linux_devices: start")
     every i_device in i_devices {
         print("allow_linux_devices: i_device =", i_device)
-        some p_device in p_devices
-        i_device.Path == p_device.Path
+        allow_linux_device(p_devices, i_device)
     }
     print("allow_linux_devices: true")
 }
 
+allow_linux_device(p_devices, i_device) if {
+    some p_device in p_devices
+    i_device.Path == p_device.Path
+
+    print("allow_linux_device 1: true")
+}
+allow_linux_device(p_devices, i_device) if {
+    # Device paths generated from the settings file - e.g., for GPU devices -
+    # are regular expressions.
+    some p_device in p_devices
+    p_regex := concat("", ["^", p_device.Path, "$"])
+    regex.match(p_regex, i_device.Path)
+
+    print("allow_linux_device 2: true")
+}
+
 allow_linux_sysctl(p_linux, i_linux) if {
     print("allow_linux_sysctl 1: start")
     not i_linux.Sysctl
@@ -928,6 +1079,43 @@ allow_var(p_process, i_process, i_var, s_name, s_namespace) if {
     print("allow_var 8: true")
 }
 
+# Allow any value for env variables that get their values from K8s secrets.
+# The policy stores a "$(secret-value)" marker for these variables instead of
+# the secret values themselves, because secrets can be created and updated
+# independently of the policy.
+allow_var(p_process, i_process, i_var, s_name, s_namespace) if {
+    name_value := split(i_var, "=")
+    count(name_value) == 2
+
+    some p_var in p_process.Env
+    p_name_value := split(p_var, "=")
+    count(p_name_value) == 2
+
+    p_name_value[0] == name_value[0]
+    p_name_value[1] == "$(secret-value)"
+
+    print("allow_var 9: true")
+}
+
+# Allow the completion index env variable of Indexed Jobs. The Job controller
+# generates a distinct index value for each pod, so the policy allows any
+# non-negative integer.
+allow_var(p_process, i_process, i_var, s_name, s_namespace) if {
+    name_value := split(i_var, "=")
+    count(name_value) == 2
+
+    some p_var in p_process.Env
+    p_name_value := split(p_var, "=")
+    count(p_name_value) == 2
+
+    p_name_value[0] == name_value[0]
+    p_name_value[1] == "$(job-completion-index)"
+
+    regex.match(`^[0-9]+$`, name_value[1])
+
+    print("allow_var 10: true")
+}
+
 allow_pod_ip_var(var_name, p_var) if {
     print("allow_pod_ip_var: var_name =", var_name, "p_var =", p_var)
 
@@ -1010,11 +1198,54 @@ check_mount(p_mount, i_mount, bundle_id, sandbox_id) if {
     p_mount.type_ == i_mount.type_
     p_mount.options == i_mount.options
 
+    allow_mount_propagation(p_mount, i_mount)
+    allow_mount_access(p_mount, i_mount)
+
     mount_source_allows(p_mount, i_mount, bundle_id, sandbox_id)
 
     print("check_mount 2: true")
 }
 
+# Mounts generated for readOnly volumeMounts must be mounted "ro" and
+# read-write mounts must be mounted "rw" in the input data.
+allow_mount_access(p_mount, i_mount) if {
+    "ro" in p_mount.options
+
+    "ro" in i_mount.options
+    not "rw" in i_mount.options
+
+    print("allow_mount_access 1: true")
+}
+allow_mount_access(p_mount, i_mount) if {
+    "rw" in p_mount.options
+
+    "rw" in i_mount.options
+    not "ro" in i_mount.options
+
+    print("allow_mount_access 2: true")
+}
+allow_mount_access(p_mount, i_mount) if {
+    not "ro" in p_mount.options
+    not "rw" in p_mount.options
+
+    print("allow_mount_access 3: true")
+}
+
+# Only mounts whose policy options include "rshared" - generated for
+# volumeMounts that explicitly request "mountPropagation: Bidirectional" -
+# are allowed to use bidirectional propagation in the input data.
+allow_mount_propagation(p_mount, i_mount) if {
+    not "rshared" in i_mount.options
+
+    print("allow_mount_propagation 1: true")
+}
+allow_mount_propagation(p_mount, i_mount) if {
+    "rshared" in i_mount.options
+    "rshared" in p_mount.options
+
+    print("allow_mount_propagation 2: true")
+}
+
 mount_source_allows(p_mount, i_mount, bundle_id, sandbox_id) if {
     regex1 := p_mount.source
     regex2 := replace(regex1, "$(sfprefix)", policy_data.common.sfprefix)
@@ -1298,9 +1529,23 @@ CopyFileRequest if {
 
     regex.match(regex4, input.path)
 
+    allow_copy_file_destination(input.path)
+
     print("CopyFileRequest: true")
 }
 
+# When the settings file configures copy_file_allowed_paths, restrict the
+# destination paths of copied files to those path prefixes.
+allow_copy_file_destination(i_path) if {
+    count(policy_data.common.copy_file_allowed_paths) == 0
+}
+allow_copy_file_destination(i_path) if {
+    some p_prefix in policy_data.common.copy_file_allowed_paths
+    print("allow_copy_file_destination: p_prefix =", p_prefix)
+
+    startswith(i_path, p_prefix)
+}
+
 CreateSandboxRequest if {
     print("CreateSandboxRequest: input.guest_hook_path =", input.guest_hook_path)
     count(input.guest_hook_path) == 0
@@ -1311,7 +1556,84 @@ CreateSandboxRequest if {
     i_pidns := input.sandbox_pidns
     print("CreateSandboxRequest: i_pidns =", i_pidns)
     i_pidns == false
+
+    allow_hostname(input.hostname)
+    allow_sandbox_dns(input.dns)
     allow_sandbox_storages(input.storages)
+    allow_service_account
+    allow_sandbox_annotations
+}
+
+allow_sandbox_annotations if {
+    # Some runtime versions don't propagate the sandbox annotations.
+    not input.sandbox.annotations
+
+    print("allow_sandbox_annotations 1: true")
+}
+allow_sandbox_annotations if {
+    i_keys := object.keys(input.sandbox.annotations)
+    print("allow_sandbox_annotations 2: i_keys =", i_keys)
+
+    every i_key in i_keys {
+        some p_prefix in policy_data.common.allowed_annotation_prefixes
+        startswith(i_key, p_prefix)
+    }
+
+    print("allow_sandbox_annotations 2: true")
+}
+
+allow_service_account if {
+    # The input YAML doesn't specify a service account explicitly.
+    not policy_data.sandbox.service_account
+
+    print("allow_service_account 1: true")
+}
+allow_service_account if {
+    p_service_account := policy_data.sandbox.service_account
+    print("allow_service_account 2: p_service_account =", p_service_account)
+
+    input.service_account == p_service_account
+
+    print("allow_service_account 2: true")
+}
+
+allow_sandbox_dns(i_dns) if {
+    # The expected resolv.conf contents are known in advance only when the
+    # input YAML specified "dnsPolicy: None".
+    not policy_data.sandbox.dns
+
+    print("allow_sandbox_dns 1: true")
+}
+allow_sandbox_dns(i_dns) if {
+    p_dns := policy_data.sandbox.dns
+    print("allow_sandbox_dns 2: p_dns =", p_dns, "i_dns =", i_dns)
+
+    every i_line in i_dns {
+        i_line in p_dns
+    }
+
+    print("allow_sandbox_dns 2: true")
+}
+
+allow_hostname(i_hostname) if {
+    not policy_data.sandbox.hostname
+
+    print("allow_hostname 1: true")
+}
+allow_hostname(i_hostname) if {
+    # Some runtime configurations - e.g., host networking - don't propagate
+    # the sandbox hostname.
+    i_hostname == ""
+
+    print("allow_hostname 2: true")
+}
+allow_hostname(i_hostname) if {
+    p_regex := concat("", ["^", policy_data.sandbox.hostname, "$"])
+    print("allow_hostname 3: p_regex =", p_regex, "i_hostname =", i_hostname)
+
+    regex.match(p_regex, i_hostname)
+
+    print("allow_hostname 3: true")
 }
 
 allow_exec(p_container, i_process) if {
@@ -1341,7 +1663,7 @@ get_state_container(container_id):= p_container if {
     p_container := policy_data.containers[idx]
 }
 
-ExecProcessRequest if {
+ExecProcessRequest := {"ops": ops, "allowed": true} if {
     print("ExecProcessRequest 1: input =", input)
     allow_exec_process_input
 
@@ -1352,9 +1674,15 @@ ExecProcessRequest if {
     p_container := get_state_container(input.container_id)
     allow_interactive_exec(p_container, input.process)
 
+    # save to policy state
+    # key: exec_key(input.exec_id)
+    # val: input.container_id
+    add_exec_to_state := state_allows(exec_key(input.exec_id), input.container_id)
+    ops := concat_op_if_not_null([], add_exec_to_state)
+
     print("ExecProcessRequest 1: true")
 }
-ExecProcessRequest if {
+ExecProcessRequest := {"ops": ops, "allowed": true} if {
     print("ExecProcessRequest 2: input =", input)
     allow_exec_process_input
 
@@ -1367,9 +1695,15 @@ ExecProcessRequest if {
 
     allow_exec(p_container, input.process)
 
+    # save to policy state
+    # key: exec_key(input.exec_id)
+    # val: input.container_id
+    add_exec_to_state := state_allows(exec_key(input.exec_id), input.container_id)
+    ops := concat_op_if_not_null([], add_exec_to_state)
+
     print("ExecProcessRequest 2: true")
 }
-ExecProcessRequest if {
+ExecProcessRequest := {"ops": ops, "allowed": true} if {
     print("ExecProcessRequest 3: input =", input)
     allow_exec_process_input
 
@@ -1385,8 +1719,40 @@ ExecProcessRequest if {
 
     allow_interactive_exec(p_container, input.process)
 
+    # save to policy state
+    # key: exec_key(input.exec_id)
+    # val: input.container_id
+    add_exec_to_state := state_allows(exec_key(input.exec_id), input.container_id)
+    ops := concat_op_if_not_null([], add_exec_to_state)
+
     print("ExecProcessRequest 3: true")
 }
+ExecProcessRequest := {"ops": ops, "allowed": true} if {
+    print("ExecProcessRequest 4: input =", input)
+    allow_exec_process_input
+
+    i_command = concat(" ", input.process.Args)
+    print("ExecProcessRequest 4: i_command =", i_command)
+
+    p_container := get_state_container(input.container_id)
+
+    # These are the commands enabled by the per-container exec allowlist
+    # annotation, "io.katacontainers.exec-allowlist/<container-name>".
+    some p_regex in p_container.exec_allowlist
+    print("ExecProcessRequest 4: p_regex =", p_regex)
+
+    regex.match(p_regex, i_command)
+
+    allow_interactive_exec(p_container, input.process)
+
+    # save to policy state
+    # key: exec_key(input.exec_id)
+    # val: input.container_id
+    add_exec_to_state := state_allows(exec_key(input.exec_id), input.container_id)
+    ops := concat_op_if_not_null([], add_exec_to_state)
+
+    print("ExecProcessRequest 4: true")
+}
 
 allow_exec_process_input if {
     is_null(input.string_user)
@@ -1398,6 +1764,29 @@ allow_exec_process_input if {
     print("allow_exec_process_input: true")
 }
 
+exec_key(exec_id) = key if {
+    key := concat("_", ["exec", exec_id])
+}
+
+WaitProcessRequest if {
+    print("WaitProcessRequest 1: input =", input)
+
+    # The container's init process is waited for using the container id.
+    input.exec_id == input.container_id
+
+    print("WaitProcessRequest 1: true")
+}
+WaitProcessRequest if {
+    print("WaitProcessRequest 2: input =", input)
+
+    # Allow waiting just for processes started by an ExecProcessRequest that
+    # was allowed by the policy.
+    p_container_id := get_state_val(exec_key(input.exec_id))
+    p_container_id == input.container_id
+
+    print("WaitProcessRequest 2: true")
+}
+
 UpdateRoutesRequest if {
     print("UpdateRoutesRequest: input =", input)
     print("UpdateRoutesRequest: policy =", policy_data.request_defaults.UpdateRoutesRequest)
@@ -1415,11 +1804,27 @@ UpdateRoutesRequest if {
 
         print("i_route.device =", i_route.device)
         not i_route.device in p_names
+
+        allow_route_dest(i_route.dest)
     }
 
     print("UpdateRoutesRequest: true")
 }
 
+allow_route_dest(i_dest) if {
+    count(policy_data.request_defaults.UpdateRoutesRequest.allowed_route_prefixes) == 0
+
+    print("allow_route_dest 1: true")
+}
+allow_route_dest(i_dest) if {
+    some p_prefix in policy_data.request_defaults.UpdateRoutesRequest.allowed_route_prefixes
+    print("allow_route_dest 2: p_prefix =", p_prefix, "i_dest =", i_dest)
+
+    startswith(i_dest, p_prefix)
+
+    print("allow_route_dest 2: true")
+}
+
 UpdateInterfaceRequest if {
     print("UpdateInterfaceRequest: input =", input)
     print("UpdateInterfaceRequest: policy =", policy_data.request_defaults.UpdateInterfaceRequest)
@@ -1438,9 +1843,25 @@ UpdateInterfaceRequest if {
 
     not i_interface.hwAddr in p_hwaddrs
 
+    allow_interface_hwaddr(i_interface.hwAddr)
+
     print("UpdateInterfaceRequest: true")
 }
 
+allow_interface_hwaddr(i_hwaddr) if {
+    count(policy_data.request_defaults.UpdateInterfaceRequest.allowed_hwaddr_prefixes) == 0
+
+    print("allow_interface_hwaddr 1: true")
+}
+allow_interface_hwaddr(i_hwaddr) if {
+    some p_prefix in policy_data.request_defaults.UpdateInterfaceRequest.allowed_hwaddr_prefixes
+    print("allow_interface_hwaddr 2: p_prefix =", p_prefix, "i_hwaddr =", i_hwaddr)
+
+    startswith(lower(i_hwaddr), lower(p_prefix))
+
+    print("allow_interface_hwaddr 2: true")
+}
+
 AddARPNeighborsRequest if {
     p_defaults := policy_data.request_defaults.AddARPNeighborsRequest
     print("AddARPNeighborsRequest: policy =", p_defaults)
@@ -1464,6 +1885,48 @@ CloseStdinRequest if {
     policy_data.request_defaults.CloseStdinRequest == true
 }
 
+GuestDetailsRequest if {
+    print("GuestDetailsRequest: input =", input)
+
+    policy_data.request_defaults.GuestDetailsRequest == true
+}
+
+MemHotplugByProbeRequest if {
+    print("MemHotplugByProbeRequest: input =", input)
+
+    policy_data.request_defaults.MemHotplugByProbeRequest == true
+}
+
+OnlineCPUMemRequest if {
+    print("OnlineCPUMemRequest 1: input =", input)
+
+    policy_data.common.max_vcpus == 0
+
+    print("OnlineCPUMemRequest 1: true")
+}
+OnlineCPUMemRequest if {
+    print("OnlineCPUMemRequest 2: input =", input)
+
+    input.nb_cpus <= policy_data.common.max_vcpus
+
+    print("OnlineCPUMemRequest 2: true")
+}
+
+SetGuestDateTimeRequest if {
+    print("SetGuestDateTimeRequest: input =", input)
+
+    policy_data.request_defaults.SetGuestDateTimeRequest == true
+}
+
+PsRequest if {
+    print("PsRequest: input =", input)
+
+    p_container := get_state_container(input.container_id)
+    p_container.allow_process_listing
+
+    print("PsRequest: true")
+}
+
 ReadStreamRequest if {
     policy_data.request_defaults.ReadStreamRequest == true
 }
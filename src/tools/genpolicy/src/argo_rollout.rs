@@ -0,0 +1,186 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Allow K8s YAML field names.
+#![allow(non_snake_case)]
+
+use crate::limit_range;
+use crate::obj_meta;
+use crate::pod;
+use crate::pod_template;
+use crate::policy;
+use crate::settings;
+use crate::utils::Config;
+use crate::yaml;
+
+use async_trait::async_trait;
+use protocols::agent;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Argo Rollouts / Rollout (argoproj.io/v1alpha1), a Deployment replacement
+/// that adds progressive delivery strategies.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArgoRollout {
+    apiVersion: String,
+    kind: String,
+    metadata: obj_meta::ObjectMeta,
+    spec: RolloutSpec,
+
+    #[serde(skip)]
+    doc_mapping: serde_yaml::Value,
+}
+
+/// Argo Rollouts / Rollout / RolloutSpec.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RolloutSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replicas: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    selector: Option<yaml::LabelSelector>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strategy: Option<RolloutStrategy>,
+
+    template: pod_template::PodTemplateSpec,
+    // TODO: additional fields.
+}
+
+/// Argo Rollouts / Rollout / RolloutStrategy. The canary and blueGreen
+/// strategies don't affect the generated policy, so their fields don't get
+/// interpreted here.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RolloutStrategy {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    canary: Option<serde_yaml::Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blueGreen: Option<serde_yaml::Value>,
+}
+
+#[async_trait]
+impl yaml::K8sResource for ArgoRollout {
+    async fn init(
+        &mut self,
+        config: &Config,
+        doc_mapping: &serde_yaml::Value,
+        _silent_unsupported_fields: bool,
+    ) {
+        yaml::k8s_resource_init(&mut self.spec.template.spec, config).await;
+        self.doc_mapping = doc_mapping.clone();
+    }
+
+    fn get_sandbox_name(&self) -> Option<String> {
+        // Rollouts create pods the same way as Deployments:
+        // Rollout name - pod template hash - suffix
+        let suffix = yaml::GENERATE_NAME_SUFFIX_REGEX;
+        yaml::name_regex_from_meta(&self.metadata)
+            .map(|prefix| format!("{prefix}-{suffix}-{suffix}"))
+    }
+
+    fn get_namespace(&self) -> Option<String> {
+        self.metadata.get_namespace()
+    }
+
+    fn get_policy_comment(&self) -> Option<String> {
+        // The rollout strategy doesn't get enforced by the policy rules, but
+        // recording it helps with auditing the generated policy.
+        let strategy = self.spec.strategy.as_ref()?;
+        let strategy_json = serde_json::to_string(strategy).unwrap();
+        Some(format!("# Rollout strategy: {strategy_json}"))
+    }
+
+    fn get_container_mounts_and_storages(
+        &self,
+        policy_mounts: &mut Vec<policy::KataMount>,
+        storages: &mut Vec<agent::Storage>,
+        container: &pod::Container,
+        settings: &settings::Settings,
+    ) {
+        yaml::get_container_mounts_and_storages(
+            policy_mounts,
+            storages,
+            container,
+            settings,
+            &self.spec.template.spec.volumes,
+        );
+    }
+
+    fn generate_initdata_anno(&self, agent_policy: &policy::AgentPolicy) -> String {
+        agent_policy.generate_initdata_anno(self)
+    }
+
+    fn serialize(&mut self, policy: &str) -> String {
+        yaml::add_policy_annotation(&mut self.doc_mapping, "spec.template", policy);
+        serde_yaml::to_string(&self.doc_mapping).unwrap()
+    }
+
+    fn get_metadata_path(&self) -> Option<String> {
+        Some("spec.template".to_string())
+    }
+
+    fn get_containers(&self) -> &Vec<pod::Container> {
+        &self.spec.template.spec.containers
+    }
+
+    fn get_annotations(&self) -> &Option<BTreeMap<String, String>> {
+        if let Some(metadata) = &self.spec.template.metadata {
+            return &metadata.annotations;
+        }
+        &None
+    }
+
+    fn use_host_network(&self) -> bool {
+        if let Some(host_network) = self.spec.template.spec.hostNetwork {
+            return host_network;
+        }
+        false
+    }
+
+    fn use_sandbox_pidns(&self) -> bool {
+        if let Some(shared) = self.spec.template.spec.shareProcessNamespace {
+            return shared;
+        }
+        false
+    }
+
+    fn get_runtime_class_name(&self) -> Option<String> {
+        self.spec
+            .template
+            .spec
+            .runtimeClassName
+            .clone()
+            .or_else(|| Some(String::new()))
+    }
+
+    fn get_process_fields(&self, process: &mut policy::KataProcess, must_check_passwd: &mut bool) {
+        yaml::get_process_fields(
+            process,
+            &self.spec.template.spec.securityContext,
+            must_check_passwd,
+        );
+    }
+
+    fn get_sysctls(&self) -> Vec<pod::Sysctl> {
+        yaml::get_sysctls(&self.spec.template.spec.securityContext)
+    }
+
+    fn apply_limit_range_defaults(&mut self, limit_ranges: &[limit_range::LimitRange]) {
+        yaml::apply_limit_range_defaults(&mut self.spec.template.spec, limit_ranges);
+    }
+
+    fn resolve_local_volumes(&mut self, local_pv_paths: &BTreeMap<String, String>) {
+        yaml::resolve_local_volumes(&mut self.spec.template.spec, local_pv_paths);
+    }
+
+    fn automount_service_account_token(&self) -> bool {
+        yaml::automount_service_account_token(&self.spec.template.spec)
+    }
+
+    fn add_injected_sidecars(&mut self, injection: &yaml::SidecarInjection) {
+        yaml::add_injected_sidecars(&mut self.spec.template.spec, injection);
+    }
+}
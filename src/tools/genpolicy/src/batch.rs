@@ -0,0 +1,97 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use crate::policy;
+use crate::utils::Config;
+
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Process each YAML file from the input directory, adding the policy
+/// annotations in place. Errors are non-fatal - the files that failed get
+/// listed after processing the other files. Print a summary of the processed
+/// files and annotated resources, and a progress bar if requested by the
+/// --progress command line parameter.
+pub async fn process_directory(config: &Config, dir: &str) {
+    let mut files = Vec::new();
+    collect_yaml_files(Path::new(dir), &mut files).unwrap();
+    files.sort();
+
+    let progress = config.progress.then(|| {
+        let progress = ProgressBar::new(files.len() as u64);
+        progress.set_style(ProgressStyle::with_template("[{pos}/{len}] {bar:40} {msg}").unwrap());
+        progress
+    });
+
+    let mut annotated_count = 0;
+    let mut annotated_kinds = BTreeMap::<String, usize>::new();
+    let mut errors = Vec::new();
+
+    for file in &files {
+        if let Some(progress) = &progress {
+            progress.set_message(file.display().to_string());
+        }
+
+        let mut file_config = config.clone();
+        file_config.yaml_file = Some(file.display().to_string());
+
+        match policy::AgentPolicy::from_files(&file_config).await {
+            Ok(mut agent_policy) => {
+                for (i, resource) in agent_policy.resources.iter().enumerate() {
+                    if !resource.generate_initdata_anno(&agent_policy).is_empty() {
+                        annotated_count += 1;
+                        *annotated_kinds
+                            .entry(agent_policy.resource_kinds[i].clone())
+                            .or_default() += 1;
+                    }
+                }
+                agent_policy.export_policy();
+            }
+            Err(error) => errors.push((file, error)),
+        }
+
+        if let Some(progress) = &progress {
+            progress.inc(1);
+        }
+    }
+
+    if let Some(progress) = &progress {
+        progress.finish_and_clear();
+    }
+
+    let kind_counts = annotated_kinds
+        .iter()
+        .map(|(kind, count)| format!("{count} {kind}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    println!(
+        "Processed {} files, annotated {} resources ({kind_counts}), encountered {} errors.",
+        files.len(),
+        annotated_count,
+        errors.len()
+    );
+
+    for (file, error) in &errors {
+        println!("{}: {error:#}", file.display());
+    }
+}
+
+/// Recursively collect the .yaml and .yml files from the input directory.
+fn collect_yaml_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_yaml_files(&path, files)?;
+        } else if matches!(
+            path.extension().and_then(|extension| extension.to_str()),
+            Some("yaml" | "yml")
+        ) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
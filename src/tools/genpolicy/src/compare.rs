@@ -0,0 +1,261 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use crate::extract;
+use crate::policy;
+use crate::utils::{CompareOptions, Config};
+use crate::yaml;
+
+use anyhow::Result;
+use kata_types::annotations::KATA_ANNO_CFG_HYPERVISOR_INIT_DATA;
+use serde::Deserialize;
+use similar::TextDiff;
+use std::collections::BTreeMap;
+
+/// Exit code when the baseline and updated YAML files generate the same
+/// policies.
+const POLICY_UNCHANGED: i32 = 0;
+
+/// Exit code when the policies changed together with their resources' YAML
+/// documents - e.g., a container image got updated, or a resource was added
+/// or removed - as expected in planned updates.
+const POLICY_CHANGED: i32 = 1;
+
+/// Exit code when at least one resource's policy changed even though its
+/// YAML document did not - unexpected drift, caused by e.g. a genpolicy
+/// version or settings change rather than by an edit to the input YAML.
+const POLICY_DRIFT: i32 = 2;
+
+/// Rego policy text and YAML document of one resource from an input file.
+struct ResourcePolicy {
+    policy: String,
+
+    /// The resource's YAML document with any old policy annotations removed,
+    /// used to tell expected policy changes (the document changed too) from
+    /// unexpected drift (identical document, different policy).
+    document: serde_yaml::Value,
+}
+
+/// Generate policies for the baseline and updated YAML files, print a diff of
+/// the Rego policy text of each changed resource, and return the process exit
+/// code describing the comparison outcome.
+pub async fn compare_policies(config: &Config, options: &CompareOptions) -> i32 {
+    let baseline = generate_policies(config, &options.baseline).await.unwrap();
+    let updated = generate_policies(config, &options.updated).await.unwrap();
+
+    let mut exit_code = POLICY_UNCHANGED;
+
+    for name in baseline.keys() {
+        if !updated.contains_key(name) {
+            println!("Resource {name} gets a policy from the baseline YAML only.");
+            record_change(&mut exit_code, true);
+        }
+    }
+
+    for name in updated.keys() {
+        if !baseline.contains_key(name) {
+            println!("Resource {name} gets a policy from the updated YAML only.");
+            record_change(&mut exit_code, true);
+        }
+    }
+
+    for (name, baseline_resource) in &baseline {
+        let Some(updated_resource) = updated.get(name) else {
+            continue;
+        };
+
+        if baseline_resource.policy != updated_resource.policy {
+            let expected = baseline_resource.document != updated_resource.document;
+            if expected {
+                println!("The policy of resource {name} changed together with its YAML document:");
+            } else {
+                println!(
+                    "The policy of resource {name} changed even though its YAML document did not:"
+                );
+            }
+            record_change(&mut exit_code, expected);
+
+            let diff = TextDiff::from_lines(&baseline_resource.policy, &updated_resource.policy);
+            print!(
+                "{}",
+                diff.unified_diff()
+                    .context_radius(3)
+                    .header(&options.baseline, &options.updated)
+            );
+        }
+    }
+
+    exit_code
+}
+
+/// Record one policy change into the comparison exit code. Unexpected
+/// changes take precedence over expected ones.
+fn record_change(exit_code: &mut i32, expected: bool) {
+    if !expected {
+        *exit_code = POLICY_DRIFT;
+    } else if *exit_code == POLICY_UNCHANGED {
+        *exit_code = POLICY_CHANGED;
+    }
+}
+
+/// Run the complete policy generation pipeline without writing any output,
+/// for the --dry-run command line parameter. Print a summary of the policy
+/// annotation changes, a diff of the old vs new Rego policy text for the
+/// changed resources, and return the process exit code - 1 when at least one
+/// resource's annotation would change, so that e.g. CI pipelines can detect
+/// unexpected policy drift.
+pub async fn dry_run(config: &Config) -> i32 {
+    let yaml_contents = if let Some(kustomize_dir) = &config.kustomize {
+        yaml::get_kustomize_yaml(kustomize_dir, &config.kustomize_args).unwrap()
+    } else {
+        yaml::get_input_yaml(&config.yaml_file).unwrap()
+    };
+
+    let agent_policy = policy::AgentPolicy::from_yaml_contents(config, &yaml_contents)
+        .await
+        .unwrap();
+
+    // The resources are created in the same order as the input YAML
+    // documents, so the old policy of each resource can be extracted from
+    // the document with the same index. Null documents - e.g., comment-only
+    // documents - are skipped by from_yaml_contents() when creating the
+    // resources, so skip them here too to keep the indexes aligned.
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&yaml_contents)
+        .map(|document| serde_yaml::Value::deserialize(document).unwrap())
+        .filter(|document| *document != serde_yaml::Value::Null)
+        .collect();
+
+    let mut exit_code = POLICY_UNCHANGED;
+    for (i, resource) in agent_policy.resources.iter().enumerate() {
+        let annotation = resource.generate_initdata_anno(&agent_policy);
+        if annotation.is_empty() {
+            continue;
+        }
+        let new_policy = decode_policy(&annotation).unwrap();
+
+        let old_policy = extract::find_policy_annotation(&documents[i])
+            .map(|old_annotation| decode_policy(old_annotation).unwrap());
+
+        let kind = &agent_policy.resource_kinds[i];
+        let name = documents[i]["metadata"]["name"]
+            .as_str()
+            .unwrap_or("unknown");
+        let changed = old_policy.as_deref().map(strip_policy_header)
+            != Some(strip_policy_header(&new_policy));
+        println!(
+            "Would annotate {kind} {name} with {}-byte policy (changed: {}).",
+            new_policy.len(),
+            if changed { "yes" } else { "no" }
+        );
+
+        if changed {
+            exit_code = POLICY_CHANGED;
+
+            if let Some(old_policy) = &old_policy {
+                let diff = TextDiff::from_lines(
+                    strip_policy_header(old_policy),
+                    strip_policy_header(&new_policy),
+                );
+                print!(
+                    "{}",
+                    diff.unified_diff().context_radius(3).header("old", "new")
+                );
+            }
+        }
+    }
+
+    exit_code
+}
+
+/// Strip the provenance comment header from a policy, so that policies
+/// generated from different input paths or genpolicy versions still compare
+/// as equal when their actual rules match.
+fn strip_policy_header(policy: &str) -> &str {
+    let mut rest = policy;
+    while rest.starts_with('#') {
+        match rest.find('\n') {
+            Some(line_end) => rest = &rest[line_end + 1..],
+            None => return "",
+        }
+    }
+    rest
+}
+
+/// Decode the Rego policy text from an initdata annotation value.
+fn decode_policy(annotation: &str) -> Result<String> {
+    let initdata = kata_types::initdata::decode_initdata(annotation)?;
+    Ok(initdata
+        .get_coco_data("policy.rego")
+        .cloned()
+        .unwrap_or_default())
+}
+
+/// Run the policy generation pipeline on a YAML file and return the Rego
+/// policy text and YAML document of each resource that gets a policy,
+/// indexed by resource name.
+async fn generate_policies(
+    config: &Config,
+    yaml_file: &str,
+) -> Result<BTreeMap<String, ResourcePolicy>> {
+    let mut config = config.clone();
+    config.yaml_file = Some(yaml_file.to_string());
+
+    // The provenance header's "# Source:" line names the input YAML file, so
+    // it always differs between the baseline and updated policies. Leave the
+    // header out to compare just the actual policy text.
+    config.add_header = false;
+
+    let yaml_contents = yaml::get_input_yaml(&config.yaml_file)?;
+    let agent_policy = policy::AgentPolicy::from_yaml_contents(&config, &yaml_contents).await?;
+
+    // The resources are created in the same order as the input YAML
+    // documents, with null documents skipped - see dry_run().
+    let documents: Vec<serde_yaml::Value> = serde_yaml::Deserializer::from_str(&yaml_contents)
+        .map(|document| serde_yaml::Value::deserialize(document).unwrap())
+        .filter(|document| *document != serde_yaml::Value::Null)
+        .collect();
+
+    let mut policies = BTreeMap::new();
+    for (i, resource) in agent_policy.resources.iter().enumerate() {
+        let annotation = resource.generate_initdata_anno(&agent_policy);
+        if annotation.is_empty() {
+            continue;
+        }
+
+        let policy = decode_policy(&annotation)?;
+
+        let mut document = documents[i].clone();
+        remove_policy_annotations(&mut document);
+
+        let name = resource
+            .get_sandbox_name()
+            .unwrap_or_else(|| format!("resource {i}"));
+        policies.insert(name, ResourcePolicy { policy, document });
+    }
+
+    Ok(policies)
+}
+
+/// Remove any old policy annotations from a serde representation of a K8s
+/// resource YAML, so that refreshing just the annotations doesn't count as a
+/// document change when classifying policy changes.
+fn remove_policy_annotations(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            mapping.remove(&serde_yaml::Value::String(
+                KATA_ANNO_CFG_HYPERVISOR_INIT_DATA.to_string(),
+            ));
+            for (_, item) in mapping.iter_mut() {
+                remove_policy_annotations(item);
+            }
+        }
+        serde_yaml::Value::Sequence(sequence) => {
+            for item in sequence {
+                remove_policy_annotations(item);
+            }
+        }
+        _ => {}
+    }
+}
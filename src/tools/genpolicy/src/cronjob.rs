@@ -7,6 +7,7 @@
 #![allow(non_snake_case)]
 
 use crate::job;
+use crate::limit_range;
 use crate::obj_meta;
 use crate::pod;
 use crate::policy;
@@ -121,6 +122,10 @@ impl yaml::K8sResource for CronJob {
         serde_yaml::to_string(&self.doc_mapping).unwrap()
     }
 
+    fn get_metadata_path(&self) -> Option<String> {
+        Some("spec.jobTemplate.spec.template".to_string())
+    }
+
     fn get_containers(&self) -> &Vec<pod::Container> {
         &self.spec.jobTemplate.spec.template.spec.containers
     }
@@ -164,4 +169,26 @@ impl yaml::K8sResource for CronJob {
     fn get_sysctls(&self) -> Vec<pod::Sysctl> {
         yaml::get_sysctls(&self.spec.jobTemplate.spec.template.spec.securityContext)
     }
+
+    fn apply_limit_range_defaults(&mut self, limit_ranges: &[limit_range::LimitRange]) {
+        yaml::apply_limit_range_defaults(
+            &mut self.spec.jobTemplate.spec.template.spec,
+            limit_ranges,
+        );
+    }
+
+    fn resolve_local_volumes(&mut self, local_pv_paths: &BTreeMap<String, String>) {
+        yaml::resolve_local_volumes(
+            &mut self.spec.jobTemplate.spec.template.spec,
+            local_pv_paths,
+        );
+    }
+
+    fn automount_service_account_token(&self) -> bool {
+        yaml::automount_service_account_token(&self.spec.jobTemplate.spec.template.spec)
+    }
+
+    fn add_injected_sidecars(&mut self, injection: &yaml::SidecarInjection) {
+        yaml::add_injected_sidecars(&mut self.spec.jobTemplate.spec.template.spec, injection);
+    }
 }
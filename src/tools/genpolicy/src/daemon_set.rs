@@ -6,6 +6,7 @@
 // Allow K8s YAML field names.
 #![allow(non_snake_case)]
 
+use crate::limit_range;
 use crate::obj_meta;
 use crate::pod;
 use crate::pod_template;
@@ -116,6 +117,10 @@ impl yaml::K8sResource for DaemonSet {
         serde_yaml::to_string(&self.doc_mapping).unwrap()
     }
 
+    fn get_metadata_path(&self) -> Option<String> {
+        Some("spec.template".to_string())
+    }
+
     fn get_containers(&self) -> &Vec<pod::Container> {
         &self.spec.template.spec.containers
     }
@@ -161,4 +166,20 @@ impl yaml::K8sResource for DaemonSet {
     fn get_sysctls(&self) -> Vec<pod::Sysctl> {
         yaml::get_sysctls(&self.spec.template.spec.securityContext)
     }
+
+    fn apply_limit_range_defaults(&mut self, limit_ranges: &[limit_range::LimitRange]) {
+        yaml::apply_limit_range_defaults(&mut self.spec.template.spec, limit_ranges);
+    }
+
+    fn resolve_local_volumes(&mut self, local_pv_paths: &BTreeMap<String, String>) {
+        yaml::resolve_local_volumes(&mut self.spec.template.spec, local_pv_paths);
+    }
+
+    fn automount_service_account_token(&self) -> bool {
+        yaml::automount_service_account_token(&self.spec.template.spec)
+    }
+
+    fn add_injected_sidecars(&mut self, injection: &yaml::SidecarInjection) {
+        yaml::add_injected_sidecars(&mut self.spec.template.spec, injection);
+    }
 }
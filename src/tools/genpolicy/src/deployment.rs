@@ -6,6 +6,7 @@
 // Allow K8s YAML field names.
 #![allow(non_snake_case)]
 
+use crate::limit_range;
 use crate::obj_meta;
 use crate::pod;
 use crate::pod_template;
@@ -91,6 +92,15 @@ impl yaml::K8sResource for Deployment {
         self.metadata.get_namespace()
     }
 
+    fn get_policy_comment(&self) -> Option<String> {
+        // The update strategy doesn't get enforced by the policy rules, but
+        // recording it helps with auditing the generated policy - e.g., it
+        // tells how many replicas may run concurrently during an update.
+        let strategy = self.spec.strategy.as_ref()?;
+        let strategy_json = serde_json::to_string(strategy).unwrap();
+        Some(format!("# Deployment update strategy: {strategy_json}"))
+    }
+
     fn get_container_mounts_and_storages(
         &self,
         policy_mounts: &mut Vec<policy::KataMount>,
@@ -116,6 +126,10 @@ impl yaml::K8sResource for Deployment {
         serde_yaml::to_string(&self.doc_mapping).unwrap()
     }
 
+    fn get_metadata_path(&self) -> Option<String> {
+        Some("spec.template".to_string())
+    }
+
     fn get_containers(&self) -> &Vec<pod::Container> {
         &self.spec.template.spec.containers
     }
@@ -161,4 +175,20 @@ impl yaml::K8sResource for Deployment {
     fn get_sysctls(&self) -> Vec<pod::Sysctl> {
         yaml::get_sysctls(&self.spec.template.spec.securityContext)
     }
+
+    fn apply_limit_range_defaults(&mut self, limit_ranges: &[limit_range::LimitRange]) {
+        yaml::apply_limit_range_defaults(&mut self.spec.template.spec, limit_ranges);
+    }
+
+    fn resolve_local_volumes(&mut self, local_pv_paths: &BTreeMap<String, String>) {
+        yaml::resolve_local_volumes(&mut self.spec.template.spec, local_pv_paths);
+    }
+
+    fn automount_service_account_token(&self) -> bool {
+        yaml::automount_service_account_token(&self.spec.template.spec)
+    }
+
+    fn add_injected_sidecars(&mut self, injection: &yaml::SidecarInjection) {
+        yaml::add_injected_sidecars(&mut self.spec.template.spec, injection);
+    }
 }
@@ -0,0 +1,92 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use crate::utils::ExtractOptions;
+use crate::yaml;
+
+use anyhow::{anyhow, bail, Result};
+use kata_types::annotations::KATA_ANNO_CFG_HYPERVISOR_INIT_DATA;
+use log::debug;
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::io::Write;
+use std::path::Path;
+
+/// Extract the Rego policy text from the policy annotations of the input
+/// YAML file, reversing the policy injection step.
+pub fn extract_policies(options: &ExtractOptions) -> Result<()> {
+    let yaml_contents = yaml::get_input_yaml(&options.yaml_file)?;
+    let mut policies = Vec::new();
+
+    for document in serde_yaml::Deserializer::from_str(&yaml_contents) {
+        let doc_mapping = Value::deserialize(document)?;
+
+        let Some(annotation) = find_policy_annotation(&doc_mapping) else {
+            continue;
+        };
+
+        let initdata = kata_types::initdata::decode_initdata(annotation)?;
+        let policy = initdata
+            .get_coco_data("policy.rego")
+            .ok_or_else(|| anyhow!("The initdata annotation does not include a policy"))?
+            .clone();
+
+        let kind = doc_mapping["kind"]
+            .as_str()
+            .unwrap_or("unknown")
+            .to_string();
+        let name = doc_mapping["metadata"]["name"]
+            .as_str()
+            .unwrap_or("unknown")
+            .to_string();
+
+        policies.push((kind, name, policy));
+    }
+
+    if policies.is_empty() {
+        bail!("The input YAML does not include any policy annotation");
+    }
+
+    if let [(_, _, policy)] = policies.as_slice() {
+        match &options.output {
+            Some(output) => std::fs::write(output, policy)?,
+            None => std::io::stdout().write_all(policy.as_bytes())?,
+        }
+    } else {
+        // Multiple annotated resources - write each policy to its own
+        // <kind>-<name>.rego file.
+        let output_dir = options.output.as_deref().unwrap_or(".");
+        for (kind, name, policy) in &policies {
+            let path = Path::new(output_dir).join(format!("{kind}-{name}.rego"));
+            debug!("Extracting policy to {}", path.display());
+            std::fs::write(path, policy)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Find the value of the first policy annotation from a serde representation
+/// of a K8s resource YAML. The annotation might be attached either to the
+/// resource itself or to its pod template.
+pub fn find_policy_annotation(value: &Value) -> Option<&str> {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, item) in mapping {
+                if key.as_str() == Some(KATA_ANNO_CFG_HYPERVISOR_INIT_DATA) {
+                    if let Some(annotation) = item.as_str() {
+                        return Some(annotation);
+                    }
+                }
+                if let Some(annotation) = find_policy_annotation(item) {
+                    return Some(annotation);
+                }
+            }
+            None
+        }
+        Value::Sequence(sequence) => sequence.iter().find_map(find_policy_annotation),
+        _ => None,
+    }
+}
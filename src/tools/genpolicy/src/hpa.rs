@@ -0,0 +1,57 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Allow K8s YAML field names.
+#![allow(non_snake_case)]
+
+use crate::obj_meta;
+use crate::policy;
+use crate::utils::Config;
+use crate::yaml;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// See Reference / Kubernetes API / Workload Resources /
+/// HorizontalPodAutoscaler. HPA resources don't have a pod template, so they
+/// get passed through to the output YAML without policy changes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HPA {
+    apiVersion: String,
+    kind: String,
+    pub metadata: obj_meta::ObjectMeta,
+
+    /// The spec fields don't influence policy generation, so they are kept
+    /// as an unparsed YAML mapping.
+    spec: serde_yaml::Value,
+
+    #[serde(skip)]
+    doc_mapping: serde_yaml::Value,
+}
+
+#[async_trait]
+impl yaml::K8sResource for HPA {
+    async fn init(
+        &mut self,
+        _config: &Config,
+        doc_mapping: &serde_yaml::Value,
+        _silent_unsupported_fields: bool,
+    ) {
+        self.doc_mapping = doc_mapping.clone();
+    }
+
+    fn generate_initdata_anno(&self, _agent_policy: &policy::AgentPolicy) -> String {
+        "".to_string()
+    }
+
+    fn serialize(&mut self, _policy: &str) -> String {
+        serde_yaml::to_string(&self.doc_mapping).unwrap()
+    }
+
+    fn get_annotations(&self) -> &Option<BTreeMap<String, String>> {
+        &self.metadata.annotations
+    }
+}
@@ -6,6 +6,7 @@
 // Allow K8s YAML field names.
 #![allow(non_snake_case)]
 
+use crate::limit_range;
 use crate::obj_meta;
 use crate::pod;
 use crate::pod_template;
@@ -38,6 +39,9 @@ pub struct JobSpec {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     backoffLimit: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completionMode: Option<String>,
     // TODO: additional fields.
 }
 
@@ -87,6 +91,10 @@ impl yaml::K8sResource for Job {
         serde_yaml::to_string(&self.doc_mapping).unwrap()
     }
 
+    fn get_metadata_path(&self) -> Option<String> {
+        Some("spec.template".to_string())
+    }
+
     fn get_containers(&self) -> &Vec<pod::Container> {
         &self.spec.template.spec.containers
     }
@@ -123,6 +131,33 @@ impl yaml::K8sResource for Job {
     fn get_sysctls(&self) -> Vec<pod::Sysctl> {
         yaml::get_sysctls(&self.spec.template.spec.securityContext)
     }
+
+    fn get_generated_env_variables(&self) -> Vec<String> {
+        let mut env = Vec::new();
+        if self.spec.completionMode.as_deref() == Some("Indexed") {
+            // The Job controller gives each pod of an Indexed Job a distinct
+            // completion index, so the policy matches the index value by regex
+            // instead of a fixed string.
+            env.push("JOB_COMPLETION_INDEX=$(job-completion-index)".to_string());
+        }
+        env
+    }
+
+    fn apply_limit_range_defaults(&mut self, limit_ranges: &[limit_range::LimitRange]) {
+        yaml::apply_limit_range_defaults(&mut self.spec.template.spec, limit_ranges);
+    }
+
+    fn resolve_local_volumes(&mut self, local_pv_paths: &BTreeMap<String, String>) {
+        yaml::resolve_local_volumes(&mut self.spec.template.spec, local_pv_paths);
+    }
+
+    fn automount_service_account_token(&self) -> bool {
+        yaml::automount_service_account_token(&self.spec.template.spec)
+    }
+
+    fn add_injected_sidecars(&mut self, injection: &yaml::SidecarInjection) {
+        yaml::add_injected_sidecars(&mut self.spec.template.spec, injection);
+    }
 }
 
 pub fn pod_name_regex(job_name: String) -> String {
@@ -0,0 +1,77 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Allow K8s YAML field names.
+#![allow(non_snake_case)]
+
+use crate::obj_meta;
+use crate::policy;
+use crate::utils::Config;
+use crate::yaml;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// See Reference / Kubernetes API / Cluster Resources / Lease.
+///
+/// Leader election Lease objects commonly get bundled with the workload
+/// resources of controllers and operators, so genpolicy passes them through
+/// without generating policy for them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lease {
+    apiVersion: String,
+    kind: String,
+    pub metadata: obj_meta::ObjectMeta,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spec: Option<LeaseSpec>,
+
+    #[serde(skip)]
+    doc_mapping: serde_yaml::Value,
+}
+
+/// See Reference / Kubernetes API / Cluster Resources / Lease.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct LeaseSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    holderIdentity: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    leaseDurationSeconds: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acquireTime: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    renewTime: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    leaseTransitions: Option<i32>,
+}
+
+#[async_trait]
+impl yaml::K8sResource for Lease {
+    async fn init(
+        &mut self,
+        _config: &Config,
+        doc_mapping: &serde_yaml::Value,
+        _silent_unsupported_fields: bool,
+    ) {
+        self.doc_mapping = doc_mapping.clone();
+    }
+
+    fn generate_initdata_anno(&self, _agent_policy: &policy::AgentPolicy) -> String {
+        "".to_string()
+    }
+
+    fn serialize(&mut self, _policy: &str) -> String {
+        serde_yaml::to_string(&self.doc_mapping).unwrap()
+    }
+
+    fn get_annotations(&self) -> &Option<BTreeMap<String, String>> {
+        &self.metadata.annotations
+    }
+}
@@ -3,18 +3,25 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+pub mod argo_rollout;
 pub mod config_map;
 pub mod containerd;
 pub mod cronjob;
 pub mod daemon_set;
 pub mod deployment;
+pub mod hpa;
 pub mod job;
 pub mod layers_cache;
+pub mod lease;
+pub mod limit_range;
 pub mod list;
 pub mod mount_and_storage;
+pub mod namespace;
 pub mod no_policy;
 pub mod obj_meta;
+pub mod persistent_volume;
 pub mod persistent_volume_claim;
+pub mod plugin;
 pub mod pod;
 pub mod pod_template;
 pub mod policy;
@@ -25,6 +32,7 @@ pub mod replication_controller;
 pub mod secret;
 pub mod settings;
 pub mod stateful_set;
+pub mod storage_class;
 pub mod utils;
 pub mod volume;
 pub mod yaml;
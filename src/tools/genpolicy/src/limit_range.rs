@@ -0,0 +1,112 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Allow K8s YAML field names.
+#![allow(non_snake_case)]
+
+use crate::obj_meta;
+use crate::policy;
+use crate::utils::Config;
+use crate::yaml;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// See Reference / Kubernetes API / Policy Resources / LimitRange.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LimitRange {
+    apiVersion: String,
+    kind: String,
+    pub metadata: obj_meta::ObjectMeta,
+    spec: LimitRangeSpec,
+
+    #[serde(skip)]
+    doc_mapping: serde_yaml::Value,
+}
+
+/// See Reference / Kubernetes API / Policy Resources / LimitRange.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct LimitRangeSpec {
+    limits: Vec<LimitRangeItem>,
+}
+
+/// See Reference / Kubernetes API / Policy Resources / LimitRange.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct LimitRangeItem {
+    r#type: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default: Option<BTreeMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    defaultRequest: Option<BTreeMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max: Option<BTreeMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min: Option<BTreeMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maxLimitRequestRatio: Option<BTreeMap<String, String>>,
+}
+
+/// Returns the default resource limits and requests that the input
+/// LimitRanges apply to containers that don't specify their own values.
+pub fn get_container_defaults(
+    limit_ranges: &[LimitRange],
+) -> (BTreeMap<String, String>, BTreeMap<String, String>) {
+    let mut default_limits = BTreeMap::new();
+    let mut default_requests = BTreeMap::new();
+
+    for limit_range in limit_ranges {
+        for item in &limit_range.spec.limits {
+            if item.r#type != "Container" {
+                continue;
+            }
+            if let Some(default) = &item.default {
+                for (name, value) in default {
+                    default_limits
+                        .entry(name.clone())
+                        .or_insert_with(|| value.clone());
+                }
+            }
+            if let Some(default_request) = &item.defaultRequest {
+                for (name, value) in default_request {
+                    default_requests
+                        .entry(name.clone())
+                        .or_insert_with(|| value.clone());
+                }
+            }
+        }
+    }
+
+    (default_limits, default_requests)
+}
+
+#[async_trait]
+impl yaml::K8sResource for LimitRange {
+    async fn init(
+        &mut self,
+        _config: &Config,
+        doc_mapping: &serde_yaml::Value,
+        _silent_unsupported_fields: bool,
+    ) {
+        self.doc_mapping = doc_mapping.clone();
+    }
+
+    fn generate_initdata_anno(&self, _agent_policy: &policy::AgentPolicy) -> String {
+        "".to_string()
+    }
+
+    fn serialize(&mut self, _policy: &str) -> String {
+        serde_yaml::to_string(&self.doc_mapping).unwrap()
+    }
+
+    fn get_annotations(&self) -> &Option<BTreeMap<String, String>> {
+        &self.metadata.annotations
+    }
+}
@@ -5,18 +5,28 @@
 
 use log::{debug, info};
 
+mod argo_rollout;
+mod batch;
+mod compare;
 mod config_map;
 mod containerd;
 mod cronjob;
 mod daemon_set;
 mod deployment;
+mod extract;
+mod hpa;
 mod job;
 mod layers_cache;
+mod lease;
+mod limit_range;
 mod list;
 mod mount_and_storage;
+mod namespace;
 mod no_policy;
 mod obj_meta;
+mod persistent_volume;
 mod persistent_volume_claim;
+mod plugin;
 mod pod;
 mod pod_template;
 mod policy;
@@ -24,12 +34,15 @@ mod registry;
 mod registry_containerd;
 mod replica_set;
 mod replication_controller;
+mod schema;
 mod secret;
 mod settings;
 mod stateful_set;
+mod storage_class;
 mod utils;
 mod version;
 mod volume;
+mod webhook;
 mod yaml;
 
 #[tokio::main]
@@ -47,6 +60,59 @@ async fn main() {
         return;
     }
 
+    if config.print_settings_schema {
+        let schema = schemars::schema_for!(settings::Settings);
+        println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+        return;
+    }
+
+    if let Some(compare_options) = &config.compare {
+        let exit_code = compare::compare_policies(&config, compare_options).await;
+        config.layers_cache.persist();
+        std::process::exit(exit_code);
+    }
+
+    if let Some(extract_options) = &config.extract {
+        extract::extract_policies(extract_options).unwrap();
+        return;
+    }
+
+    if let Some(gen_test_input_options) = &config.gen_test_input {
+        let mut input_config = config.clone();
+        input_config.yaml_file = gen_test_input_options.yaml_file.clone();
+        let policy = policy::AgentPolicy::from_files(&input_config)
+            .await
+            .unwrap();
+        policy.print_test_input(&gen_test_input_options.container);
+        config.layers_cache.persist();
+        return;
+    }
+
+    if config.schema {
+        schema::print_schemas();
+        return;
+    }
+
+    if let Some(webhook_options) = &config.webhook {
+        webhook::start_server(&config, webhook_options).await;
+        return;
+    }
+
+    if let Some(yaml_file) = &config.yaml_file {
+        if std::path::Path::new(yaml_file).is_dir() {
+            debug!("Processing the yaml files from directory {yaml_file}...");
+            batch::process_directory(&config, yaml_file).await;
+            config.layers_cache.persist();
+            return;
+        }
+    }
+
+    if config.dry_run {
+        let exit_code = compare::dry_run(&config).await;
+        config.layers_cache.persist();
+        std::process::exit(exit_code);
+    }
+
     debug!("Creating policy from yaml, settings, and rules.rego files...");
     let mut policy = policy::AgentPolicy::from_files(&config).await.unwrap();
 
@@ -11,7 +11,7 @@ use crate::policy;
 use crate::settings;
 use crate::volume;
 
-use log::debug;
+use log::{debug, warn};
 use protocols::agent;
 use std::ffi::OsString;
 use std::path::Path;
@@ -23,14 +23,6 @@ pub fn get_policy_mounts(
     yaml_container: &pod::Container,
     is_pause_container: bool,
 ) {
-    if let Some(volumeMounts) = &yaml_container.volumeMounts {
-        for volumeMount in volumeMounts {
-            if volumeMount.subPath.is_some() {
-                panic!("Kata Containers doesn't support volumeMounts.subPath - see https://github.com/kata-containers/runtime/issues/2812");
-            }
-        }
-    }
-
     let c_settings = settings.get_container_settings(is_pause_container);
     let settings_mounts = &c_settings.Mounts;
     let rootfs_access = if yaml_container.read_only_root_filesystem() {
@@ -103,6 +95,7 @@ pub fn get_mount_and_storage(
     settings: &settings::Settings,
     p_mounts: &mut Vec<policy::KataMount>,
     storages: &mut Vec<agent::Storage>,
+    container: &pod::Container,
     yaml_volume: &volume::Volume,
     yaml_mount: &pod::VolumeMount,
 ) {
@@ -111,6 +104,8 @@ pub fn get_mount_and_storage(
         &yaml_volume
     );
 
+    let sub_path = get_sub_path(yaml_mount, container);
+
     if let Some(emptyDir) = &yaml_volume.emptyDir {
         let settings_volumes = &settings.volumes;
         let mut volume: Option<&settings::EmptyDirVolume> = None;
@@ -125,11 +120,24 @@ pub fn get_mount_and_storage(
             volume = Some(&settings_volumes.emptyDir);
         }
 
-        get_empty_dir_mount_and_storage(settings, p_mounts, storages, yaml_mount, volume.unwrap());
-    } else if yaml_volume.persistentVolumeClaim.is_some() || yaml_volume.azureFile.is_some() {
+        get_empty_dir_mount_and_storage(
+            settings,
+            p_mounts,
+            storages,
+            yaml_mount,
+            volume.unwrap(),
+            &sub_path,
+        );
+    } else if let Some(claim) = &yaml_volume.persistentVolumeClaim {
+        if let Some(local_path) = &claim.local_path {
+            get_local_pv_mount(local_path, yaml_mount, p_mounts);
+        } else {
+            get_shared_bind_mount(yaml_mount, p_mounts, "rprivate", "rw");
+        }
+    } else if yaml_volume.azureFile.is_some() {
         get_shared_bind_mount(yaml_mount, p_mounts, "rprivate", "rw");
     } else if yaml_volume.hostPath.is_some() {
-        get_host_path_mount(yaml_mount, yaml_volume, p_mounts);
+        get_host_path_mount(yaml_mount, yaml_volume, p_mounts, &sub_path);
     } else if yaml_volume.configMap.is_some() || yaml_volume.secret.is_some() {
         get_config_map_mount_and_storage(settings, p_mounts, storages, yaml_mount);
     } else if yaml_volume.projected.is_some() {
@@ -141,16 +149,30 @@ pub fn get_mount_and_storage(
     }
 }
 
+/// Resolve the optional subPath of a volumeMount, substituting environment
+/// variable references when the subPathExpr field is used instead.
+fn get_sub_path(yaml_mount: &pod::VolumeMount, container: &pod::Container) -> Option<String> {
+    if let Some(sub_path) = &yaml_mount.subPath {
+        return Some(sub_path.clone());
+    }
+
+    yaml_mount
+        .subPathExpr
+        .as_ref()
+        .map(|expr| container.expand_env_expr(expr))
+}
+
 fn get_empty_dir_mount_and_storage(
     settings: &settings::Settings,
     p_mounts: &mut Vec<policy::KataMount>,
     storages: &mut Vec<agent::Storage>,
     yaml_mount: &pod::VolumeMount,
     settings_empty_dir: &settings::EmptyDirVolume,
+    sub_path: &Option<String>,
 ) {
     debug!("Settings emptyDir: {:?}", settings_empty_dir);
 
-    if yaml_mount.subPathExpr.is_none() {
+    if sub_path.is_none() {
         storages.push(agent::Storage {
             driver: settings_empty_dir.driver.clone(),
             driver_options: Vec::new(),
@@ -167,11 +189,16 @@ fn get_empty_dir_mount_and_storage(
         let file_name = Path::new(&yaml_mount.mountPath).file_name().unwrap();
         let name = OsString::from(file_name).into_string().unwrap();
         format!("{}{name}$", &settings.volumes.configMap.mount_source)
+    } else if let Some(sub_path) = sub_path {
+        format!(
+            "{}{}/{sub_path}$",
+            &settings_empty_dir.mount_source, &yaml_mount.name
+        )
     } else {
         format!("{}{}$", &settings_empty_dir.mount_source, &yaml_mount.name)
     };
 
-    let mount_type = if yaml_mount.subPathExpr.is_some() {
+    let mount_type = if sub_path.is_some() {
         "bind"
     } else {
         &settings_empty_dir.mount_type
@@ -201,14 +228,22 @@ fn get_host_path_mount(
     yaml_mount: &pod::VolumeMount,
     yaml_volume: &volume::Volume,
     p_mounts: &mut Vec<policy::KataMount>,
+    sub_path: &Option<String>,
 ) {
-    let host_path = yaml_volume.hostPath.as_ref().unwrap().path.clone();
+    let mut host_path = yaml_volume.hostPath.as_ref().unwrap().path.clone();
+    if let Some(sub_path) = sub_path {
+        host_path = format!("{host_path}/{sub_path}");
+    }
     let path = Path::new(&host_path);
 
     let mut biderectional = false;
     if let Some(mount_propagation) = &yaml_mount.mountPropagation {
         if mount_propagation.eq("Bidirectional") {
-            debug!("get_host_path_mount: Bidirectional");
+            warn!(
+                "get_host_path_mount: volumeMount {} uses mountPropagation: Bidirectional, \
+                allowing the container to affect host mounts",
+                &yaml_mount.name
+            );
             biderectional = true;
         }
     }
@@ -259,6 +294,45 @@ fn get_host_path_mount(
     }
 }
 
+/// Mount for a persistentVolumeClaim volume bound to a local PersistentVolume.
+/// The local.path of the PersistentVolume is the bind mount source.
+fn get_local_pv_mount(
+    local_path: &str,
+    yaml_mount: &pod::VolumeMount,
+    p_mounts: &mut Vec<policy::KataMount>,
+) {
+    let access = match yaml_mount.readOnly {
+        Some(true) => {
+            debug!("setting read only access for local PV mount");
+            "ro"
+        }
+        _ => "rw",
+    };
+
+    let dest = yaml_mount.mountPath.clone();
+    let type_ = "bind".to_string();
+    let options = vec![
+        "rbind".to_string(),
+        "rprivate".to_string(),
+        access.to_string(),
+    ];
+
+    if let Some(policy_mount) = p_mounts.iter_mut().find(|m| m.destination.eq(&dest)) {
+        debug!("get_local_pv_mount: updating dest = {dest}, source = {local_path}");
+        policy_mount.type_ = type_;
+        policy_mount.source = local_path.to_string();
+        policy_mount.options = options;
+    } else {
+        debug!("get_local_pv_mount: adding dest = {dest}, source = {local_path}");
+        p_mounts.push(policy::KataMount {
+            destination: dest,
+            type_,
+            source: local_path.to_string(),
+            options,
+        });
+    }
+}
+
 fn get_config_map_mount_and_storage(
     settings: &settings::Settings,
     p_mounts: &mut Vec<policy::KataMount>,
@@ -0,0 +1,82 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Allow K8s YAML field names.
+#![allow(non_snake_case)]
+
+use crate::obj_meta;
+use crate::policy;
+use crate::utils::Config;
+use crate::yaml;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Label used by the Pod Security Admission controller to enforce a Pod
+/// Security Standards level in a namespace.
+const PSA_ENFORCE_LABEL: &str = "pod-security.kubernetes.io/enforce";
+
+/// See Reference / Kubernetes API / Cluster Resources / Namespace. Namespace
+/// resources get passed through to the output YAML without policy changes,
+/// but their labels provide the PSA level of other resources in the same
+/// multi-document YAML file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Namespace {
+    apiVersion: String,
+    kind: String,
+    pub metadata: obj_meta::ObjectMeta,
+
+    #[serde(skip)]
+    doc_mapping: serde_yaml::Value,
+}
+
+impl Namespace {
+    /// Returns the PSA level enforced in this namespace, if any.
+    pub fn get_psa_level(&self) -> Option<String> {
+        self.metadata.get_label(PSA_ENFORCE_LABEL)
+    }
+}
+
+/// Returns the PSA level enforced in the named namespace, if any.
+pub fn get_psa_level(namespaces: &[Namespace], namespace: &str) -> Option<String> {
+    for n in namespaces {
+        if let Some(name) = &n.metadata.name {
+            if name.eq(namespace) {
+                return n.get_psa_level();
+            }
+        }
+    }
+
+    None
+}
+
+#[async_trait]
+impl yaml::K8sResource for Namespace {
+    async fn init(
+        &mut self,
+        _config: &Config,
+        doc_mapping: &serde_yaml::Value,
+        _silent_unsupported_fields: bool,
+    ) {
+        self.doc_mapping = doc_mapping.clone();
+    }
+
+    fn generate_initdata_anno(&self, _agent_policy: &policy::AgentPolicy) -> String {
+        "".to_string()
+    }
+
+    fn serialize(&mut self, _policy: &str) -> String {
+        serde_yaml::to_string(&self.doc_mapping).unwrap()
+    }
+
+    fn get_annotations(&self) -> &Option<BTreeMap<String, String>> {
+        &self.metadata.annotations
+    }
+
+    fn is_cluster_scoped(&self) -> bool {
+        true
+    }
+}
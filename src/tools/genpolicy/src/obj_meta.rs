@@ -35,4 +35,12 @@ impl ObjectMeta {
     pub fn get_namespace(&self) -> Option<String> {
         self.namespace.as_ref().cloned()
     }
+
+    pub fn get_label(&self, key: &str) -> Option<String> {
+        if let Some(labels) = &self.labels {
+            return labels.get(key).cloned();
+        }
+
+        None
+    }
 }
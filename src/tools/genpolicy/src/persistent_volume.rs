@@ -0,0 +1,71 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Allow K8s YAML field names.
+#![allow(non_snake_case)]
+
+use crate::obj_meta;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// See Reference / Kubernetes API / Config and Storage Resources / PersistentVolume.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PersistentVolume {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    apiVersion: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+
+    pub metadata: obj_meta::ObjectMeta,
+    pub spec: PersistentVolumeSpec,
+}
+
+/// See Reference / Kubernetes API / Config and Storage Resources / PersistentVolume.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PersistentVolumeSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    capacity: Option<BTreeMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accessModes: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    persistentVolumeReclaimPolicy: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    storageClassName: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volumeMode: Option<String>,
+
+    /// Host path backing this volume, for local PersistentVolumes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local: Option<LocalVolumeSource>,
+    // TODO: additional fields.
+}
+
+/// See Reference / Kubernetes API / Config and Storage Resources / PersistentVolume.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LocalVolumeSource {
+    pub path: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fsType: Option<String>,
+}
+
+/// Returns the local path of the PersistentVolume with the input name, if
+/// that volume is a local PersistentVolume.
+pub fn get_local_path<'a>(
+    persistent_volumes: &'a [PersistentVolume],
+    volume_name: &str,
+) -> Option<&'a str> {
+    persistent_volumes
+        .iter()
+        .find(|volume| volume.metadata.name.as_deref() == Some(volume_name))
+        .and_then(|volume| volume.spec.local.as_ref())
+        .map(|local| local.path.as_str())
+}
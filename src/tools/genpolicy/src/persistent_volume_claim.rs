@@ -21,19 +21,28 @@ pub struct PersistentVolumeClaim {
     kind: Option<String>,
 
     pub metadata: obj_meta::ObjectMeta,
-    spec: PersistentVolumeClaimSpec,
+    pub spec: PersistentVolumeClaimSpec,
 }
 
 /// See Reference / Kubernetes API / Config and Storage Resources / PersistentVolumeClaim.
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
-struct PersistentVolumeClaimSpec {
+pub struct PersistentVolumeClaimSpec {
     resources: ResourceRequirements,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     accessModes: Option<Vec<String>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    storageClassName: Option<String>,
+    pub storageClassName: Option<String>,
+
+    /// Name of the specific PersistentVolume bound to this claim.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volumeName: Option<String>,
+
+    /// Name of the K8s 1.30+ VolumeAttributesClass modifying the mutable
+    /// attributes - e.g., IOPS or throughput - of this claim's volume.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volumeAttributesClassName: Option<String>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     volumeMode: Option<String>,
@@ -0,0 +1,231 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Allow K8s YAML field names.
+#![allow(non_snake_case)]
+
+use crate::limit_range;
+use crate::obj_meta;
+use crate::pod;
+use crate::pod_template;
+use crate::policy;
+use crate::settings;
+use crate::utils::Config;
+use crate::yaml;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use log::debug;
+use protocols::agent;
+use std::collections::BTreeMap;
+
+/// Trait implemented by genpolicy plugins, loaded from the shared libraries
+/// in the --plugin-dir directory. A plugin adds support for a custom K8s
+/// resource type that wraps a standard pod template - e.g., an Argo Rollouts
+/// "Rollout" or a Flux "HelmRelease".
+pub trait GenpolicyPlugin: Send + Sync {
+    /// Return true if this plugin handles input resources of this kind.
+    fn handles_kind(&self, kind: &str) -> bool;
+
+    /// Extract the pod template from a serde representation of an input K8s
+    /// resource YAML. The returned value must deserialize into a
+    /// PodTemplateSpec.
+    fn extract_pod_template(&self, doc: &serde_yaml::Value) -> Result<serde_yaml::Value>;
+
+    /// Return the path of the pod template inside the input resource YAML -
+    /// e.g., "spec.template". The policy annotation gets inserted into the
+    /// metadata at this path.
+    fn template_path(&self, doc: &serde_yaml::Value) -> String;
+}
+
+/// The entry point that each plugin library must export as
+/// "genpolicy_plugin_create". Plugins pass Rust trait objects across the
+/// library boundary, so they must be built with the same compiler version as
+/// genpolicy itself.
+pub type GenpolicyPluginCreate = unsafe fn() -> *mut dyn GenpolicyPlugin;
+
+/// A plugin instance, keeping alive the shared library it was loaded from.
+pub struct Plugin {
+    instance: Box<dyn GenpolicyPlugin>,
+
+    /// The library must outlive the plugin instance created from it, so this
+    /// field must be declared after the instance field to keep the drop
+    /// order correct.
+    _library: libloading::Library,
+}
+
+impl Plugin {
+    pub fn handles_kind(&self, kind: &str) -> bool {
+        self.instance.handles_kind(kind)
+    }
+}
+
+/// Load the plugins from the shared libraries of the directory specified by
+/// the --plugin-dir command line parameter.
+pub fn load_plugins(plugin_dir: &str) -> Result<Vec<Plugin>> {
+    let mut plugins = Vec::new();
+
+    for entry in std::fs::read_dir(plugin_dir)? {
+        let path = entry?.path();
+        if !matches!(
+            path.extension().and_then(|extension| extension.to_str()),
+            Some("so" | "dylib" | "dll")
+        ) {
+            continue;
+        }
+
+        debug!("Loading plugin library {}", path.display());
+        unsafe {
+            let library = libloading::Library::new(&path)?;
+            let create: libloading::Symbol<GenpolicyPluginCreate> =
+                library.get(b"genpolicy_plugin_create")?;
+            let instance = Box::from_raw(create());
+            plugins.push(Plugin {
+                instance,
+                _library: library,
+            });
+        }
+    }
+
+    Ok(plugins)
+}
+
+/// A custom K8s resource handled by one of the loaded plugins. The pod
+/// template extracted by the plugin gets processed by the standard policy
+/// generation logic.
+pub struct PluginResource {
+    metadata: obj_meta::ObjectMeta,
+    template: pod_template::PodTemplateSpec,
+    template_path: String,
+    doc_mapping: serde_yaml::Value,
+}
+
+impl PluginResource {
+    pub fn new(plugin: &Plugin, doc_mapping: &serde_yaml::Value) -> Result<Self> {
+        let metadata = serde_yaml::from_value(doc_mapping["metadata"].clone())?;
+        let template = serde_yaml::from_value(plugin.instance.extract_pod_template(doc_mapping)?)?;
+        let template_path = plugin.instance.template_path(doc_mapping);
+
+        Ok(Self {
+            metadata,
+            template,
+            template_path,
+            doc_mapping: serde_yaml::Value::Null,
+        })
+    }
+}
+
+#[async_trait]
+impl yaml::K8sResource for PluginResource {
+    async fn init(
+        &mut self,
+        config: &Config,
+        doc_mapping: &serde_yaml::Value,
+        _silent_unsupported_fields: bool,
+    ) {
+        yaml::k8s_resource_init(&mut self.template.spec, config).await;
+        self.doc_mapping = doc_mapping.clone();
+    }
+
+    fn get_sandbox_name(&self) -> Option<String> {
+        let suffix = yaml::GENERATE_NAME_SUFFIX_REGEX;
+        yaml::name_regex_from_meta(&self.metadata)
+            .map(|prefix| format!("{prefix}-{suffix}-{suffix}"))
+    }
+
+    fn get_namespace(&self) -> Option<String> {
+        self.metadata.get_namespace()
+    }
+
+    fn get_container_mounts_and_storages(
+        &self,
+        policy_mounts: &mut Vec<policy::KataMount>,
+        storages: &mut Vec<agent::Storage>,
+        container: &pod::Container,
+        settings: &settings::Settings,
+    ) {
+        yaml::get_container_mounts_and_storages(
+            policy_mounts,
+            storages,
+            container,
+            settings,
+            &self.template.spec.volumes,
+        );
+    }
+
+    fn generate_initdata_anno(&self, agent_policy: &policy::AgentPolicy) -> String {
+        agent_policy.generate_initdata_anno(self)
+    }
+
+    fn serialize(&mut self, policy: &str) -> String {
+        yaml::add_policy_annotation(&mut self.doc_mapping, &self.template_path, policy);
+        serde_yaml::to_string(&self.doc_mapping).unwrap()
+    }
+
+    fn get_metadata_path(&self) -> Option<String> {
+        Some(self.template_path.clone())
+    }
+
+    fn get_containers(&self) -> &Vec<pod::Container> {
+        &self.template.spec.containers
+    }
+
+    fn get_annotations(&self) -> &Option<BTreeMap<String, String>> {
+        if let Some(metadata) = &self.template.metadata {
+            return &metadata.annotations;
+        }
+        &None
+    }
+
+    fn use_host_network(&self) -> bool {
+        if let Some(host_network) = self.template.spec.hostNetwork {
+            return host_network;
+        }
+        false
+    }
+
+    fn use_sandbox_pidns(&self) -> bool {
+        if let Some(shared) = self.template.spec.shareProcessNamespace {
+            return shared;
+        }
+        false
+    }
+
+    fn get_runtime_class_name(&self) -> Option<String> {
+        self.template
+            .spec
+            .runtimeClassName
+            .clone()
+            .or_else(|| Some(String::new()))
+    }
+
+    fn get_process_fields(&self, process: &mut policy::KataProcess, must_check_passwd: &mut bool) {
+        yaml::get_process_fields(
+            process,
+            &self.template.spec.securityContext,
+            must_check_passwd,
+        );
+    }
+
+    fn get_sysctls(&self) -> Vec<pod::Sysctl> {
+        yaml::get_sysctls(&self.template.spec.securityContext)
+    }
+
+    fn apply_limit_range_defaults(&mut self, limit_ranges: &[limit_range::LimitRange]) {
+        yaml::apply_limit_range_defaults(&mut self.template.spec, limit_ranges);
+    }
+
+    fn resolve_local_volumes(&mut self, local_pv_paths: &BTreeMap<String, String>) {
+        yaml::resolve_local_volumes(&mut self.template.spec, local_pv_paths);
+    }
+
+    fn automount_service_account_token(&self) -> bool {
+        yaml::automount_service_account_token(&self.template.spec)
+    }
+
+    fn add_injected_sidecars(&mut self, injection: &yaml::SidecarInjection) {
+        yaml::add_injected_sidecars(&mut self.template.spec, injection);
+    }
+}
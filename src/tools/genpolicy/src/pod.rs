@@ -7,6 +7,7 @@
 #![allow(non_snake_case)]
 
 use crate::config_map;
+use crate::limit_range;
 use crate::obj_meta;
 use crate::policy;
 use crate::registry;
@@ -69,6 +70,9 @@ pub struct PodSpec {
     #[serde(skip_serializing_if = "Option::is_none")]
     serviceAccount: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub automountServiceAccountToken: Option<bool>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     terminationGracePeriodSeconds: Option<i64>,
 
@@ -78,6 +82,9 @@ pub struct PodSpec {
     #[serde(skip_serializing_if = "Option::is_none")]
     hostname: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subdomain: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hostNetwork: Option<bool>,
 
@@ -90,6 +97,9 @@ pub struct PodSpec {
     #[serde(skip_serializing_if = "Option::is_none")]
     dnsPolicy: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hostAliases: Option<Vec<HostAlias>>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     topologySpreadConstraints: Option<Vec<TopologySpreadConstraint>>,
 
@@ -98,6 +108,18 @@ pub struct PodSpec {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     priorityClassName: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resourceClaims: Option<Vec<PodResourceClaim>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os: Option<PodOS>,
+}
+
+/// See Reference / Kubernetes API / Workload Resources / Pod.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PodOS {
+    pub name: String,
 }
 
 /// See Reference / Kubernetes API / Workload Resources / Pod.
@@ -134,6 +156,9 @@ pub struct Container {
     #[serde(skip_serializing_if = "Option::is_none")]
     ports: Option<Vec<ContainerPort>>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workingDir: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub command: Option<Vec<String>>,
 
@@ -240,9 +265,21 @@ struct Probe {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     tcpSocket: Option<TCPSocketAction>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grpc: Option<GRPCAction>,
     // TODO: additional fields.
 }
 
+/// See Reference / Kubernetes API / Workload Resources / Pod.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct GRPCAction {
+    port: i32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service: Option<String>,
+}
+
 /// See Reference / Kubernetes API / Workload Resources / Pod.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct TCPSocketAction {
@@ -556,6 +593,25 @@ struct LocalObjectReference {
     name: String,
 }
 
+/// See Reference / Kubernetes API / Workload Resources / Pod.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct PodResourceClaim {
+    name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<ResourceClaimSource>,
+}
+
+/// See Reference / Kubernetes API / Workload Resources / Pod.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ResourceClaimSource {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resourceClaimName: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resourceClaimTemplateName: Option<String>,
+}
+
 /// See Reference / Kubernetes API / Workload Resources / Pod.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct PodDNSConfig {
@@ -578,6 +634,13 @@ struct PodDNSConfigOption {
     value: Option<String>,
 }
 
+/// See Reference / Kubernetes API / Workload Resources / Pod.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct HostAlias {
+    ip: String,
+    hostnames: Vec<String>,
+}
+
 /// See Reference / Kubernetes API / Workload Resources / Pod.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct TopologySpreadConstraint {
@@ -627,7 +690,15 @@ impl Container {
                 );
                 let src_string = format!("{}={value}", &env_variable.name);
 
-                if !dest_env.contains(&src_string) {
+                // The K8s env variables take precedence over the variables
+                // defined by the image config, already present in dest_env.
+                let name_prefix = format!("{}=", &env_variable.name);
+                if let Some(existing) = dest_env
+                    .iter_mut()
+                    .find(|dest_var| dest_var.starts_with(&name_prefix))
+                {
+                    existing.clone_from(&src_string);
+                } else {
                     dest_env.push(src_string.clone());
                 }
             }
@@ -646,6 +717,92 @@ impl Container {
         }
     }
 
+    /// Expand "$(VAR)" references in a string - e.g., a volumeMount
+    /// subPathExpr - using this container's environment variables that
+    /// have plain values in the input YAML.
+    pub fn expand_env_expr(&self, expr: &str) -> String {
+        let mut expanded = expr.to_string();
+
+        if let Some(source_env) = &self.env {
+            for env_variable in source_env {
+                if let Some(value) = &env_variable.value {
+                    let reference = format!("$({})", &env_variable.name);
+                    expanded = expanded.replace(&reference, value);
+                }
+            }
+        }
+
+        expanded
+    }
+
+    pub fn requests_gpu(&self) -> bool {
+        if let Some(resources) = &self.resources {
+            if let Some(limits) = &resources.limits {
+                return limits.contains_key("nvidia.com/gpu");
+            }
+        }
+        false
+    }
+
+    /// Applies the default resource limits and requests of the input
+    /// LimitRanges to this container, for the resources that this container
+    /// doesn't specify explicitly.
+    pub fn apply_limit_range_defaults(&mut self, limit_ranges: &[limit_range::LimitRange]) {
+        let (default_limits, default_requests) = limit_range::get_container_defaults(limit_ranges);
+        if default_limits.is_empty() && default_requests.is_empty() {
+            return;
+        }
+
+        let resources = self.resources.get_or_insert(ResourceRequirements {
+            requests: None,
+            limits: None,
+        });
+        for (name, value) in default_limits {
+            resources
+                .limits
+                .get_or_insert_with(BTreeMap::new)
+                .entry(name)
+                .or_insert(value);
+        }
+        for (name, value) in default_requests {
+            resources
+                .requests
+                .get_or_insert_with(BTreeMap::new)
+                .entry(name)
+                .or_insert(value);
+        }
+    }
+
+    /// Returns the number of vCPUs needed to satisfy this container's "cpu"
+    /// resource limit, if any. Fractional K8s CPU quantities are rounded up
+    /// to whole vCPUs.
+    pub fn get_cpu_limit_vcpus(&self) -> Option<u32> {
+        if let Some(resources) = &self.resources {
+            if let Some(limits) = &resources.limits {
+                if let Some(cpu) = limits.get("cpu") {
+                    let vcpus = if let Some(milli_cpu) = cpu.strip_suffix('m') {
+                        let milli_cpu: u32 = milli_cpu.parse().unwrap();
+                        milli_cpu.div_ceil(1000)
+                    } else {
+                        let cpus: f64 = cpu.parse().unwrap();
+                        cpus.ceil() as u32
+                    };
+                    return Some(vcpus);
+                }
+            }
+        }
+        None
+    }
+
+    pub fn requests_sriov(&self) -> bool {
+        if let Some(resources) = &self.resources {
+            if let Some(limits) = &resources.limits {
+                return limits.contains_key("intel.com/sriov_netdevice");
+            }
+        }
+        false
+    }
+
     pub fn is_privileged(&self) -> bool {
         if let Some(context) = &self.securityContext {
             if let Some(privileged) = context.privileged {
@@ -664,29 +821,17 @@ impl Container {
         false
     }
 
-    pub fn get_process_args(&self, policy_args: &mut Vec<String>) -> (bool, bool) {
-        let mut yaml_has_command = true;
-        let mut yaml_has_args = true;
-
-        if let Some(commands) = &self.command {
-            for command in commands {
-                policy_args.push(command.clone());
-            }
-        } else {
-            yaml_has_command = false;
-        }
-
-        if let Some(args) = &self.args {
-            for arg in args {
-                policy_args.push(arg.clone());
-            }
-        } else {
-            yaml_has_args = false;
-        }
-
-        (yaml_has_command, yaml_has_args)
+    /// True for K8s v1.30+ sidecar containers - i.e., init containers with
+    /// restartPolicy Always. K8s allows the per-container restartPolicy
+    /// field for init containers only.
+    pub fn is_sidecar(&self) -> bool {
+        self.restartPolicy.as_deref() == Some("Always")
     }
 
+    /// Returns the commands that get executed in this container by kubelet,
+    /// using ExecProcessRequest: the exec actions of the container's probes
+    /// and of its postStart/preStop lifecycle hooks. The generated policy
+    /// allows executing these exact commands.
     pub fn get_exec_commands(&self) -> Vec<Vec<String>> {
         let mut commands = Vec::new();
 
@@ -774,8 +919,23 @@ impl EnvVar {
                 return value.clone();
             }
 
-            if let Some(value) = secret::get_value(value_from, secrets) {
-                return value.clone();
+            if let Some(key_ref) = &value_from.secretKeyRef {
+                // Warn about references to secret keys that are not part of
+                // the input YAML, helping catch typos before deployment.
+                if secret::get_value(value_from, secrets).is_none() {
+                    warn!(
+                        "Env var {}: secret key {} of {} is not part of the input YAML",
+                        &self.name,
+                        &key_ref.key,
+                        key_ref.name.as_deref().unwrap_or("")
+                    );
+                }
+
+                // Don't embed the value of the secret in the policy text.
+                // The "$(secret-value)" marker makes the generated policy
+                // accept any input value for this variable, because secrets
+                // can be created and updated independently of the policy.
+                return "$(secret-value)".to_string();
             }
 
             if let Some(field_ref) = &value_from.fieldRef {
@@ -864,6 +1024,87 @@ impl yaml::K8sResource for Pod {
         self.metadata.get_namespace()
     }
 
+    fn get_sandbox_hostname(&self) -> Option<String> {
+        // An explicit spec.hostname overrides the default hostname -
+        // the name of the pod.
+        self.spec
+            .hostname
+            .clone()
+            .or_else(|| yaml::name_regex_from_meta(&self.metadata))
+    }
+
+    fn get_image_pull_secrets(&self) -> Option<Vec<String>> {
+        self.spec
+            .imagePullSecrets
+            .as_ref()
+            .map(|secrets| secrets.iter().map(|secret| secret.name.clone()).collect())
+    }
+
+    fn get_service_account_name(&self) -> Option<String> {
+        // spec.serviceAccount is the deprecated alias of serviceAccountName.
+        self.spec
+            .serviceAccountName
+            .clone()
+            .or_else(|| self.spec.serviceAccount.clone())
+    }
+
+    fn get_resource_claims(&self) -> Option<Vec<String>> {
+        let claims: Vec<String> = self
+            .spec
+            .resourceClaims
+            .as_ref()?
+            .iter()
+            .map(|claim| claim.name.clone())
+            .collect();
+        if claims.is_empty() {
+            None
+        } else {
+            Some(claims)
+        }
+    }
+
+    fn get_sandbox_dns(&self) -> Option<Vec<String>> {
+        // With other dnsPolicy values the guest's resolv.conf gets cluster
+        // and/or node DNS settings that are not known in advance.
+        if self.spec.dnsPolicy.as_deref() != Some("None") {
+            return None;
+        }
+
+        let dns_config = self.spec.dnsConfig.as_ref()?;
+        let mut lines = Vec::new();
+
+        if let Some(nameservers) = &dns_config.nameservers {
+            for nameserver in nameservers {
+                lines.push(format!("nameserver {nameserver}"));
+            }
+        }
+
+        if let Some(searches) = &dns_config.searches {
+            if !searches.is_empty() {
+                lines.push(format!("search {}", searches.join(" ")));
+            }
+        }
+
+        if let Some(options) = &dns_config.options {
+            for option in options {
+                if let Some(value) = &option.value {
+                    lines.push(format!("options {}:{value}", &option.name));
+                } else {
+                    lines.push(format!("options {}", &option.name));
+                }
+            }
+        }
+
+        Some(lines)
+    }
+
+    fn has_host_aliases(&self) -> bool {
+        if let Some(host_aliases) = &self.spec.hostAliases {
+            return !host_aliases.is_empty();
+        }
+        false
+    }
+
     fn get_container_mounts_and_storages(
         &self,
         policy_mounts: &mut Vec<policy::KataMount>,
@@ -889,6 +1130,10 @@ impl yaml::K8sResource for Pod {
         serde_yaml::to_string(&self.doc_mapping).unwrap()
     }
 
+    fn get_metadata_path(&self) -> Option<String> {
+        Some(String::new())
+    }
+
     fn get_containers(&self) -> &Vec<Container> {
         &self.spec.containers
     }
@@ -925,6 +1170,34 @@ impl yaml::K8sResource for Pod {
     fn get_sysctls(&self) -> Vec<Sysctl> {
         yaml::get_sysctls(&self.spec.securityContext)
     }
+
+    fn apply_limit_range_defaults(&mut self, limit_ranges: &[limit_range::LimitRange]) {
+        yaml::apply_limit_range_defaults(&mut self.spec, limit_ranges);
+    }
+
+    fn resolve_local_volumes(&mut self, local_pv_paths: &BTreeMap<String, String>) {
+        yaml::resolve_local_volumes(&mut self.spec, local_pv_paths);
+    }
+
+    fn automount_service_account_token(&self) -> bool {
+        yaml::automount_service_account_token(&self.spec)
+    }
+
+    fn add_injected_sidecars(&mut self, injection: &yaml::SidecarInjection) {
+        yaml::add_injected_sidecars(&mut self.spec, injection);
+    }
+
+    fn get_topology_keys(&self) -> Option<Vec<String>> {
+        self.spec
+            .topologySpreadConstraints
+            .as_ref()
+            .map(|constraints| {
+                constraints
+                    .iter()
+                    .map(|constraint| constraint.topologyKey.clone())
+                    .collect()
+            })
+    }
 }
 
 impl Container {
@@ -974,6 +1247,12 @@ impl Container {
     }
 
     pub fn get_process_fields(&self, process: &mut policy::KataProcess) {
+        // An explicit workingDir overrides the WORKDIR obtained from the
+        // container image configuration.
+        if let Some(working_dir) = &self.workingDir {
+            process.Cwd.clone_from(working_dir);
+        }
+
         if let Some(context) = &self.securityContext {
             if let Some(uid) = context.runAsUser {
                 process.User.UID = uid.try_into().unwrap();
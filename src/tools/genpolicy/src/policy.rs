@@ -8,18 +8,26 @@
 
 use crate::config_map;
 use crate::containerd;
+use crate::limit_range;
 use crate::mount_and_storage;
+use crate::namespace;
 use crate::no_policy;
+use crate::persistent_volume;
+use crate::persistent_volume_claim;
+use crate::plugin;
 use crate::pod;
 use crate::policy;
+use crate::registry;
 use crate::secret;
+use crate::storage_class;
 use crate::utils;
 use crate::yaml;
 
 use anyhow::Result;
-use log::debug;
+use log::{debug, warn};
 use oci_spec::runtime as oci;
 use protocols::agent;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_yaml::Value;
 use std::boxed;
@@ -32,6 +40,15 @@ pub struct AgentPolicy {
     /// K8s resources described by the input YAML file.
     pub resources: Vec<boxed::Box<dyn yaml::K8sResource + Send + Sync>>,
 
+    /// K8s resource kind of each of the resources, in the same order.
+    pub resource_kinds: Vec<String>,
+
+    /// Raw input YAML text of each of the resources, in the same order, used
+    /// for inserting the policy annotation without reformatting the other
+    /// lines of the input. Empty when the input documents couldn't be
+    /// correlated with their raw text.
+    raw_documents: Vec<String>,
+
     /// K8s ConfigMap resources described by an additional input YAML file
     /// or by the "main" input YAML file, containing additional pod settings.
     config_maps: Vec<config_map::ConfigMap>,
@@ -39,6 +56,10 @@ pub struct AgentPolicy {
     /// K8s Secret resources, containing additional pod settings.
     secrets: Vec<secret::Secret>,
 
+    /// K8s Namespace resources from the input YAML, providing e.g. the PSA
+    /// level labels of the namespaces used by other input resources.
+    namespaces: Vec<namespace::Namespace>,
+
     /// Rego rules read from a file (rules.rego).
     pub rules: String,
 
@@ -68,7 +89,7 @@ pub struct PolicyData {
 /// is ordered, thus resulting in the same output policy contents every time
 /// when this apps runs with the same inputs. Also, it preserves the upper
 /// case field names, for consistency with the structs used by agent's rpc.rs.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
 pub struct KataSpec {
     /// Version of the Open Container Initiative Runtime Specification with which the bundle complies.
     #[serde(default)]
@@ -87,6 +108,7 @@ pub struct KataSpec {
 
     /// Hooks configures callbacks for container lifecycle events.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<serde_json::Value>")]
     pub Hooks: Option<oci::Hooks>,
 
     /// Annotations contains arbitrary metadata for the container.
@@ -102,7 +124,7 @@ pub struct KataSpec {
 /// struct generated from oci.proto. The main difference is that it preserves
 /// the upper case field names from oci.proto, for consistency with the structs
 /// used by agent's rpc.rs.
-#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, JsonSchema)]
 pub struct KataProcess {
     /// Terminal creates an interactive terminal for the container.
     #[serde(default)]
@@ -138,7 +160,7 @@ pub struct KataProcess {
 /// struct generated from oci.proto. The main difference is that it preserves
 /// the upper case field names from oci.proto, for consistency with the structs
 /// used by agent's rpc.rs.
-#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, JsonSchema)]
 pub struct KataUser {
     /// UID is the user id.
     pub UID: u32,
@@ -157,7 +179,7 @@ pub struct KataUser {
 /// struct generated from oci.proto. The main difference is that it preserves the
 /// upper case field names from oci.proto, for consistency with the structs used
 /// by agent's rpc.rs.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct KataRoot {
     /// Path is the absolute path to the container's root filesystem.
     pub Path: String,
@@ -170,7 +192,7 @@ pub struct KataRoot {
 /// OCI container Linux struct. This struct is similar to the Linux struct
 /// generated from oci.proto, but includes just the fields that are currently
 /// relevant for automatic generation of policy.
-#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, JsonSchema)]
 pub struct KataLinux {
     /// Namespaces contains the namespaces that are created and/or joined by the container
     #[serde(default)]
@@ -196,7 +218,7 @@ pub struct KataLinux {
 /// OCI container LinuxNamespace struct. This struct is similar to the LinuxNamespace
 /// struct generated from oci.proto, but includes just the fields that are currently
 /// relevant for automatic generation of policy.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct KataLinuxNamespace {
     /// Type is the type of namespace
     pub Type: String,
@@ -209,7 +231,7 @@ pub struct KataLinuxNamespace {
 /// OCI container LinuxDevice struct. This struct is similar to the LinuxDevice
 /// struct generated from oci.proto, but includes just the fields that are currently
 /// relevant for automatic generation of policy.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct KataLinuxDevice {
     /// Type is the type of device.
     pub Type: String,
@@ -222,7 +244,7 @@ pub struct KataLinuxDevice {
 /// LinuxCapabilities struct generated from oci.proto. The main difference is
 /// that it preserves the upper case field names from oci.proto, for consistency
 /// with the structs used by agent's rpc.rs.
-#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, JsonSchema)]
 pub struct KataLinuxCapabilities {
     // Ambient is the ambient set of capabilities that are kept.
     pub Ambient: Vec<String>,
@@ -244,7 +266,7 @@ pub struct KataLinuxCapabilities {
 /// struct generated from oci.proto. The main difference is that it preserves
 /// the field names from oci.proto, for consistency with the structs used by
 /// agent's rpc.rs.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct KataMount {
     /// destination is the path inside the container expect when it starts with "tmp:/"
     pub destination: String,
@@ -279,6 +301,42 @@ pub struct ContainerPolicy {
     /// ExecProcessRequest. By default, all ExecProcessRequest calls are blocked
     /// by the policy.
     exec_commands: Vec<Vec<String>>,
+
+    /// Regex patterns from this container's exec allowlist annotation.
+    /// Command lines matching one of these patterns are allowed to be
+    /// executed using ExecProcessRequest.
+    exec_allowlist: Vec<String>,
+
+    /// Platform properties of the container image, compared with the target
+    /// platform of the policy for CreateContainerRequest calls.
+    image: ImageData,
+
+    /// Hash of the SBOM attestation attached to the container image,
+    /// recorded when the --use-sbom command line parameter was used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sbom_hash: Option<String>,
+
+    /// Whether the container image signature was verified using cosign,
+    /// recorded when the --verify-image-signatures command line parameter
+    /// was used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature_verified: Option<bool>,
+
+    /// The required signer that verified the container image signature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signer_identity: Option<String>,
+
+    /// Allow the Host to list this container's processes using PsRequest.
+    /// Initialized from request_defaults.PsRequest, but recorded per container
+    /// so that e.g. debug containers can keep process listing while production
+    /// containers deny it.
+    allow_process_listing: bool,
+
+    /// True for K8s v1.30+ sidecar containers - i.e., init containers with
+    /// restartPolicy Always. Sidecar containers keep running alongside the
+    /// regular containers, so policy rules can apply different lifecycle
+    /// checks to them than to one-shot init containers.
+    is_sidecar: bool,
 }
 
 /// See Reference / Kubernetes API / Config and Storage Resources / Volume.
@@ -311,14 +369,14 @@ pub struct PersistentVolumeClaimVolume {
 }
 
 /// CreateContainerRequest settings from genpolicy-settings.json.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct CreateContainerRequestDefaults {
     /// Allow env variables that match any of these regexes.
     allow_env_regex: Vec<String>,
 }
 
 /// ExecProcessRequest settings from genpolicy-settings.json.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ExecProcessRequestDefaults {
     /// Allow these commands to be executed. This field has been deprecated - use allowed_commands instead.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -332,17 +390,22 @@ pub struct ExecProcessRequestDefaults {
 }
 
 /// UpdateRoutesRequest settings from genpolicy-settings.json.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct UpdateRoutesRequestDefaults {
     /// Forbid adding routes to devices of these names.
     forbidden_device_names: Vec<String>,
 
     /// Forbid adding routes originating from these addresses.
     forbidden_source_regex: Vec<String>,
+
+    /// When not empty, only allow routes with a destination that starts with
+    /// one of these prefixes.
+    #[serde(default)]
+    allowed_route_prefixes: Vec<String>,
 }
 
 /// UpdateInterfaceRequest settings from genpolicy-settings.json.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct UpdateInterfaceRequestDefaults {
     /// Raw flag bitmask explicitly allowed to configure
     allow_raw_flags: u32,
@@ -352,10 +415,16 @@ pub struct UpdateInterfaceRequestDefaults {
 
     /// Explicitly blocked mac addresses. Intent is to block changes to loopback interface.
     forbidden_hw_addrs: Vec<String>,
+
+    /// When not empty, only allow interfaces with a mac address that starts
+    /// with one of these prefixes - e.g., for CNIs that assign deterministic
+    /// mac address prefixes.
+    #[serde(default)]
+    allowed_hwaddr_prefixes: Vec<String>,
 }
 
 /// UpdateInterfaceRequest settings from genpolicy-settings.json.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct AddARPNeighborsRequestDefaults {
     /// Explicitly blocked interface names. Intent is to block changes to loopback interface.
     forbidden_device_names: Vec<String>,
@@ -366,7 +435,7 @@ pub struct AddARPNeighborsRequestDefaults {
 
 /// Settings specific to each kata agent endpoint, loaded from
 /// genpolicy-settings.json.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct RequestDefaults {
     /// Settings for CreateContainerRequest.
     pub CreateContainerRequest: CreateContainerRequestDefaults,
@@ -389,6 +458,23 @@ pub struct RequestDefaults {
     /// Allow the Host to close stdin for a container. Typically used with WriteStreamRequest.
     pub CloseStdinRequest: bool,
 
+    /// Allow the Host to query details of the Guest VM.
+    #[serde(default = "default_true")]
+    pub GuestDetailsRequest: bool,
+
+    /// Allow the Host to hot-plug memory into the Guest using probe addresses.
+    #[serde(default = "default_true")]
+    pub MemHotplugByProbeRequest: bool,
+
+    /// Allow the Host to change the Guest's date and time.
+    #[serde(default = "default_true")]
+    pub SetGuestDateTimeRequest: bool,
+
+    /// Allow the Host to list the processes running inside containers. Used
+    /// as the default value of each container's allow_process_listing field.
+    #[serde(default = "default_true")]
+    pub PsRequest: bool,
+
     /// Allow Host reading from Guest containers stdout and stderr.
     pub ReadStreamRequest: bool,
 
@@ -399,8 +485,23 @@ pub struct RequestDefaults {
     pub WriteStreamRequest: bool,
 }
 
+fn default_true() -> bool {
+    true
+}
+
+fn default_service_account_token_path() -> String {
+    "/var/run/secrets/kubernetes.io/serviceaccount".to_string()
+}
+
+fn default_allowed_annotation_prefixes() -> Vec<String> {
+    vec![
+        "io.katacontainers.".to_string(),
+        "kubectl.kubernetes.io/".to_string(),
+    ]
+}
+
 /// Struct used to read data from the settings file and copy that data into the policy.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct CommonData {
     /// Path to the shared container files - e.g., "/run/kata-containers/shared/containers".
     pub cpath: String,
@@ -428,10 +529,88 @@ pub struct CommonData {
 
     /// Default capabilities for a privileged container.
     pub privileged_caps: Vec<String>,
+
+    /// Optional Pod Security Admission level enforced by the generated policy:
+    /// "privileged", "baseline" or "restricted".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub psa_level: Option<String>,
+
+    /// Regexes for the device paths allowed for containers that have a
+    /// "nvidia.com/gpu" resource limit.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gpu_device_policies: Vec<String>,
+
+    /// Path prefix of the VFIO devices allowed for containers that have an
+    /// "intel.com/sriov_netdevice" resource limit - e.g., "/dev/vfio".
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub sriov_device_prefix: String,
+
+    /// Mount point of the projected K8s service account token - e.g.,
+    /// "/var/run/secrets/kubernetes.io/serviceaccount".
+    #[serde(default = "default_service_account_token_path")]
+    pub service_account_token_path: String,
+
+    /// Maximum number of vCPUs that the Host is allowed to online using
+    /// OnlineCPUMemRequest. When 0, the limit is derived from the input
+    /// YAML's CPU limits - or left unrestricted if no limits are specified.
+    #[serde(default)]
+    pub max_vcpus: u32,
+
+    /// Optional mirrors for container image registries, tried in order when
+    /// pulling image information from the source registry fails - e.g.,
+    /// "docker.io" => ["mirror.example.com"].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub registry_mirrors: BTreeMap<String, Vec<String>>,
+
+    /// Optional settings for verifying container image signatures, used when
+    /// the --verify-image-signatures command line parameter is specified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_signature_policy: Option<ImageSignaturePolicy>,
+
+    /// GOARCH-style name of the architecture that the container images of
+    /// the generated policy must be built for. When empty, the architecture
+    /// from the --target-arch command line parameter is used.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub target_arch: String,
+
+    /// Path prefixes that CopyFileRequest is allowed to write to - e.g.,
+    /// "/run/secrets". When empty, just the request_defaults.CopyFileRequest
+    /// regex patterns restrict the destination paths of copied files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub copy_file_allowed_paths: Vec<String>,
+
+    /// Prefixes of the sandbox annotation keys accepted by the generated
+    /// policy, preventing an attacker from injecting arbitrary annotations.
+    #[serde(default = "default_allowed_annotation_prefixes")]
+    pub allowed_annotation_prefixes: Vec<String>,
+
+    /// Dynamic Resource Allocation driver names - e.g., "gpu.nvidia.com" -
+    /// allowed to inject CDI devices into containers. When empty, CDI
+    /// device annotations are not restricted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dra_allowed_drivers: Vec<String>,
+}
+
+/// Platform properties obtained from a container image's config.
+#[derive(Debug, Serialize)]
+pub struct ImageData {
+    /// Operating system of the image - e.g., "linux".
+    pub os: String,
+
+    /// Architecture of the image - e.g., "amd64".
+    pub architecture: String,
+}
+
+/// Settings for verifying container image signatures using cosign.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ImageSignaturePolicy {
+    /// Signers allowed to sign the container images - either paths to cosign
+    /// public key files, or OIDC issuers for keyless signatures.
+    pub required_signers: Vec<String>,
 }
 
 /// Configuration from "kubectl config".
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ClusterConfig {
     /// Pause container image reference.
     pub pause_container_image: String,
@@ -439,13 +618,64 @@ pub struct ClusterConfig {
     /// In guest pull, host can't look into layers to determine GID.
     /// See issue https://github.com/kata-containers/kata-containers/issues/11162
     pub guest_pull: bool,
+    /// Whether hostAliases entries are allowed in the input YAML. The /etc/hosts
+    /// file is written by the host, so its contents cannot be verified by the
+    /// policy - clusters that don't use hostAliases should keep this disabled.
+    #[serde(default)]
+    pub allow_host_aliases: bool,
 }
 
 /// Struct used to read data from the settings file and copy that data into the policy.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct SandboxData {
     /// Expected value of the CreateSandboxRequest storages field.
+    #[schemars(with = "Vec<serde_json::Value>")]
     pub storages: Vec<agent::Storage>,
+
+    /// Regex for the expected CreateSandboxRequest hostname field, generated
+    /// from the input YAML. Any hostname is allowed when not present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+
+    /// Names of the imagePullSecrets listed by the input YAML, recorded so
+    /// that policy rules can tell whether registry credentials are expected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_pull_secrets: Option<Vec<String>>,
+
+    /// Expected K8s namespace of the sandbox, generated from the input YAML.
+    /// Prevents reusing a policy generated for one namespace in another one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+
+    /// Expected CreateSandboxRequest dns lines, generated from the input YAML
+    /// when its dnsPolicy field makes these lines predictable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Vec<String>>,
+
+    /// Topology keys of the input YAML's topologySpreadConstraints, recorded
+    /// so that policy rules can verify that pods claiming to target
+    /// zone-specific hardware have the matching constraint set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topology_keys: Option<Vec<String>>,
+
+    /// K8s service account explicitly specified by the input YAML. Prevents
+    /// substituting a pod spec that runs the same containers under a
+    /// higher-privileged service account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_account: Option<String>,
+
+    /// VolumeAttributesClass names referenced by the input YAML's
+    /// volumeClaimTemplates, recorded for auditing the storage policy.
+    /// The volume attributes are applied by the CSI driver on the host,
+    /// so the agent can't verify them directly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_attributes_classes: Option<Vec<String>>,
+
+    /// Names of the Dynamic Resource Allocation resourceClaims listed by
+    /// the input YAML's pod spec, recorded for auditing the devices that
+    /// the claims are expected to provide.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_claims: Option<Vec<String>>,
 }
 
 enum K8sEnvFromSource {
@@ -455,17 +685,72 @@ enum K8sEnvFromSource {
 
 impl AgentPolicy {
     pub async fn from_files(config: &utils::Config) -> Result<AgentPolicy> {
+        let yaml_contents = if let Some(kustomize_dir) = &config.kustomize {
+            yaml::get_kustomize_yaml(kustomize_dir, &config.kustomize_args)?
+        } else {
+            yaml::get_input_yaml(&config.yaml_file)?
+        };
+        Self::from_yaml_contents(config, &yaml_contents).await
+    }
+
+    pub async fn from_yaml_contents(
+        config: &utils::Config,
+        yaml_contents: &str,
+    ) -> Result<AgentPolicy> {
         let mut config_maps = Vec::new();
+        let mut limit_ranges = Vec::new();
+        let mut namespaces = Vec::new();
+        let mut persistent_volumes = Vec::new();
+        let mut persistent_volume_claims = Vec::new();
         let mut secrets = Vec::new();
+        let mut storage_classes = Vec::new();
         let mut resources = Vec::new();
-        let yaml_contents = yaml::get_input_yaml(&config.yaml_file)?;
+        let mut resource_kinds = Vec::new();
+
+        let plugins = if let Some(plugin_dir) = &config.plugin_dir {
+            plugin::load_plugins(plugin_dir)?
+        } else {
+            Vec::new()
+        };
+
+        // Deserialize all the documents before awaiting, because the YAML
+        // deserializer cannot be sent between threads.
+        let mut doc_mappings = Vec::new();
+        for document in serde_yaml::Deserializer::from_str(yaml_contents) {
+            doc_mappings.push(Value::deserialize(document)?);
+        }
+
+        // The raw text of each document enables inserting the policy
+        // annotation without reformatting the rest of the input, when the
+        // raw documents can be correlated 1:1 with the parsed documents.
+        let mut raw_docs = yaml::split_documents(yaml_contents);
+        if raw_docs.len() != doc_mappings.len() {
+            raw_docs.clear();
+        }
+        let mut raw_documents = Vec::new();
 
-        for document in serde_yaml::Deserializer::from_str(&yaml_contents) {
-            let doc_mapping = Value::deserialize(document)?;
+        for (doc_index, doc_mapping) in doc_mappings.into_iter().enumerate() {
             if doc_mapping != Value::Null {
+                if let Some(raw_doc) = raw_docs.get(doc_index) {
+                    raw_documents.push(raw_doc.clone());
+                }
                 let yaml_string = serde_yaml::to_string(&doc_mapping)?;
                 let silent = config.silent_unsupported_fields;
-                let (mut resource, kind) = yaml::new_k8s_resource(&yaml_string, silent)?;
+                let header = yaml::get_yaml_header(&yaml_string)?;
+                let (mut resource, kind) = if let Some(matching_plugin) = plugins
+                    .iter()
+                    .find(|loaded_plugin| loaded_plugin.handles_kind(&header.kind))
+                {
+                    let plugin_resource =
+                        plugin::PluginResource::new(matching_plugin, &doc_mapping)?;
+                    (
+                        boxed::Box::new(plugin_resource)
+                            as boxed::Box<dyn yaml::K8sResource + Send + Sync>,
+                        header.kind,
+                    )
+                } else {
+                    yaml::new_k8s_resource(&yaml_string, silent)?
+                };
 
                 // Filter out resources that don't match the runtime class name.
                 if let Some(resource_runtime_name) = resource.get_runtime_class_name() {
@@ -478,10 +763,31 @@ impl AgentPolicy {
                         resource =
                             boxed::Box::new(no_policy::NoPolicyResource { yaml: yaml_string });
                         resources.push(resource);
+                        resource_kinds.push(kind);
+                        continue;
+                    }
+                }
+
+                // Filter out resources that don't match the label selector.
+                if let Some(label_selector) = &config.label_selector {
+                    if !label_selector.matches(&doc_mapping) {
+                        resource =
+                            boxed::Box::new(no_policy::NoPolicyResource { yaml: yaml_string });
+                        resources.push(resource);
+                        resource_kinds.push(kind);
                         continue;
                     }
                 }
 
+                // Filter out resources that don't match the kinds selected on
+                // the command line.
+                if !config.kinds.is_empty() && !config.kinds.contains(&kind.to_lowercase()) {
+                    resource = boxed::Box::new(no_policy::NoPolicyResource { yaml: yaml_string });
+                    resources.push(resource);
+                    resource_kinds.push(kind);
+                    continue;
+                }
+
                 resource.init(config, &doc_mapping, silent).await;
 
                 // ConfigMap and Secret documents contain additional input for policy generation.
@@ -493,6 +799,29 @@ impl AgentPolicy {
                     let secret: secret::Secret = serde_yaml::from_str(&yaml_string)?;
                     debug!("{:#?}", &secret);
                     secrets.push(secret);
+                } else if kind.eq("LimitRange") {
+                    let limit_range: limit_range::LimitRange = serde_yaml::from_str(&yaml_string)?;
+                    debug!("{:#?}", &limit_range);
+                    limit_ranges.push(limit_range);
+                } else if kind.eq("Namespace") {
+                    let namespace: namespace::Namespace = serde_yaml::from_str(&yaml_string)?;
+                    debug!("{:#?}", &namespace);
+                    namespaces.push(namespace);
+                } else if kind.eq("StorageClass") {
+                    let storage_class: storage_class::StorageClass =
+                        serde_yaml::from_str(&yaml_string)?;
+                    debug!("{:#?}", &storage_class);
+                    storage_classes.push(storage_class);
+                } else if kind.eq("PersistentVolume") {
+                    let persistent_volume: persistent_volume::PersistentVolume =
+                        serde_yaml::from_str(&yaml_string)?;
+                    debug!("{:#?}", &persistent_volume);
+                    persistent_volumes.push(persistent_volume);
+                } else if kind.eq("PersistentVolumeClaim") {
+                    let claim: persistent_volume_claim::PersistentVolumeClaim =
+                        serde_yaml::from_str(&yaml_string)?;
+                    debug!("{:#?}", &claim);
+                    persistent_volume_claims.push(claim);
                 }
 
                 // Although copies of ConfigMap and Secret resources get created above,
@@ -500,6 +829,7 @@ impl AgentPolicy {
                 // the elements of this vector will eventually be used to create the output
                 // YAML file.
                 resources.push(resource);
+                resource_kinds.push(kind);
             }
         }
 
@@ -518,12 +848,125 @@ impl AgentPolicy {
             }
         }
 
-        if let Ok(rules) = read_to_string(&config.rego_rules_path) {
+        if let Some(limit_range_file) = &config.limit_range_file {
+            let limit_range_contents = read_to_string(limit_range_file)?;
+            for document in serde_yaml::Deserializer::from_str(&limit_range_contents) {
+                let doc_mapping = Value::deserialize(document)?;
+                if doc_mapping != Value::Null {
+                    let yaml_string = serde_yaml::to_string(&doc_mapping)?;
+                    let header = yaml::get_yaml_header(&yaml_string)?;
+                    if header.kind.eq("LimitRange") {
+                        let limit_range: limit_range::LimitRange =
+                            serde_yaml::from_str(&yaml_string)?;
+                        debug!("{:#?}", &limit_range);
+                        limit_ranges.push(limit_range);
+                    }
+                }
+            }
+        }
+
+        if let Some(pv_file) = &config.pv_file {
+            let pv_contents = read_to_string(pv_file)?;
+            for document in serde_yaml::Deserializer::from_str(&pv_contents) {
+                let doc_mapping = Value::deserialize(document)?;
+                if doc_mapping != Value::Null {
+                    let yaml_string = serde_yaml::to_string(&doc_mapping)?;
+                    let header = yaml::get_yaml_header(&yaml_string)?;
+                    if header.kind.eq("PersistentVolume") {
+                        let persistent_volume: persistent_volume::PersistentVolume =
+                            serde_yaml::from_str(&yaml_string)?;
+                        debug!("{:#?}", &persistent_volume);
+                        persistent_volumes.push(persistent_volume);
+                    }
+                }
+            }
+        }
+
+        if let Some(sidecar_injection_file) = &config.sidecar_injection_file {
+            let injection_contents = read_to_string(sidecar_injection_file)?;
+            let mut injection: yaml::SidecarInjection = serde_yaml::from_str(&injection_contents)?;
+            debug!("{:#?}", &injection);
+
+            if let Some(containers) = &mut injection.containers {
+                for container in containers.iter_mut() {
+                    container.init(config).await;
+                }
+            }
+
+            for resource in &mut resources {
+                resource.add_injected_sidecars(&injection);
+            }
+        }
+
+        // Cross-reference the storageClassName of each claim against the
+        // StorageClass resources from the same input YAML. The provisioner
+        // is informational - e.g., it helps identifying the CSI driver
+        // behind a claim when investigating the generated Storage policy
+        // data - and StorageClasses are commonly defined cluster-side rather
+        // than in the application YAML, so a missing class is not an error.
+        for claim in &persistent_volume_claims {
+            let (Some(claim_name), Some(class_name)) =
+                (&claim.metadata.name, claim.spec.storageClassName.as_deref())
+            else {
+                continue;
+            };
+            match storage_class::get_provisioner(&storage_classes, class_name) {
+                Some(provisioner) => debug!(
+                    "PersistentVolumeClaim {claim_name}: storage class \
+                    {class_name} uses provisioner {provisioner}"
+                ),
+                None => warn!(
+                    "PersistentVolumeClaim {claim_name} references storage class \
+                    {class_name} that is not part of the input YAML"
+                ),
+            }
+        }
+
+        // Claims bound to a specific local PersistentVolume through their
+        // volumeName determine the bind mount source of the corresponding
+        // persistentVolumeClaim volumes.
+        let mut local_pv_paths = BTreeMap::new();
+        for claim in &persistent_volume_claims {
+            if let (Some(claim_name), Some(volume_name)) =
+                (&claim.metadata.name, &claim.spec.volumeName)
+            {
+                if let Some(local_path) =
+                    persistent_volume::get_local_path(&persistent_volumes, volume_name)
+                {
+                    local_pv_paths.insert(claim_name.clone(), local_path.to_string());
+                }
+            }
+        }
+        if !local_pv_paths.is_empty() {
+            for resource in &mut resources {
+                resource.resolve_local_volumes(&local_pv_paths);
+            }
+        }
+
+        if !limit_ranges.is_empty() {
+            // Containers that don't specify their own resource requirements
+            // inherit the LimitRange defaults, possibly changing the policy
+            // contents generated for these containers.
+            for resource in &mut resources {
+                resource.apply_limit_range_defaults(&limit_ranges);
+            }
+        }
+
+        if let Ok(mut rules) = read_to_string(&config.rego_rules_path) {
+            if config.settings.debug_print_on_deny {
+                rules = add_debug_prints(&rules);
+            }
+            if config.deduplicate_rules {
+                rules = deduplicate_rules(&rules);
+            }
             Ok(AgentPolicy {
                 resources,
+                resource_kinds,
+                raw_documents,
                 rules,
                 config_maps,
                 secrets,
+                namespaces,
                 config: config.clone(),
             })
         } else {
@@ -539,7 +982,23 @@ impl AgentPolicy {
             if self.config.base64_out {
                 println!("{}", annotation);
             }
-            yaml_string += &self.resources[i].serialize(&annotation);
+            if self.config.generate_tests && !annotation.is_empty() {
+                self.export_tests(i);
+            }
+
+            let raw_document = self.raw_documents.get(i);
+            let surgical = if annotation.is_empty() {
+                // Resources that don't get a policy annotation are re-emitted
+                // verbatim when their raw text is available.
+                raw_document.cloned()
+            } else if let (Some(raw_document), Some(metadata_path)) =
+                (raw_document, self.resources[i].get_metadata_path())
+            {
+                yaml::insert_policy_annotation(raw_document, &metadata_path, &annotation)
+            } else {
+                None
+            };
+            yaml_string += &surgical.unwrap_or_else(|| self.resources[i].serialize(&annotation));
         }
 
         if let Some(yaml_file) = &self.config.yaml_file {
@@ -557,23 +1016,250 @@ impl AgentPolicy {
         }
     }
 
+    /// Generate a <resource>_test.rego file containing "opa test" rules for
+    /// the policy of the resource with the given index. The tests exercise
+    /// the request_defaults booleans plus, for each container of the
+    /// resource, the CreateContainerRequest allow path with the container
+    /// configuration generated from the input YAML and the deny path with
+    /// modified configurations - an unexpected container image and modified
+    /// exec commands. To execute these tests, save the policy text of the
+    /// same resource (e.g., using the -r command line parameter) next to the
+    /// test file:
+    ///
+    /// opa test <resource>.rego <resource>_test.rego
+    ///
+    /// Like for print_test_input(), placeholder values that the agent
+    /// substitutes at runtime may need manual adjustment in the allow tests.
+    fn export_tests(&self, resource_index: usize) {
+        let resource = self.resources[resource_index].as_ref();
+        let name: String = match resource.get_sandbox_name() {
+            Some(sandbox_name) => sandbox_name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect(),
+            None => format!("resource{resource_index}"),
+        };
+
+        let mut tests = "package agent_policy\n\nimport future.keywords.if\n".to_string();
+
+        let defaults = &self.config.settings.request_defaults;
+        let boolean_defaults = [
+            ("CloseStdinRequest", defaults.CloseStdinRequest),
+            ("GuestDetailsRequest", defaults.GuestDetailsRequest),
+            (
+                "MemHotplugByProbeRequest",
+                defaults.MemHotplugByProbeRequest,
+            ),
+            ("ReadStreamRequest", defaults.ReadStreamRequest),
+            ("SetGuestDateTimeRequest", defaults.SetGuestDateTimeRequest),
+            (
+                "UpdateEphemeralMountsRequest",
+                defaults.UpdateEphemeralMountsRequest,
+            ),
+            ("WriteStreamRequest", defaults.WriteStreamRequest),
+        ];
+        for (request, allowed) in boolean_defaults {
+            if allowed {
+                tests += &format!(
+                    "\ntest_{request}_allowed if {{\n    {request} with input as {{}}\n}}\n"
+                );
+            } else {
+                tests += &format!(
+                    "\ntest_{request}_denied if {{\n    not {request} with input as {{}}\n}}\n"
+                );
+            }
+        }
+
+        let yaml_containers = resource.get_containers();
+        for (i, yaml_container) in yaml_containers.iter().enumerate() {
+            let container_policy = self.get_container_policy(resource, yaml_container, i == 0);
+            let input = build_test_input(&container_policy);
+
+            let container: String = if yaml_container.name.is_empty() {
+                format!("container{i}")
+            } else {
+                yaml_container
+                    .name
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .collect()
+            };
+
+            tests += &format!(
+                "\n# The container configuration generated from the input YAML is allowed.\n\
+                test_CreateContainerRequest_{container}_allowed if {{\n    \
+                CreateContainerRequest with input as {input}\n}}\n"
+            );
+
+            let mut modified = input.clone();
+            modified["OCI"]["Annotations"]["io.kubernetes.cri.image-name"] =
+                serde_json::Value::from("unexpected_image");
+            tests += &format!(
+                "\n# Changing the container image is denied.\n\
+                test_CreateContainerRequest_{container}_denied if {{\n    \
+                not CreateContainerRequest with input as {modified}\n}}\n"
+            );
+
+            for (j, exec_command) in yaml_container.get_exec_commands().iter().enumerate() {
+                let mut modified_command = exec_command.clone();
+                modified_command.push("unexpected_arg".to_string());
+                tests += &format!(
+                    "\n# Modified versions of the exec commands from the input YAML are denied.\n\
+                    test_ExecProcessRequest_{container}_{j}_denied if {{\n    \
+                    not ExecProcessRequest with input as {{\"container_id\": \"c\", \
+                    \"exec_id\": \"e\", \"process\": {{\"Args\": {args}}}}}\n}}\n",
+                    args = serde_json::to_string(&modified_command).unwrap()
+                );
+            }
+        }
+
+        let file_name = format!("{name}_test.rego");
+        if std::path::Path::new(&file_name).exists() {
+            warn!("export_tests: overwriting existing file {file_name}");
+        }
+        std::fs::write(file_name, tests).unwrap();
+    }
+
+    /// Print a synthetic CreateContainerRequest input JSON document for the
+    /// named container from the input YAML. The output is usable as
+    /// evaluation input for testing the generated policy offline - e.g.:
+    ///
+    /// opa eval -d policy.rego -i input.json "data.agent_policy.CreateContainerRequest"
+    ///
+    /// Placeholder values that the agent substitutes at runtime - e.g., the
+    /// sandbox name matched by regex for generate-name objects - may need
+    /// manual adjustment in the printed document.
+    pub fn print_test_input(&self, container_name: &str) {
+        for resource in &self.resources {
+            if resource.generate_initdata_anno(self).is_empty() {
+                // This resource doesn't get a policy - e.g., a ConfigMap.
+                continue;
+            }
+
+            let yaml_containers = resource.get_containers();
+            for (i, yaml_container) in yaml_containers.iter().enumerate() {
+                if yaml_container.name != container_name {
+                    continue;
+                }
+
+                let container_policy =
+                    self.get_container_policy(resource.as_ref(), yaml_container, i == 0);
+
+                let input = build_test_input(&container_policy);
+                println!("{}", serde_json::to_string_pretty(&input).unwrap());
+                return;
+            }
+        }
+
+        panic!("Container {container_name} not found in the input YAML");
+    }
+
+    /// Provenance comment header that gets prepended to the generated
+    /// policies, unless disabled by the --no-header command line parameter.
+    /// The header doesn't include a timestamp, so that identical inputs
+    /// generate byte-for-byte identical policies.
+    fn policy_header(&self) -> String {
+        let source = self.config.yaml_file.as_deref().unwrap_or("stdin");
+
+        // Hash the effective settings - i.e., after applying the settings
+        // override files and environment variables.
+        let settings_json = serde_json::to_string(&self.config.settings).unwrap();
+        let settings_hash: String = openssl::sha::sha256(settings_json.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+
+        format!(
+            "# Generated by genpolicy {}\n# Source: {source}\n# Settings hash: {settings_hash}\n",
+            env!("CARGO_PKG_VERSION"),
+        )
+    }
+
     pub fn generate_initdata_anno(&self, resource: &dyn yaml::K8sResource) -> String {
         let yaml_containers = resource.get_containers();
         let mut policy_containers = Vec::new();
 
+        if resource.has_host_aliases() && !self.config.settings.cluster_config.allow_host_aliases {
+            panic!(
+                "The input YAML specifies hostAliases, but allow_host_aliases \
+                is disabled in the settings file's cluster_config."
+            );
+        }
+
         for (i, yaml_container) in yaml_containers.iter().enumerate() {
             policy_containers.push(self.get_container_policy(resource, yaml_container, i == 0));
         }
 
+        let mut sandbox = self.config.settings.sandbox.clone();
+        sandbox.hostname = resource.get_sandbox_hostname();
+        sandbox.image_pull_secrets = resource.get_image_pull_secrets();
+        sandbox.namespace = if resource.is_cluster_scoped() {
+            // Cluster-scoped resources don't carry namespace metadata.
+            None
+        } else {
+            resource.get_namespace()
+        };
+        sandbox.dns = resource.get_sandbox_dns();
+        sandbox.topology_keys = resource.get_topology_keys();
+        sandbox.service_account = resource.get_service_account_name();
+        sandbox.volume_attributes_classes = resource.get_volume_attributes_classes();
+        sandbox.resource_claims = resource.get_resource_claims();
+
+        let mut common = self.config.settings.common.clone();
+        if common.psa_level.is_none() {
+            // A Namespace from the input YAML can enforce a PSA level for the
+            // resources created in that namespace.
+            if let Some(resource_namespace) = resource.get_namespace() {
+                common.psa_level = namespace::get_psa_level(&self.namespaces, &resource_namespace);
+            }
+        }
+        if common.target_arch.is_empty() {
+            common.target_arch = self.config.target_arch.clone();
+        }
+        if common.max_vcpus == 0 {
+            // Derive the vCPU limit from the input YAML's CPU limits. A zero
+            // sum keeps OnlineCPUMemRequest unrestricted.
+            common.max_vcpus = yaml_containers
+                .iter()
+                .filter_map(|yaml_container| yaml_container.get_cpu_limit_vcpus())
+                .sum();
+        }
+
+        if sandbox.image_pull_secrets.is_none() {
+            for yaml_container in yaml_containers {
+                if yaml_container.registry.auth_configured {
+                    warn!(
+                        "Pulling container image {} requires registry authentication, \
+                        but the input YAML does not specify imagePullSecrets",
+                        &yaml_container.image
+                    );
+                }
+            }
+        }
+
         let policy_data = policy::PolicyData {
             containers: policy_containers,
             request_defaults: self.config.settings.request_defaults.clone(),
-            common: self.config.settings.common.clone(),
-            sandbox: self.config.settings.sandbox.clone(),
+            common,
+            sandbox,
         };
 
         let json_data = serde_json::to_string_pretty(&policy_data).unwrap();
-        let policy = format!("{}\npolicy_data := {json_data}", &self.rules);
+        let mut policy = if let Some(comment) = resource.get_policy_comment() {
+            format!("{}\n{comment}\npolicy_data := {json_data}", &self.rules)
+        } else {
+            format!("{}\npolicy_data := {json_data}", &self.rules)
+        };
+        if let Some(extra_rules) = get_extra_policy_rules(resource) {
+            // The extra rules are part of the policy text, so they are
+            // covered by the policy hash verified by the agent.
+            policy = format!(
+                "{policy}\n\n# Extra rules from the {EXTRA_POLICY_ANNOTATION_KEY} annotation.\n{extra_rules}"
+            );
+        }
+        if self.config.add_header {
+            policy = format!("{}{policy}", self.policy_header());
+        }
         if self.config.raw_out {
             std::io::stdout().write_all(policy.as_bytes()).unwrap();
         }
@@ -626,6 +1312,11 @@ impl AgentPolicy {
             is_pause_container,
         );
 
+        if !resource.automount_service_account_token() {
+            let token_path = &self.config.settings.common.service_account_token_path;
+            mounts.retain(|mount| !mount.destination.eq(token_path));
+        }
+
         let mut storages = Default::default();
         resource.get_container_mounts_and_storages(
             &mut mounts,
@@ -652,6 +1343,10 @@ impl AgentPolicy {
             resource.use_sandbox_pidns()
         };
         let exec_commands = yaml_container.get_exec_commands();
+        let exec_allowlist = get_exec_allowlist(resource, yaml_container, &process);
+
+        let (os, architecture) = yaml_container.registry.config_layer.get_os_arch();
+        let image = ImageData { os, architecture };
 
         let mut devices: Vec<agent::Device> = vec![];
         if let Some(volumeDevices) = &yaml_container.volumeDevices {
@@ -670,11 +1365,50 @@ impl AgentPolicy {
             linux.Devices.push(default_device.clone())
         }
 
+        if yaml_container.requests_gpu() {
+            for device_regex in &self.config.settings.common.gpu_device_policies {
+                linux.Devices.push(KataLinuxDevice {
+                    Type: "".to_string(),
+                    Path: device_regex.clone(),
+                })
+            }
+        }
+
+        if yaml_container.requests_sriov() {
+            let sriov_device_prefix = &self.config.settings.common.sriov_device_prefix;
+            if !sriov_device_prefix.is_empty() {
+                linux.Devices.push(KataLinuxDevice {
+                    Type: "".to_string(),
+                    Path: format!("{sriov_device_prefix}/[0-9]+"),
+                })
+            }
+        }
+
         linux.Sysctl.extend(c_settings.Linux.Sysctl.clone());
         for sysctl in resource.get_sysctls() {
             linux.Sysctl.insert(sysctl.name, sysctl.value);
         }
 
+        let sbom_hash = if self.config.use_sbom && !is_pause_container {
+            Some(registry::get_sbom_hash(&yaml_container.image))
+        } else {
+            None
+        };
+
+        let mut signature_verified = None;
+        let mut signer_identity = None;
+        if self.config.verify_image_signatures && !is_pause_container {
+            if let Some(signature_policy) = &self.config.settings.common.image_signature_policy {
+                let signer = registry::verify_image_signature(
+                    &yaml_container.image,
+                    &signature_policy.required_signers,
+                )
+                .unwrap();
+                signature_verified = Some(true);
+                signer_identity = Some(signer);
+            }
+        }
+
         ContainerPolicy {
             OCI: KataSpec {
                 Version: self.config.settings.kata_config.oci_version.clone(),
@@ -689,6 +1423,13 @@ impl AgentPolicy {
             devices,
             sandbox_pidns,
             exec_commands,
+            exec_allowlist,
+            image,
+            sbom_hash,
+            signature_verified,
+            signer_identity,
+            allow_process_listing: self.config.settings.request_defaults.PsRequest,
+            is_sidecar: yaml_container.is_sidecar(),
         }
     }
 
@@ -707,10 +1448,11 @@ impl AgentPolicy {
 
         yaml_container.apply_capabilities(&mut process.Capabilities, &self.config.settings.common);
 
-        let (yaml_has_command, yaml_has_args) = yaml_container.get_process_args(&mut process.Args);
-        yaml_container
-            .registry
-            .get_process(&mut process, yaml_has_command, yaml_has_args);
+        process.Args = yaml::resolve_process_args(
+            yaml_container,
+            &yaml_container.registry.config_layer.config,
+        );
+        yaml_container.registry.get_process(&mut process);
 
         if let Some(tty) = yaml_container.tty {
             process.Terminal = tty;
@@ -738,6 +1480,14 @@ impl AgentPolicy {
             service_account_name,
         );
 
+        if !is_pause_container {
+            for env_var in resource.get_generated_env_variables() {
+                if !process.Env.contains(&env_var) {
+                    process.Env.push(env_var);
+                }
+            }
+        }
+
         substitute_env_variables(&mut process.Env);
         substitute_args_env_variables(&mut process.Args, &process.Env);
 
@@ -795,8 +1545,15 @@ async fn parse_config_file(
 ) -> Result<Vec<K8sEnvFromSource>> {
     let mut k8sRes = Vec::new();
     let yaml_contents = yaml::get_input_yaml(&Some(yaml_file))?;
+
+    // Deserialize all the documents before awaiting, because the YAML
+    // deserializer cannot be sent between threads.
+    let mut doc_mappings = Vec::new();
     for document in serde_yaml::Deserializer::from_str(&yaml_contents) {
-        let doc_mapping = Value::deserialize(document)?;
+        doc_mappings.push(Value::deserialize(document)?);
+    }
+
+    for doc_mapping in doc_mappings {
         if doc_mapping != Value::Null {
             let yaml_string = serde_yaml::to_string(&doc_mapping)?;
             let silent = config.silent_unsupported_fields;
@@ -973,10 +1730,12 @@ fn get_container_annotations(
             .or_insert(image_name);
     }
 
-    annotations.insert(
-        "io.kubernetes.cri.sandbox-namespace".to_string(),
-        namespace.to_string(),
-    );
+    if !resource.is_cluster_scoped() {
+        annotations.insert(
+            "io.kubernetes.cri.sandbox-namespace".to_string(),
+            namespace.to_string(),
+        );
+    }
 
     if !yaml_container.name.is_empty() {
         annotations
@@ -998,6 +1757,240 @@ fn get_container_annotations(
     annotations
 }
 
+/// Build a synthetic CreateContainerRequest input JSON document from a
+/// container's policy data, substituting a fixed synthetic bundle id for the
+/// "$(bundle-id)" placeholders.
+fn build_test_input(container_policy: &ContainerPolicy) -> serde_json::Value {
+    let bundle_id = "0".repeat(64);
+    let oci_text = serde_json::to_string(&container_policy.OCI)
+        .unwrap()
+        .replace("$(bundle-id)", &bundle_id);
+    let mut oci: serde_json::Value = serde_json::from_str(&oci_text).unwrap();
+    add_test_input_fields(&mut oci);
+
+    serde_json::json!({
+        "container_id": bundle_id,
+        "OCI": oci,
+        "storages": container_policy.storages,
+        "devices": container_policy.devices,
+        "sandbox_pidns": container_policy.sandbox_pidns,
+        "shared_mounts": [],
+        "string_user": serde_json::Value::Null,
+    })
+}
+
+/// Add to a synthetic CreateContainerRequest OCI document the input-only
+/// fields that the agent sends but that don't get recorded in the policy
+/// data, expected by the policy's allow_create_container_input rule.
+fn add_test_input_fields(oci: &mut serde_json::Value) {
+    let oci_object = oci.as_object_mut().unwrap();
+    for field in ["Hooks", "Solaris", "Windows"] {
+        oci_object.insert(field.to_string(), serde_json::Value::Null);
+    }
+
+    let linux_object = oci_object
+        .get_mut("Linux")
+        .unwrap()
+        .as_object_mut()
+        .unwrap();
+    for field in ["GIDMappings", "UIDMappings"] {
+        linux_object.insert(field.to_string(), serde_json::json!([]));
+    }
+    for field in ["MountLabel", "RootfsPropagation"] {
+        linux_object.insert(field.to_string(), serde_json::json!(""));
+    }
+    for field in ["IntelRdt", "Seccomp"] {
+        linux_object.insert(field.to_string(), serde_json::Value::Null);
+    }
+    linux_object.insert(
+        "Resources".to_string(),
+        serde_json::json!({
+            "Devices": [],
+            "BlockIO": null,
+            "Network": null,
+            "Pids": null,
+        }),
+    );
+
+    let process_object = oci_object
+        .get_mut("Process")
+        .unwrap()
+        .as_object_mut()
+        .unwrap();
+    process_object.insert("SelinuxLabel".to_string(), serde_json::json!(""));
+    process_object.insert("ApparmorProfile".to_string(), serde_json::json!(""));
+}
+
+/// Prefix of the per-container annotations listing the regex patterns of
+/// additional command lines that ExecProcessRequest is allowed to execute,
+/// e.g., "io.katacontainers.exec-allowlist/my-container": "command1,command2".
+const EXEC_ALLOWLIST_ANNOTATION_PREFIX: &str = "io.katacontainers.exec-allowlist/";
+
+/// Returns the comma separated regex patterns from this container's exec
+/// allowlist annotation, if present. Invalid regex patterns are fatal errors.
+/// The patterns get compared during policy generation with the container's
+/// image entrypoint/cmd, because the allowlist is meant for additional
+/// commands rather than for re-executing the container's main process.
+fn get_exec_allowlist(
+    resource: &dyn yaml::K8sResource,
+    yaml_container: &pod::Container,
+    process: &KataProcess,
+) -> Vec<String> {
+    let mut patterns = Vec::new();
+
+    if let Some(annotations) = resource.get_annotations() {
+        let annotation_key = format!("{EXEC_ALLOWLIST_ANNOTATION_PREFIX}{}", &yaml_container.name);
+        if let Some(annotation_value) = annotations.get(&annotation_key) {
+            let entry_command = process.Args.join(" ");
+            for pattern in annotation_value.split(',') {
+                let pattern = pattern.trim();
+                if pattern.is_empty() {
+                    continue;
+                }
+                let regex = regex::Regex::new(pattern).unwrap_or_else(|e| {
+                    panic!(
+                        "Invalid exec allowlist pattern <{pattern}> for container {}: {e}",
+                        &yaml_container.name
+                    )
+                });
+                if regex.is_match(&entry_command) {
+                    warn!(
+                        "Exec allowlist pattern <{pattern}> matches the entrypoint/cmd of container {}",
+                        &yaml_container.name
+                    );
+                }
+                patterns.push(pattern.to_string());
+            }
+        }
+    }
+
+    patterns
+}
+
+/// Annotation carrying additional Rego rules appended to the generated policy.
+const EXTRA_POLICY_ANNOTATION_KEY: &str = "io.katacontainers.extra-policy";
+
+/// Get the extra policy rules from the input YAML's annotations, after
+/// checking that regorus can compile them - instead of generating a policy
+/// that the agent would refuse to load.
+fn get_extra_policy_rules(resource: &dyn yaml::K8sResource) -> Option<String> {
+    let annotations = resource.get_annotations().as_ref()?;
+    let extra_rules = annotations.get(EXTRA_POLICY_ANNOTATION_KEY)?;
+
+    let mut engine = regorus::Engine::new();
+    engine
+        .add_policy(
+            "extra-policy.rego".to_string(),
+            format!("package agent_policy\n\n{extra_rules}"),
+        )
+        .unwrap_or_else(|e| panic!("Invalid {EXTRA_POLICY_ANNOTATION_KEY} annotation rules: {e}"));
+
+    warn!(
+        "Appending the extra policy rules from the {EXTRA_POLICY_ANNOTATION_KEY} \
+        annotation to the generated policy"
+    );
+    Some(extra_rules.clone())
+}
+
+/// Deduplicate identical rule bodies from the rules file: the shared body of
+/// each group of identical top level rules gets extracted into a helper rule,
+/// and the duplicated bodies get replaced with references to that helper.
+/// This reduces the policy size and makes policy diffs easier to read.
+///
+/// Only zero-argument rules get deduplicated. The body of a parameterized
+/// rule - e.g., "allow_var(p_process, i_process) if {" - references the rule
+/// parameters, which would be unbound variables in a shared zero-argument
+/// helper rule.
+fn deduplicate_rules(rules: &str) -> String {
+    // Parse the top level "<name> if {" ... "}" rules, remembering the other
+    // lines verbatim.
+    enum Segment {
+        Verbatim(String),
+        Rule { header: String, body: String },
+    }
+
+    let mut segments = Vec::new();
+    let mut body_counts: BTreeMap<String, usize> = BTreeMap::new();
+    let mut current_rule: Option<(String, String)> = None;
+
+    for line in rules.lines() {
+        if let Some((header, body)) = &mut current_rule {
+            if line == "}" {
+                *body_counts.entry(body.clone()).or_default() += 1;
+                segments.push(Segment::Rule {
+                    header: header.clone(),
+                    body: body.clone(),
+                });
+                current_rule = None;
+            } else {
+                body.push_str(line);
+                body.push('\n');
+            }
+        } else if !line.starts_with(char::is_whitespace)
+            && line.trim_end().ends_with(" if {")
+            && !line.contains('(')
+        {
+            current_rule = Some((line.to_string(), String::new()));
+        } else {
+            segments.push(Segment::Verbatim(line.to_string()));
+        }
+    }
+
+    // Rebuild the rules, substituting the helper rule names for the
+    // duplicated bodies.
+    let mut output = String::new();
+    let mut helpers: BTreeMap<String, String> = BTreeMap::new();
+
+    for segment in segments {
+        match segment {
+            Segment::Verbatim(line) => {
+                output.push_str(&line);
+                output.push('\n');
+            }
+            Segment::Rule { header, body } => {
+                if body_counts[&body] > 1 {
+                    let helper = helpers.entry(body.clone()).or_insert_with(|| {
+                        let hash: String = openssl::sha::sha256(body.as_bytes())
+                            .iter()
+                            .map(|byte| format!("{byte:02x}"))
+                            .collect();
+                        format!("shared_rule_body_{}", &hash[..16])
+                    });
+                    output.push_str(&format!("{header}\n    {helper}\n}}\n"));
+                } else {
+                    output.push_str(&format!("{header}\n{body}}}\n"));
+                }
+            }
+        }
+    }
+
+    // Define the helper rules.
+    for (body, helper) in &helpers {
+        output.push_str(&format!("\n{helper} if {{\n{body}}}\n"));
+    }
+
+    output
+}
+
+/// Add a default print statement at the start of each rule body, to help
+/// investigating requests denied by rules that don't print by themselves.
+fn add_debug_prints(rules: &str) -> String {
+    let mut output = String::new();
+
+    for line in rules.lines() {
+        output.push_str(line);
+        output.push('\n');
+
+        if line.trim_end().ends_with(" if {") {
+            let indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+            output.push_str(&indent);
+            output.push_str("    print(sprintf(\"Denied by rule: %v\", [input]))\n");
+        }
+    }
+
+    output
+}
+
 fn add_missing_strings(src: &Vec<String>, dest: &mut Vec<String>) {
     for src_string in src {
         if !dest.contains(src_string) {
@@ -1030,3 +2023,60 @@ pub fn get_kata_namespaces(
 
     namespaces
 }
+
+#[cfg(test)]
+mod tests {
+    use super::deduplicate_rules;
+
+    #[test]
+    fn deduplicate_rules_extracts_shared_bodies() {
+        let rules = "package agent_policy\n\
+            \n\
+            CloseStdinRequest if {\n\
+            \x20   policy_data.request_defaults.CloseStdinRequest\n\
+            }\n\
+            \n\
+            ReadStreamRequest if {\n\
+            \x20   policy_data.request_defaults.CloseStdinRequest\n\
+            }\n";
+        let output = deduplicate_rules(rules);
+
+        // The shared body got replaced with a single helper rule.
+        assert_eq!(output.matches("shared_rule_body_").count(), 3);
+        assert_eq!(
+            output
+                .matches("policy_data.request_defaults.CloseStdinRequest")
+                .count(),
+            1
+        );
+        assert!(output.contains("CloseStdinRequest if {"));
+        assert!(output.contains("ReadStreamRequest if {"));
+    }
+
+    #[test]
+    fn deduplicate_rules_keeps_unique_bodies() {
+        let rules = "package agent_policy\n\
+            \n\
+            CloseStdinRequest if {\n\
+            \x20   policy_data.request_defaults.CloseStdinRequest\n\
+            }\n\
+            \n\
+            ReadStreamRequest if {\n\
+            \x20   policy_data.request_defaults.ReadStreamRequest\n\
+            }\n";
+        assert_eq!(deduplicate_rules(rules), rules);
+    }
+
+    #[test]
+    fn deduplicate_rules_keeps_parameterized_rules_verbatim() {
+        // The bodies are identical, but the rules take parameters, so they
+        // must not get replaced with a shared zero-argument helper.
+        let rules = "allow_var(p_process, i_var) if {\n\
+            \x20   some p_var in p_process.Env\n\
+            }\n\
+            allow_var(p_process, i_var, s_name) if {\n\
+            \x20   some p_var in p_process.Env\n\
+            }\n";
+        assert_eq!(deduplicate_rules(rules), rules);
+    }
+}
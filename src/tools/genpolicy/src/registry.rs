@@ -15,14 +15,22 @@ use anyhow::{anyhow, bail, Result};
 use docker_credential::{CredentialRetrievalError, DockerCredential};
 use log::{debug, info, warn};
 use oci_client::{
-    client::{linux_amd64_resolver, ClientConfig, ClientProtocol},
+    client::{ClientConfig, ClientProtocol},
     manifest,
     secrets::RegistryAuth,
     Client, Reference,
 };
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, io::Read, io::Write, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    io::Read,
+    io::Write,
+    path::Path,
+    sync::Arc,
+};
 use tokio::io::AsyncWriteExt;
+use tokio::sync::{OnceCell, RwLock};
 
 /// Container image properties obtained from an OCI repository.
 #[derive(Clone, Debug, Default)]
@@ -32,25 +40,41 @@ pub struct Container {
     pub config_layer: DockerConfigLayer,
     pub passwd: String,
     pub group: String,
+
+    /// True if docker credentials were configured for the image's registry,
+    /// indicating that pulling the image requires authentication.
+    pub auth_configured: bool,
 }
 
 /// Image config layer properties.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct DockerConfigLayer {
     architecture: String,
+
+    #[serde(default)]
+    os: String,
+
     pub config: DockerImageConfig,
     pub rootfs: DockerRootfs,
 }
 
+impl DockerConfigLayer {
+    /// The "os" and "architecture" fields of the image config - e.g.,
+    /// ("linux", "amd64").
+    pub fn get_os_arch(&self) -> (String, String) {
+        (self.os.clone(), self.architecture.clone())
+    }
+}
+
 /// See: https://docs.docker.com/reference/dockerfile/.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct DockerImageConfig {
     User: Option<String>,
     Tty: Option<bool>,
     Env: Option<Vec<String>>,
-    Cmd: Option<Vec<String>>,
+    pub Cmd: Option<Vec<String>>,
     WorkingDir: Option<String>,
-    Entrypoint: Option<Vec<String>>,
+    pub Entrypoint: Option<Vec<String>>,
     pub Volumes: Option<BTreeMap<String, DockerVolumeHostDirectory>>,
 }
 
@@ -124,25 +148,183 @@ const GROUP_FILE_WHITEOUT_TAR_PATH: &str = "etc/.wh.group";
 /// layers should be discarded.
 pub const WHITEOUT_MARKER: &str = "WHITEOUT";
 
+/// GOARCH-style name of the host architecture, used as the default target
+/// architecture when resolving multi-arch container image manifest lists.
+pub fn default_target_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "powerpc64" => "ppc64le",
+        arch => arch,
+    }
+}
+
+/// Select from a multi-arch manifest list the manifest of the linux image
+/// matching the target architecture.
+fn arch_resolver(arch: &str, manifests: &[manifest::ImageIndexEntry]) -> Option<String> {
+    manifests
+        .iter()
+        .find(|entry| {
+            entry
+                .platform
+                .as_ref()
+                .is_some_and(|platform| platform.os == "linux" && platform.architecture == arch)
+        })
+        .map(|entry| entry.digest.clone())
+}
+
+/// Get references to the mirrors configured for the registry of an image
+/// reference. The image reference got normalized by its parser, so e.g. the
+/// "docker.io" mirrors apply to images that don't specify a registry.
+fn get_mirror_references(config: &Config, reference: &Reference) -> Vec<Reference> {
+    let Some(mirrors) = config
+        .settings
+        .common
+        .registry_mirrors
+        .get(reference.resolve_registry())
+    else {
+        return Vec::new();
+    };
+
+    mirrors
+        .iter()
+        .map(|mirror| {
+            if let Some(digest) = reference.digest() {
+                Reference::with_digest(
+                    mirror.to_string(),
+                    reference.repository().to_string(),
+                    digest.to_string(),
+                )
+            } else {
+                Reference::with_tag(
+                    mirror.to_string(),
+                    reference.repository().to_string(),
+                    reference.tag().unwrap_or("latest").to_string(),
+                )
+            }
+        })
+        .collect()
+}
+
+/// Expand a possibly short container image reference to its fully qualified
+/// form - e.g., "nginx:latest" gets expanded to
+/// "docker.io/library/nginx:latest", and the legacy "index.docker.io" domain
+/// gets replaced with "docker.io". Short forms of otherwise identical
+/// references could evade the registry mirror and image signature checks
+/// without this normalization.
+pub fn normalize_image_ref(image: &str) -> Result<String> {
+    let reference: Reference = image.to_string().parse()?;
+    Ok(reference.whole())
+}
+
+/// Manifest, manifest digest, and config pulled for one container image
+/// reference.
+#[derive(Clone, Debug)]
+pub struct ImageManifest {
+    /// Image reference the pull actually succeeded from - differs from the
+    /// requested reference when a registry mirror got used.
+    pub reference: String,
+    pub manifest: manifest::OciImageManifest,
+    pub digest_hash: String,
+    pub config_layer_str: String,
+}
+
+/// Cache of pulled image manifests, keyed by image reference. Multiple
+/// containers of the same pod often use the same image, so concurrent
+/// requests for one reference share a single in-flight registry pull through
+/// the OnceCell of that reference - later requests wait for the first pull to
+/// complete instead of issuing duplicate registry API calls.
+#[derive(Clone, Debug, Default)]
+pub struct ManifestCache {
+    inner: Arc<RwLock<HashMap<String, Arc<OnceCell<ImageManifest>>>>>,
+}
+
+impl ManifestCache {
+    async fn get_cell(&self, image: &str) -> Arc<OnceCell<ImageManifest>> {
+        if let Some(cell) = self.inner.read().await.get(image) {
+            return cell.clone();
+        }
+        self.inner
+            .write()
+            .await
+            .entry(image.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Returns the cached manifest of this image, pulling it through the
+    /// input future when no other request pulled it already.
+    pub async fn get_or_pull<F, Fut>(
+        &self,
+        image: &str,
+        pull: F,
+    ) -> std::result::Result<ImageManifest, oci_client::errors::OciDistributionError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<
+            Output = std::result::Result<ImageManifest, oci_client::errors::OciDistributionError>,
+        >,
+    {
+        let cell = self.get_cell(image).await;
+        Ok(cell.get_or_try_init(pull).await?.clone())
+    }
+}
+
 impl Container {
     pub async fn new(config: &Config, image: &str) -> Result<Self> {
         info!("============================================");
-        info!("Pulling manifest and config for {image}");
-        let image_string = image.to_string();
+        let image_string = normalize_image_ref(image).unwrap();
+        info!("Pulling manifest and config for {image_string}");
         let reference: Reference = image_string.parse().unwrap();
-        let auth = build_auth(&reference);
+        let auth = build_auth(&reference, config.docker_config.as_deref());
+        let auth_configured = !matches!(auth, RegistryAuth::Anonymous);
 
+        let target_arch = config.target_arch.clone();
         let mut client = Client::new(ClientConfig {
             protocol: ClientProtocol::HttpsExcept(config.insecure_registries.clone()),
-            platform_resolver: Some(Box::new(linux_amd64_resolver)),
+            platform_resolver: Some(Box::new(
+                move |manifests: &[manifest::ImageIndexEntry]| {
+                    arch_resolver(&target_arch, manifests)
+                },
+            )),
             ..Default::default()
         });
 
-        let (manifest, digest_hash, config_layer_str) = match client
-            .pull_manifest_and_config(&reference, &auth)
-            .await
-        {
-            Ok((m, d, c)) => (m, d, c),
+        let pull_result = config
+            .manifest_cache
+            .get_or_pull(&image_string, || async {
+                let mut reference = reference.clone();
+                let mut pull_result = client.pull_manifest_and_config(&reference, &auth).await;
+
+                if pull_result.is_err() {
+                    for mirror_reference in get_mirror_references(config, &reference) {
+                        warn!(
+                            "Failed to pull manifest and config for {reference}, retrying with mirror {}",
+                            mirror_reference.registry()
+                        );
+                        let mirror_auth =
+                            build_auth(&mirror_reference, config.docker_config.as_deref());
+                        pull_result = client
+                            .pull_manifest_and_config(&mirror_reference, &mirror_auth)
+                            .await;
+                        if pull_result.is_ok() {
+                            reference = mirror_reference;
+                            break;
+                        }
+                    }
+                }
+
+                pull_result.map(|(manifest, digest_hash, config_layer_str)| ImageManifest {
+                    reference: reference.whole(),
+                    manifest,
+                    digest_hash,
+                    config_layer_str,
+                })
+            })
+            .await;
+
+        let image_manifest = match pull_result {
+            Ok(image_manifest) => image_manifest,
             Err(oci_client::errors::OciDistributionError::AuthenticationFailure(message)) => {
                 panic!("Container image registry authentication failure ({}). Are docker credentials set-up for current user?", &message);
             }
@@ -154,6 +336,11 @@ impl Container {
             }
         };
 
+        let reference: Reference = image_manifest.reference.parse().unwrap();
+        let manifest = image_manifest.manifest;
+        let digest_hash = image_manifest.digest_hash;
+        let config_layer_str = image_manifest.config_layer_str;
+
         debug!("digest_hash: {:?}", digest_hash);
         debug!(
             "manifest: {}",
@@ -175,6 +362,7 @@ impl Container {
                 config_layer,
                 passwd,
                 group,
+                auth_configured,
             });
         }
 
@@ -209,6 +397,7 @@ impl Container {
             config_layer,
             passwd,
             group,
+            auth_configured,
         })
     }
 
@@ -317,12 +506,7 @@ impl Container {
     }
 
     // Convert Docker image config to policy data.
-    pub fn get_process(
-        &self,
-        process: &mut policy::KataProcess,
-        yaml_has_command: bool,
-        yaml_has_args: bool,
-    ) {
+    pub fn get_process(&self, process: &mut policy::KataProcess) {
         debug!("Getting process field from docker config layer...");
         let docker_config = &self.config_layer.config;
 
@@ -380,45 +564,6 @@ impl Container {
             containerd::get_default_unix_env(&mut process.Env);
         }
 
-        let policy_args = &mut process.Args;
-        debug!("Already existing policy args: {:?}", policy_args);
-
-        if let Some(entry_points) = &docker_config.Entrypoint {
-            debug!("Image Entrypoint: {:?}", entry_points);
-            if !yaml_has_command {
-                debug!("Inserting Entrypoint into policy args");
-
-                let mut reversed_entry_points = entry_points.clone();
-                reversed_entry_points.reverse();
-
-                for entry_point in reversed_entry_points {
-                    policy_args.insert(0, entry_point.clone());
-                }
-            } else {
-                debug!("Ignoring image Entrypoint because YAML specified the container command");
-            }
-        } else {
-            debug!("No image Entrypoint");
-        }
-
-        debug!("Updated policy args: {:?}", policy_args);
-
-        if yaml_has_command {
-            debug!("Ignoring image Cmd because YAML specified the container command");
-        } else if yaml_has_args {
-            debug!("Ignoring image Cmd because YAML specified the container args");
-        } else if let Some(commands) = &docker_config.Cmd {
-            debug!("Adding to policy args the image Cmd: {:?}", commands);
-
-            for cmd in commands {
-                policy_args.push(cmd.clone());
-            }
-        } else {
-            debug!("Image Cmd field is not present");
-        }
-
-        debug!("Updated policy args: {:?}", policy_args);
-
         if let Some(working_dir) = &docker_config.WorkingDir {
             if !working_dir.is_empty() {
                 process.Cwd.clone_from(working_dir);
@@ -596,7 +741,81 @@ pub async fn get_container(config: &Config, image: &str) -> Result<Container> {
     Container::new(config, image).await
 }
 
-fn build_auth(reference: &Reference) -> RegistryAuth {
+/// Download the SBOM attestation attached to a container image, using cosign,
+/// and return the SHA256 hash of the SBOM document.
+pub fn get_sbom_hash(image: &str) -> String {
+    info!("Downloading SBOM for {image}");
+    let output = std::process::Command::new("cosign")
+        .args(["download", "sbom", image])
+        .output()
+        .expect("Failed to execute cosign. Is cosign installed for the current user?");
+
+    if !output.status.success() {
+        panic!(
+            "Failed to download the SBOM of {image} - error: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let digest = openssl::sha::sha256(&output.stdout);
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Verify the cosign signature of a container image against the required
+/// signers, returning the first signer that successfully verifies the image.
+pub fn verify_image_signature(image: &str, required_signers: &[String]) -> Result<String> {
+    let image = &normalize_image_ref(image)?;
+    for signer in required_signers {
+        info!("Verifying the signature of {image} using signer {signer}");
+
+        let args = if Path::new(signer).exists() {
+            vec!["verify", "--key", signer, image]
+        } else {
+            vec![
+                "verify",
+                "--certificate-oidc-issuer",
+                signer,
+                "--certificate-identity-regexp",
+                ".*",
+                image,
+            ]
+        };
+        let output = std::process::Command::new("cosign")
+            .args(args)
+            .output()
+            .expect("Failed to execute cosign. Is cosign installed for the current user?");
+
+        if output.status.success() {
+            return Ok(signer.clone());
+        }
+
+        warn!(
+            "Failed to verify the signature of {image} using signer {signer} - error: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    bail!("Failed to verify the signature of {image} using any of the required signers")
+}
+
+/// Get the credentials for a registry server from the Docker config file at
+/// the given path, supporting both inline auth entries and credential
+/// helpers.
+pub fn auth_from_docker_config(config_path: &Path, server: &str) -> Result<RegistryAuth> {
+    let file = std::fs::File::open(config_path)?;
+
+    match docker_credential::get_credential_from_reader(std::io::BufReader::new(file), server)? {
+        DockerCredential::UsernamePassword(username, password) => {
+            Ok(RegistryAuth::Basic(username, password))
+        }
+        DockerCredential::IdentityToken(_) => {
+            warn!("auth_from_docker_config: Cannot use contents of docker config, identity token not supported. Using anonymous access.");
+            Ok(RegistryAuth::Anonymous)
+        }
+    }
+}
+
+fn build_auth(reference: &Reference, docker_config: Option<&str>) -> RegistryAuth {
     debug!("build_auth: {:?}", reference);
 
     let server = reference
@@ -604,6 +823,13 @@ fn build_auth(reference: &Reference) -> RegistryAuth {
         .strip_suffix('/')
         .unwrap_or_else(|| reference.resolve_registry());
 
+    if let Some(docker_config) = docker_config {
+        return match auth_from_docker_config(Path::new(docker_config), server) {
+            Ok(auth) => auth,
+            Err(e) => panic!("Error handling docker configuration file {docker_config}: {e}"),
+        };
+    }
+
     match docker_credential::get_credential(server) {
         Ok(DockerCredential::UsernamePassword(username, password)) => {
             debug!("build_auth: Found docker credentials");
@@ -694,3 +920,48 @@ fn parse_group_file(group: &str) -> Result<Vec<GroupRecord>> {
 
     Ok(records)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_image_ref;
+
+    #[test]
+    fn normalize_image_ref_expands_short_references() {
+        assert_eq!(
+            normalize_image_ref("nginx:1.25").unwrap(),
+            "docker.io/library/nginx:1.25"
+        );
+        assert_eq!(
+            normalize_image_ref("fedora/httpd:v1").unwrap(),
+            "docker.io/fedora/httpd:v1"
+        );
+    }
+
+    #[test]
+    fn normalize_image_ref_keeps_qualified_references() {
+        assert_eq!(
+            normalize_image_ref("quay.io/prometheus/busybox:latest").unwrap(),
+            "quay.io/prometheus/busybox:latest"
+        );
+        assert_eq!(
+            normalize_image_ref(
+                "registry.k8s.io/pause@sha256:7031c1b283388d2c2e09b57badb803c05ebed362dc88d84b480cc47f72a21097"
+            )
+            .unwrap(),
+            "registry.k8s.io/pause@sha256:7031c1b283388d2c2e09b57badb803c05ebed362dc88d84b480cc47f72a21097"
+        );
+    }
+
+    #[test]
+    fn normalize_image_ref_replaces_legacy_docker_domain() {
+        assert_eq!(
+            normalize_image_ref("index.docker.io/library/nginx:1.25").unwrap(),
+            "docker.io/library/nginx:1.25"
+        );
+    }
+
+    #[test]
+    fn normalize_image_ref_rejects_invalid_references() {
+        assert!(normalize_image_ref("NGINX:latest").is_err());
+    }
+}
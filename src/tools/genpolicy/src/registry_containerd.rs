@@ -50,6 +50,8 @@ impl Container {
         let image_str = image.to_string();
         let image_ref: Reference = image_str.parse().unwrap();
 
+        let auth_configured = build_auth(&image_ref).is_some();
+
         info!("Pulling image: {:?}", image_ref);
         pull_image(&image_ref, k8_cri_image_client.clone()).await?;
 
@@ -77,6 +79,7 @@ impl Container {
                 config_layer,
                 passwd,
                 group,
+                auth_configured,
             });
         }
 
@@ -106,6 +109,7 @@ impl Container {
             config_layer,
             passwd,
             group,
+            auth_configured,
         })
     }
 }
@@ -0,0 +1,109 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use protobuf::reflect::{MessageDescriptor, RuntimeFieldType, RuntimeType};
+use protobuf::MessageFull;
+use protocols::agent;
+use serde_json::json;
+
+/// Print JSON Schema documents describing the OPA input JSON of each policy
+/// request type. The Kata agent serializes these protobuf messages using
+/// serde, so the schemas are derived from the protobuf message descriptors,
+/// following the serde representation of each field type - e.g., enum values
+/// are represented by their integer values.
+pub fn print_schemas() {
+    let descriptors = [
+        agent::AddARPNeighborsRequest::descriptor(),
+        agent::CloseStdinRequest::descriptor(),
+        agent::CopyFileRequest::descriptor(),
+        agent::CreateContainerRequest::descriptor(),
+        agent::CreateSandboxRequest::descriptor(),
+        agent::ExecProcessRequest::descriptor(),
+        agent::GuestDetailsRequest::descriptor(),
+        agent::MemHotplugByProbeRequest::descriptor(),
+        agent::OnlineCPUMemRequest::descriptor(),
+        agent::ReadStreamRequest::descriptor(),
+        agent::RemoveContainerRequest::descriptor(),
+        agent::SetGuestDateTimeRequest::descriptor(),
+        agent::SignalProcessRequest::descriptor(),
+        agent::StatsContainerRequest::descriptor(),
+        agent::TtyWinResizeRequest::descriptor(),
+        agent::UpdateEphemeralMountsRequest::descriptor(),
+        agent::UpdateInterfaceRequest::descriptor(),
+        agent::UpdateRoutesRequest::descriptor(),
+        agent::WaitProcessRequest::descriptor(),
+        agent::WriteStreamRequest::descriptor(),
+    ];
+
+    let mut schemas = serde_json::Map::new();
+    for descriptor in &descriptors {
+        let mut visiting = Vec::new();
+        let mut schema = message_schema(descriptor, &mut visiting);
+        schema["$schema"] = json!("http://json-schema.org/draft-07/schema#");
+        schemas.insert(descriptor.name().to_string(), schema);
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&serde_json::Value::Object(schemas)).unwrap()
+    );
+}
+
+/// Generate the schema of a protobuf message type from its descriptor. The
+/// visiting vector breaks the recursion for self-referential message types.
+fn message_schema(descriptor: &MessageDescriptor, visiting: &mut Vec<String>) -> serde_json::Value {
+    let full_name = descriptor.full_name().to_string();
+    if visiting.contains(&full_name) {
+        return json!({"type": "object"});
+    }
+    visiting.push(full_name);
+
+    let mut properties = serde_json::Map::new();
+    for field in descriptor.fields() {
+        let schema = match field.runtime_field_type() {
+            RuntimeFieldType::Singular(field_type) => singular_schema(&field_type, visiting),
+            RuntimeFieldType::Repeated(field_type) => json!({
+                "type": "array",
+                "items": singular_schema(&field_type, visiting),
+            }),
+            RuntimeFieldType::Map(_, value_type) => json!({
+                "type": "object",
+                "additionalProperties": singular_schema(&value_type, visiting),
+            }),
+        };
+        properties.insert(field.name().to_string(), schema);
+    }
+
+    visiting.pop();
+    json!({
+        "type": "object",
+        "properties": properties,
+    })
+}
+
+/// Generate the schema of a single protobuf field value.
+fn singular_schema(field_type: &RuntimeType, visiting: &mut Vec<String>) -> serde_json::Value {
+    match field_type {
+        RuntimeType::I32
+        | RuntimeType::I64
+        | RuntimeType::U32
+        | RuntimeType::U64
+        | RuntimeType::Enum(_) => json!({"type": "integer"}),
+        RuntimeType::F32 | RuntimeType::F64 => json!({"type": "number"}),
+        RuntimeType::Bool => json!({"type": "boolean"}),
+        RuntimeType::String => json!({"type": "string"}),
+        RuntimeType::VecU8 => json!({
+            "type": "array",
+            "items": {"type": "integer"},
+        }),
+        // Missing message fields are serialized as null.
+        RuntimeType::Message(descriptor) => json!({
+            "oneOf": [
+                message_schema(descriptor, visiting),
+                {"type": "null"},
+            ]
+        }),
+    }
+}
@@ -8,13 +8,16 @@
 
 use crate::policy;
 
+use anyhow::{bail, Result};
 use log::debug;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fs::File;
+use std::path::Path;
 use std::str;
 
 /// Policy settings loaded from genpolicy-settings.json.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
 pub struct Settings {
     pub pause_container: policy::KataSpec,
     pub other_container: policy::KataSpec,
@@ -25,10 +28,16 @@ pub struct Settings {
     pub common: policy::CommonData,
     pub mount_destinations: Vec<String>,
     pub sandbox: policy::SandboxData,
+
+    /// Add a default print statement to every rule from the rules file, so
+    /// that denied requests always produce print output, even for rules that
+    /// don't call print() themselves.
+    #[serde(default)]
+    pub debug_print_on_deny: bool,
 }
 
 /// Volume settings loaded from genpolicy-settings.json.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Volumes {
     pub emptyDir: EmptyDirVolume,
     pub emptyDir_memory: EmptyDirVolume,
@@ -37,7 +46,7 @@ pub struct Volumes {
 }
 
 /// EmptyDir volume settings loaded from genpolicy-settings.json.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct EmptyDirVolume {
     pub mount_type: String,
     pub mount_source: String,
@@ -49,7 +58,7 @@ pub struct EmptyDirVolume {
 }
 
 /// ConfigMap volume settings loaded from genpolicy-settings.json.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ConfigMapVolume {
     pub mount_type: String,
     pub mount_source: String,
@@ -60,7 +69,7 @@ pub struct ConfigMapVolume {
 }
 
 /// Container image volume settings loaded from genpolicy-settings.json.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ImageVolume {
     pub mount_type: String,
     pub mount_source: String,
@@ -72,7 +81,7 @@ pub struct ImageVolume {
 
 /// Data corresponding to the kata runtime config file data, loaded from
 /// genpolicy-settings.json.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct KataConfig {
     pub oci_version: String,
     pub enable_configmap_secret_storages: bool,
@@ -92,6 +101,67 @@ impl Settings {
         }
     }
 
+    /// Deep-merge the settings from an override file into these settings:
+    /// maps get merged key by key and scalars get replaced. Arrays get
+    /// replaced too, except that an array under a key starting with "+" gets
+    /// appended to the corresponding array from the base settings.
+    pub fn merge_from_file(&mut self, override_path: &Path) -> Result<()> {
+        let overrides: serde_json::Value = serde_json::from_reader(File::open(override_path)?)?;
+
+        let mut merged = serde_json::to_value(&self)?;
+        merge_values(&mut merged, overrides);
+
+        *self = serde_json::from_value(merged)?;
+        Self::validate_settings(self);
+        Ok(())
+    }
+
+    /// Apply settings overrides from environment variables, named using the
+    /// GENPOLICY_<SECTION>_<FIELD> convention - e.g., GENPOLICY_COMMON_CPATH
+    /// overrides the common.cpath settings field and
+    /// GENPOLICY_MOUNT_DESTINATIONS overrides the top level
+    /// mount_destinations field. The values of array fields are
+    /// comma-separated, and boolean and numeric fields use their JSON
+    /// representations.
+    pub fn apply_env_overrides(&mut self) -> Result<()> {
+        let mut merged = serde_json::to_value(&self)?;
+        let settings_map = merged.as_object_mut().unwrap();
+
+        for (name, value) in std::env::vars() {
+            let Some(name) = name.strip_prefix("GENPOLICY_") else {
+                continue;
+            };
+            let name = name.to_lowercase();
+
+            let base_value = if let Some(base_value) = settings_map.get_mut(&name) {
+                base_value
+            } else if let Some((section, field)) = settings_map.keys().find_map(|key| {
+                let field = name.strip_prefix(key.as_str())?.strip_prefix('_')?;
+                Some((key.clone(), field.to_string()))
+            }) {
+                match settings_map
+                    .get_mut(&section)
+                    .and_then(|section_value| section_value.as_object_mut())
+                    .and_then(|section_map| section_map.get_mut(&field))
+                {
+                    Some(base_value) => base_value,
+                    None => bail!("Unsupported settings field {section}.{field} from environment variable GENPOLICY_{}", name.to_uppercase()),
+                }
+            } else {
+                bail!(
+                    "Unsupported settings override from environment variable GENPOLICY_{}",
+                    name.to_uppercase()
+                );
+            };
+
+            *base_value = env_override_value(&value, base_value)?;
+        }
+
+        *self = serde_json::from_value(merged)?;
+        Self::validate_settings(self);
+        Ok(())
+    }
+
     pub fn get_container_settings(&self, is_pause_container: bool) -> &policy::KataSpec {
         if is_pause_container {
             &self.pause_container
@@ -107,5 +177,141 @@ impl Settings {
                     Please use <request_defaults.ExecProcessRequest.allowed_commands> instead.");
             }
         }
+
+        if let Some(psa_level) = &settings.common.psa_level {
+            if !matches!(psa_level.as_str(), "privileged" | "baseline" | "restricted") {
+                panic!(
+                    "Unsupported settings field <common.psa_level> value: {}. \
+                    Supported values are privileged, baseline and restricted.",
+                    psa_level
+                );
+            }
+        }
+    }
+}
+
+/// Recursively merge an override JSON value into a base value, as described
+/// for Settings::merge_from_file().
+fn merge_values(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base_map), serde_json::Value::Object(override_map)) => {
+            for (key, value) in override_map {
+                if let Some(append_key) = key.strip_prefix('+') {
+                    if let serde_json::Value::Array(mut items) = value {
+                        match base_map.get_mut(append_key) {
+                            Some(serde_json::Value::Array(base_items)) => {
+                                base_items.append(&mut items)
+                            }
+                            _ => {
+                                base_map.insert(
+                                    append_key.to_string(),
+                                    serde_json::Value::Array(items),
+                                );
+                            }
+                        }
+                        continue;
+                    }
+                    panic!("Settings override key {key} requires an array value");
+                }
+
+                match base_map.get_mut(&key) {
+                    Some(base_value) => merge_values(base_value, value),
+                    None => {
+                        base_map.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Convert the value of a settings override environment variable to the JSON
+/// type of the settings field that it overrides.
+fn env_override_value(value: &str, base: &serde_json::Value) -> Result<serde_json::Value> {
+    let value = match base {
+        serde_json::Value::Array(_) => serde_json::Value::Array(
+            value
+                .split(',')
+                .map(|item| serde_json::Value::String(item.to_string()))
+                .collect(),
+        ),
+        serde_json::Value::Bool(_) => serde_json::Value::Bool(value.parse()?),
+        serde_json::Value::Number(_) => serde_json::Value::Number(value.parse()?),
+        _ => serde_json::Value::String(value.to_string()),
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_values;
+    use serde_json::json;
+
+    #[test]
+    fn merge_values_merges_nested_objects() {
+        let mut base = json!({
+            "common": {
+                "cpath": "/run/kata-containers",
+                "sfprefix": "^$(cpath)/shared/"
+            },
+            "request_defaults": {
+                "ReadStreamRequest": false
+            }
+        });
+        merge_values(
+            &mut base,
+            json!({
+                "common": {
+                    "cpath": "/run/confidential-containers"
+                },
+                "cluster_config": {
+                    "pause_container_image": "pause:3.9"
+                }
+            }),
+        );
+        assert_eq!(
+            base,
+            json!({
+                "common": {
+                    "cpath": "/run/confidential-containers",
+                    "sfprefix": "^$(cpath)/shared/"
+                },
+                "request_defaults": {
+                    "ReadStreamRequest": false
+                },
+                "cluster_config": {
+                    "pause_container_image": "pause:3.9"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn merge_values_appends_arrays_with_plus_prefix() {
+        let mut base = json!({ "kata_config": { "oci_version": ["1.1.0"] } });
+        merge_values(
+            &mut base,
+            json!({ "kata_config": { "+oci_version": ["1.2.0"] } }),
+        );
+        assert_eq!(
+            base,
+            json!({ "kata_config": { "oci_version": ["1.1.0", "1.2.0"] } })
+        );
+    }
+
+    #[test]
+    fn merge_values_inserts_appended_array_when_missing() {
+        let mut base = json!({});
+        merge_values(&mut base, json!({ "+items": ["first"] }));
+        assert_eq!(base, json!({ "items": ["first"] }));
+    }
+
+    #[test]
+    fn merge_values_replaces_non_object_values() {
+        let mut base = json!({ "timeout": 10 });
+        merge_values(&mut base, json!({ "timeout": 20 }));
+        assert_eq!(base, json!({ "timeout": 20 }));
     }
 }
@@ -6,6 +6,7 @@
 // Allow K8s YAML field names.
 #![allow(non_snake_case)]
 
+use crate::limit_range;
 use crate::obj_meta;
 use crate::persistent_volume_claim;
 use crate::pod;
@@ -110,6 +111,21 @@ impl yaml::K8sResource for StatefulSet {
         self.metadata.get_namespace()
     }
 
+    fn get_sandbox_hostname(&self) -> Option<String> {
+        // StatefulSet pods get a stable hostname derived from the StatefulSet
+        // name and the pod's ordinal. When spec.serviceName points to a
+        // headless Service, the pods additionally get the matching
+        // <pod>.<service>.<namespace>.svc.cluster.local FQDN.
+        let prefix = yaml::name_regex_from_meta(&self.metadata)?;
+        let namespace = self
+            .get_namespace()
+            .unwrap_or_else(|| "default".to_string());
+        Some(format!(
+            "{prefix}-[0-9]+(\\.{}\\.{namespace}\\.svc\\.cluster\\.local)?",
+            regex::escape(&self.spec.serviceName)
+        ))
+    }
+
     fn get_container_mounts_and_storages(
         &self,
         policy_mounts: &mut Vec<policy::KataMount>,
@@ -160,6 +176,10 @@ impl yaml::K8sResource for StatefulSet {
         serde_yaml::to_string(&self.doc_mapping).unwrap()
     }
 
+    fn get_metadata_path(&self) -> Option<String> {
+        Some("spec.template".to_string())
+    }
+
     fn get_containers(&self) -> &Vec<pod::Container> {
         &self.spec.template.spec.containers
     }
@@ -205,6 +225,37 @@ impl yaml::K8sResource for StatefulSet {
     fn get_sysctls(&self) -> Vec<pod::Sysctl> {
         yaml::get_sysctls(&self.spec.template.spec.securityContext)
     }
+
+    fn apply_limit_range_defaults(&mut self, limit_ranges: &[limit_range::LimitRange]) {
+        yaml::apply_limit_range_defaults(&mut self.spec.template.spec, limit_ranges);
+    }
+
+    fn resolve_local_volumes(&mut self, local_pv_paths: &BTreeMap<String, String>) {
+        yaml::resolve_local_volumes(&mut self.spec.template.spec, local_pv_paths);
+    }
+
+    fn automount_service_account_token(&self) -> bool {
+        yaml::automount_service_account_token(&self.spec.template.spec)
+    }
+
+    fn add_injected_sidecars(&mut self, injection: &yaml::SidecarInjection) {
+        yaml::add_injected_sidecars(&mut self.spec.template.spec, injection);
+    }
+
+    fn get_volume_attributes_classes(&self) -> Option<Vec<String>> {
+        let classes: Vec<String> = self
+            .spec
+            .volumeClaimTemplates
+            .as_ref()?
+            .iter()
+            .filter_map(|claim| claim.spec.volumeAttributesClassName.clone())
+            .collect();
+        if classes.is_empty() {
+            None
+        } else {
+            Some(classes)
+        }
+    }
 }
 
 impl StatefulSet {
@@ -0,0 +1,85 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Allow K8s YAML field names.
+#![allow(non_snake_case)]
+
+use crate::obj_meta;
+use crate::policy;
+use crate::utils::Config;
+use crate::yaml;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// See Reference / Kubernetes API / Config and Storage Resources / StorageClass.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StorageClass {
+    apiVersion: String,
+    kind: String,
+    pub metadata: obj_meta::ObjectMeta,
+
+    pub provisioner: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<BTreeMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reclaimPolicy: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volumeBindingMode: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allowVolumeExpansion: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mountOptions: Option<Vec<String>>,
+
+    #[serde(skip)]
+    doc_mapping: serde_yaml::Value,
+}
+
+/// Returns the provisioner of the StorageClass with the input name, for
+/// cross-referencing PersistentVolumeClaim storageClassName values against
+/// the StorageClasses from the same input YAML.
+pub fn get_provisioner<'a>(
+    storage_classes: &'a [StorageClass],
+    storage_class_name: &str,
+) -> Option<&'a str> {
+    storage_classes
+        .iter()
+        .find(|storage_class| storage_class.metadata.name.as_deref() == Some(storage_class_name))
+        .map(|storage_class| storage_class.provisioner.as_str())
+}
+
+#[async_trait]
+impl yaml::K8sResource for StorageClass {
+    async fn init(
+        &mut self,
+        _config: &Config,
+        doc_mapping: &serde_yaml::Value,
+        _silent_unsupported_fields: bool,
+    ) {
+        self.doc_mapping = doc_mapping.clone();
+    }
+
+    fn is_cluster_scoped(&self) -> bool {
+        true
+    }
+
+    fn generate_initdata_anno(&self, _agent_policy: &policy::AgentPolicy) -> String {
+        "".to_string()
+    }
+
+    fn serialize(&mut self, _policy: &str) -> String {
+        serde_yaml::to_string(&self.doc_mapping).unwrap()
+    }
+
+    fn get_annotations(&self) -> &Option<BTreeMap<String, String>> {
+        &self.metadata.annotations
+    }
+}
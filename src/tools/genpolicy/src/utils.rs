@@ -4,11 +4,16 @@
 //
 
 use crate::layers_cache;
+use crate::registry;
 use crate::settings;
-use clap::Parser;
+use crate::yaml;
+use clap::{Args, Parser, Subcommand};
 
 #[derive(Debug, Parser)]
 struct CommandLineOptions {
+    #[clap(subcommand)]
+    command: Option<Commands>,
+
     #[clap(
         short,
         long,
@@ -29,6 +34,24 @@ struct CommandLineOptions {
     )]
     config_file: Option<Vec<String>>,
 
+    #[clap(
+        long,
+        help = "Optional Kubernetes YAML input file path containing LimitRange resources that provide default resource limits and requests for containers"
+    )]
+    limit_range_file: Option<String>,
+
+    #[clap(
+        long,
+        help = "Optional Kubernetes YAML input file path containing PersistentVolume resources that define e.g. the local paths bound to the PersistentVolumeClaims of the input resources"
+    )]
+    pv_file: Option<String>,
+
+    #[clap(
+        long,
+        help = "Optional YAML input file path describing the sidecar containers and volumes that admission controllers are expected to inject into the input resources"
+    )]
+    sidecar_injection_file: Option<String>,
+
     #[clap(
         short = 'p',
         long,
@@ -45,6 +68,12 @@ struct CommandLineOptions {
     )]
     json_settings_path: String,
 
+    #[clap(
+        long,
+        help = "Optional settings override file, deep-merged into the settings from --json-settings-path. Can be specified more than once - the override files get applied in order."
+    )]
+    settings_override_file: Vec<String>,
+
     #[clap(
         short,
         long,
@@ -52,6 +81,36 @@ struct CommandLineOptions {
     )]
     use_cached_files: bool,
 
+    #[clap(
+        long,
+        help = "Download each container image's SBOM attestation, using cosign, and record the hash of the SBOM document in the generated policy"
+    )]
+    use_sbom: bool,
+
+    #[clap(
+        long,
+        help = "Generate an \"opa test\" file for each resource that gets a policy"
+    )]
+    generate_tests: bool,
+
+    #[clap(
+        long,
+        help = "Target architecture used to resolve multi-arch container image manifest lists - e.g., amd64 or arm64. The host architecture gets used if this option is not specified."
+    )]
+    target_arch: Option<String>,
+
+    #[clap(
+        long,
+        help = "Path to a Docker config.json file providing container image registry credentials. The standard DOCKER_CONFIG location gets used if this option is not specified."
+    )]
+    docker_config: Option<String>,
+
+    #[clap(
+        long,
+        help = "Verify each container image's signature, using cosign, against the required signers from the image_signature_policy settings"
+    )]
+    verify_image_signatures: bool,
+
     #[clap(
         short,
         long,
@@ -96,6 +155,31 @@ struct CommandLineOptions {
     )]
     runtime_class_names: Vec<String>,
 
+    #[clap(
+        long,
+        help = "If specified, only the resources with labels matching this kubectl-style equality-based selector receive a policy - e.g., \"app=my-service,tier=backend\". Other resources are passed through unchanged."
+    )]
+    label_selector: Option<String>,
+
+    #[clap(
+        long,
+        help = "If specified, run \"kustomize build\" on this directory and use its output as the Kubernetes input YAML, instead of --yaml-file or stdin"
+    )]
+    kustomize: Option<String>,
+
+    #[clap(
+        long,
+        help = "Additional command line argument passed through to \"kustomize build\". Can be specified more than once.",
+        requires = "kustomize"
+    )]
+    kustomize_args: Vec<String>,
+
+    #[clap(
+        long = "kind",
+        help = "If specified, only the resources of this kind receive a policy - e.g., --kind StatefulSet --kind DaemonSet. The kind is matched case-insensitively and other resources are passed through unchanged."
+    )]
+    kinds: Vec<String>,
+
     #[clap(
         long,
         help = "Path to the layers cache file. This file is used to store the layers cache information. The default value is ./layers-cache.json.",
@@ -103,10 +187,147 @@ struct CommandLineOptions {
         require_equals = true
     )]
     layers_cache_file_path: Option<String>,
+    #[clap(
+        long,
+        help = "Extract the identical rule bodies from the output policies into shared helper rules, reducing the policy size"
+    )]
+    deduplicate_rules: bool,
+
+    #[clap(
+        long,
+        overrides_with = "no_header",
+        help = "Add a provenance comment header to the generated policies. This is the default."
+    )]
+    add_header: bool,
+
+    #[clap(
+        long,
+        overrides_with = "add_header",
+        help = "Don't add a provenance comment header to the generated policies"
+    )]
+    no_header: bool,
+
+    #[clap(
+        long,
+        help = "Run the complete policy generation pipeline but don't write any output files. Print a summary of the policy annotation changes instead, and exit with code 1 if any resource's annotation would change."
+    )]
+    dry_run: bool,
+
+    #[clap(
+        long,
+        help = "Directory containing shared library plugins that add support for custom resource types wrapping a pod template"
+    )]
+    plugin_dir: Option<String>,
+
+    #[clap(
+        long,
+        help = "Show a progress bar while processing the YAML files from an input directory"
+    )]
+    progress: bool,
+
+    #[clap(
+        long,
+        help = "Print the JSON Schema of the genpolicy settings file and exit"
+    )]
+    print_settings_schema: bool,
+
     #[clap(short, long, help = "Print version information and exit")]
     version: bool,
 }
 
+#[derive(Debug, Subcommand)]
+enum Commands {
+    #[clap(
+        about = "Generate policies for a baseline and an updated Kubernetes YAML file and print a diff of the Rego policy text"
+    )]
+    Compare(CompareOptions),
+
+    #[clap(
+        about = "Extract the Rego policy text from the policy annotations of a Kubernetes YAML file"
+    )]
+    Extract(ExtractOptions),
+
+    #[clap(
+        about = "Generate a synthetic CreateContainerRequest input JSON document for one container of a Kubernetes YAML file, for testing the generated policy offline"
+    )]
+    GenTestInput(GenTestInputOptions),
+
+    #[clap(
+        about = "Print JSON Schema documents describing the OPA input JSON of each policy request type"
+    )]
+    Schema,
+
+    #[clap(
+        about = "Run as a K8s mutating admission webhook server that adds the policy annotation to the admitted resources"
+    )]
+    Webhook(WebhookOptions),
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct CompareOptions {
+    #[clap(long, help = "Baseline Kubernetes input YAML file path")]
+    pub baseline: String,
+
+    #[clap(long, help = "Updated Kubernetes input YAML file path")]
+    pub updated: String,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct GenTestInputOptions {
+    #[clap(
+        short,
+        long,
+        help = "Kubernetes input YAML file path. stdin gets used if this option is not specified."
+    )]
+    pub yaml_file: Option<String>,
+
+    #[clap(
+        short,
+        long,
+        help = "Name of the container to generate the CreateContainerRequest input JSON for"
+    )]
+    pub container: String,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ExtractOptions {
+    #[clap(
+        short,
+        long,
+        help = "Kubernetes input YAML file path. stdin gets used if this option is not specified."
+    )]
+    pub yaml_file: Option<String>,
+
+    #[clap(
+        short,
+        long,
+        help = "Output Rego file path. stdout gets used if this option is not specified. If the input YAML includes multiple annotated resources, this is the output directory for the <kind>-<name>.rego files."
+    )]
+    pub output: Option<String>,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct WebhookOptions {
+    #[clap(
+        long,
+        help = "Path to the PEM encoded TLS certificate of the webhook server"
+    )]
+    pub cert: String,
+
+    #[clap(
+        long,
+        help = "Path to the PEM encoded TLS private key of the webhook server"
+    )]
+    pub key: String,
+
+    #[clap(
+        long,
+        default_value_t = 8443,
+        help = "TCP port the webhook server listens on"
+    )]
+    pub port: u16,
+}
+
 /// Application configuration, derived from on command line parameters.
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -114,18 +335,42 @@ pub struct Config {
     pub use_cache: bool,
     pub insecure_registries: Vec<String>,
     pub runtime_class_names: Vec<String>,
+    pub label_selector: Option<yaml::LabelFilter>,
+    pub kinds: Vec<String>,
 
     pub yaml_file: Option<String>,
+    pub kustomize: Option<String>,
+    pub kustomize_args: Vec<String>,
     pub rego_rules_path: String,
     pub settings: settings::Settings,
     pub config_files: Option<Vec<String>>,
+    pub limit_range_file: Option<String>,
+    pub pv_file: Option<String>,
+    pub sidecar_injection_file: Option<String>,
 
     pub silent_unsupported_fields: bool,
+    pub use_sbom: bool,
+    pub generate_tests: bool,
+    pub target_arch: String,
+    pub docker_config: Option<String>,
+    pub verify_image_signatures: bool,
     pub raw_out: bool,
     pub base64_out: bool,
     pub containerd_socket_path: Option<String>,
+    pub add_header: bool,
+    pub deduplicate_rules: bool,
+    pub dry_run: bool,
+    pub plugin_dir: Option<String>,
+    pub progress: bool,
     pub layers_cache: layers_cache::ImageLayersCache,
+    pub manifest_cache: registry::ManifestCache,
+    pub print_settings_schema: bool,
     pub version: bool,
+    pub compare: Option<CompareOptions>,
+    pub extract: Option<ExtractOptions>,
+    pub gen_test_input: Option<GenTestInputOptions>,
+    pub schema: bool,
+    pub webhook: Option<WebhookOptions>,
 }
 
 impl Config {
@@ -148,22 +393,68 @@ impl Config {
             layers_cache_file_path = Some(String::from("./layers-cache.json"));
         }
 
-        let settings = settings::Settings::new(&args.json_settings_path);
+        let mut compare = None;
+        let mut extract = None;
+        let mut gen_test_input = None;
+        let mut schema = false;
+        let mut webhook = None;
+        match args.command {
+            Some(Commands::Compare(options)) => compare = Some(options),
+            Some(Commands::Extract(options)) => extract = Some(options),
+            Some(Commands::GenTestInput(options)) => gen_test_input = Some(options),
+            Some(Commands::Schema) => schema = true,
+            Some(Commands::Webhook(options)) => webhook = Some(options),
+            None => {}
+        }
+
+        let mut settings = settings::Settings::new(&args.json_settings_path);
+        for override_path in &args.settings_override_file {
+            settings
+                .merge_from_file(std::path::Path::new(override_path))
+                .unwrap();
+        }
+        settings.apply_env_overrides().unwrap();
 
         Self {
             use_cache: args.use_cached_files,
             insecure_registries: args.insecure_registry,
             runtime_class_names: args.runtime_class_names,
+            label_selector: args.label_selector.as_deref().map(yaml::LabelFilter::new),
+            kinds: args.kinds.iter().map(|kind| kind.to_lowercase()).collect(),
             yaml_file: args.yaml_file,
+            kustomize: args.kustomize,
+            kustomize_args: args.kustomize_args,
             rego_rules_path: args.rego_rules_path,
             settings,
             config_files,
+            limit_range_file: args.limit_range_file,
+            pv_file: args.pv_file,
+            sidecar_injection_file: args.sidecar_injection_file,
             silent_unsupported_fields: args.silent_unsupported_fields,
+            use_sbom: args.use_sbom,
+            generate_tests: args.generate_tests,
+            target_arch: args
+                .target_arch
+                .unwrap_or_else(|| registry::default_target_arch().to_string()),
+            docker_config: args.docker_config,
+            verify_image_signatures: args.verify_image_signatures,
             raw_out: args.raw_out,
             base64_out: args.base64_out,
             containerd_socket_path: args.containerd_socket_path,
+            add_header: !args.no_header,
+            deduplicate_rules: args.deduplicate_rules,
+            dry_run: args.dry_run,
+            plugin_dir: args.plugin_dir,
+            progress: args.progress,
             layers_cache: layers_cache::ImageLayersCache::new(&layers_cache_file_path),
+            manifest_cache: registry::ManifestCache::default(),
+            print_settings_schema: args.print_settings_schema,
             version: args.version,
+            compare,
+            extract,
+            gen_test_input,
+            schema,
+            webhook,
         }
     }
 }
@@ -63,6 +63,12 @@ pub struct EmptyDirVolumeSource {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PersistentVolumeClaimVolumeSource {
     pub claimName: String,
+
+    /// Host path of the local PersistentVolume bound to this claim through
+    /// its volumeName, resolved from the PersistentVolume definitions of the
+    /// input YAML and/or of the --pv-file input file.
+    #[serde(skip)]
+    pub local_path: Option<String>,
     // TODO: additional fields.
 }
 
@@ -0,0 +1,172 @@
+// Copyright (c) 2025 Microsoft Corporation
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Allow K8s API field names.
+#![allow(non_snake_case)]
+
+use crate::policy;
+use crate::utils::{Config, WebhookOptions};
+
+use anyhow::{anyhow, Result};
+use axum::{extract::State, routing::post, Json, Router};
+use axum_server::tls_openssl::OpenSSLConfig;
+use base64::{engine::general_purpose, Engine as _};
+use kata_types::annotations::KATA_ANNO_CFG_HYPERVISOR_INIT_DATA;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// See Reference / Kubernetes API / Extend Resources / AdmissionReview.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct AdmissionReview {
+    apiVersion: String,
+    kind: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request: Option<AdmissionRequest>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<AdmissionResponse>,
+}
+
+/// See Reference / Kubernetes API / Extend Resources / AdmissionReview.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct AdmissionRequest {
+    uid: String,
+    object: serde_json::Value,
+}
+
+/// See Reference / Kubernetes API / Extend Resources / AdmissionReview.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct AdmissionResponse {
+    uid: String,
+    allowed: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    patchType: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    patch: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<AdmissionResponseStatus>,
+}
+
+/// See Reference / Kubernetes API / Extend Resources / AdmissionReview.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct AdmissionResponseStatus {
+    message: String,
+}
+
+/// Run genpolicy as a K8s mutating admission webhook server, generating the
+/// policy annotation for each admitted resource instead of reading the
+/// resources from an input YAML file.
+pub async fn start_server(config: &Config, options: &WebhookOptions) {
+    let tls_config = OpenSSLConfig::from_pem_file(&options.cert, &options.key).unwrap();
+
+    let app = Router::new()
+        .route("/mutate", post(mutate))
+        .with_state(Arc::new(config.clone()));
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], options.port));
+    info!("Admission webhook server listening on {addr}");
+    axum_server::bind_openssl(addr, tls_config)
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
+
+async fn mutate(
+    State(config): State<Arc<Config>>,
+    Json(mut review): Json<AdmissionReview>,
+) -> Json<AdmissionReview> {
+    let Some(request) = review.request.take() else {
+        // Reject the review instead of panicking - an AdmissionReview without
+        // a request field is valid JSON that any caller can send.
+        warn!("mutate: invalid AdmissionReview input - missing the request field");
+        review.response = Some(AdmissionResponse {
+            uid: String::new(),
+            allowed: false,
+            patchType: None,
+            patch: None,
+            status: Some(AdmissionResponseStatus {
+                message: "Invalid AdmissionReview input - missing the request field. \
+                    Is the webhook configured correctly?"
+                    .to_string(),
+            }),
+        });
+        return Json(review);
+    };
+
+    let response = match generate_patch(&config, &request.object).await {
+        Ok(patch) => {
+            debug!("mutate: patch = {:?}", &patch);
+            let patch_bytes = serde_json::to_vec(&patch).unwrap();
+            AdmissionResponse {
+                uid: request.uid,
+                allowed: true,
+                patchType: Some("JSONPatch".to_string()),
+                patch: Some(general_purpose::STANDARD.encode(patch_bytes)),
+                status: None,
+            }
+        }
+        Err(e) => {
+            warn!("mutate: failed to generate policy - error {e:#}");
+            AdmissionResponse {
+                uid: request.uid,
+                allowed: false,
+                patchType: None,
+                patch: None,
+                status: Some(AdmissionResponseStatus {
+                    message: format!("genpolicy failed to generate a policy: {e:#}"),
+                }),
+            }
+        }
+    };
+
+    review.response = Some(response);
+    Json(review)
+}
+
+/// Generate a JSON patch that adds the policy annotation to the admitted
+/// resource, by applying the regular genpolicy logic to that resource.
+async fn generate_patch(
+    config: &Config,
+    object: &serde_json::Value,
+) -> Result<Vec<serde_json::Value>> {
+    let yaml_contents = serde_yaml::to_string(object)?;
+    let agent_policy = policy::AgentPolicy::from_yaml_contents(config, &yaml_contents).await?;
+
+    let resource = agent_policy
+        .resources
+        .first()
+        .ok_or_else(|| anyhow!("the admission request does not contain a K8s resource"))?;
+    let annotation = resource.generate_initdata_anno(&agent_policy);
+    if annotation.is_empty() {
+        return Err(anyhow!("the admitted resource doesn't get a policy"));
+    }
+
+    let mut patch = Vec::new();
+    if object.pointer("/metadata/annotations").is_none() {
+        patch.push(serde_json::json!({
+            "op": "add",
+            "path": "/metadata/annotations",
+            "value": {},
+        }));
+    }
+
+    // Escape the annotation key as required by RFC 6901.
+    let escaped_key = KATA_ANNO_CFG_HYPERVISOR_INIT_DATA
+        .replace('~', "~0")
+        .replace('/', "~1");
+    patch.push(serde_json::json!({
+        "op": "add",
+        "path": format!("/metadata/annotations/{escaped_key}"),
+        "value": annotation,
+    }));
+
+    Ok(patch)
+}
@@ -6,29 +6,36 @@
 // Allow K8s YAML field names.
 #![allow(non_snake_case)]
 
+use crate::argo_rollout;
 use crate::config_map;
 use crate::cronjob;
 use crate::daemon_set;
 use crate::deployment;
+use crate::hpa;
 use crate::job;
+use crate::lease;
+use crate::limit_range;
 use crate::list;
 use crate::mount_and_storage;
+use crate::namespace;
 use crate::no_policy;
 use crate::obj_meta::ObjectMeta;
 use crate::pod;
 use crate::policy;
+use crate::registry;
 use crate::replica_set;
 use crate::replication_controller;
 use crate::secret;
 use crate::settings;
 use crate::stateful_set;
+use crate::storage_class;
 use crate::utils::Config;
 use crate::volume;
 
 use async_trait::async_trait;
 use core::fmt::Debug;
 use kata_types::annotations::KATA_ANNO_CFG_HYPERVISOR_INIT_DATA;
-use log::debug;
+use log::{debug, warn};
 use protocols::agent;
 use serde::{Deserialize, Serialize};
 use std::boxed;
@@ -56,10 +63,24 @@ pub trait K8sResource {
         panic!("Unsupported");
     }
 
+    /// Serialize the output YAML of this resource, after adding the policy
+    /// annotation to it. Implementations re-emit the unmodified doc_mapping
+    /// they got initialized with, instead of their own typed fields, so the
+    /// input fields that genpolicy doesn't interpret - e.g., fields pruned
+    /// from CRD manifests, custom annotations, status subresources, or future
+    /// K8s fields - don't get lost in the output YAML.
     fn serialize(&mut self, _policy: &str) -> String {
         panic!("Unsupported");
     }
 
+    /// Dot-separated path of the mapping whose metadata gets the policy
+    /// annotation - e.g., "spec.template". Resource types that return a path
+    /// support surgical annotation insertion into the raw input YAML text,
+    /// keeping the other output lines byte-identical to the input.
+    fn get_metadata_path(&self) -> Option<String> {
+        None
+    }
+
     fn get_sandbox_name(&self) -> Option<String> {
         panic!("Unsupported");
     }
@@ -68,6 +89,58 @@ pub trait K8sResource {
         panic!("Unsupported");
     }
 
+    /// Whether this is a cluster-scoped resource type - e.g., a Namespace or
+    /// a PersistentVolume. Cluster-scoped resources don't carry namespace
+    /// metadata, so the generated policy doesn't validate the sandbox
+    /// namespace for them.
+    fn is_cluster_scoped(&self) -> bool {
+        false
+    }
+
+    fn get_sandbox_hostname(&self) -> Option<String> {
+        // Resource types that don't create a sandbox don't restrict the
+        // sandbox hostname either.
+        None
+    }
+
+    fn get_image_pull_secrets(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    fn get_sandbox_dns(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    fn get_service_account_name(&self) -> Option<String> {
+        // Resource types that don't create a sandbox don't constrain the
+        // sandbox's service account either.
+        None
+    }
+
+    /// VolumeAttributesClass names referenced by this resource's
+    /// volumeClaimTemplates, if any.
+    fn get_volume_attributes_classes(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Names of the Dynamic Resource Allocation resourceClaims listed by
+    /// this resource's pod spec, if any.
+    fn get_resource_claims(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    fn has_host_aliases(&self) -> bool {
+        false
+    }
+
+    fn get_policy_comment(&self) -> Option<String> {
+        None
+    }
+
+    fn get_topology_keys(&self) -> Option<Vec<String>> {
+        None
+    }
+
     fn get_container_mounts_and_storages(
         &self,
         _policy_mounts: &mut Vec<policy::KataMount>,
@@ -110,6 +183,44 @@ pub trait K8sResource {
     fn get_sysctls(&self) -> Vec<pod::Sysctl> {
         vec![]
     }
+
+    /// Env variables in "name=value" format that the K8s controllers generate
+    /// for this resource's containers, in addition to those from its YAML.
+    /// Values may be policy markers - e.g., "$(job-completion-index)".
+    fn get_generated_env_variables(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn apply_limit_range_defaults(&mut self, _limit_ranges: &[limit_range::LimitRange]) {
+        // LimitRange defaults apply just to the K8s resource types that
+        // create containers.
+    }
+
+    fn resolve_local_volumes(&mut self, _local_pv_paths: &BTreeMap<String, String>) {
+        // Local PersistentVolume paths apply just to the K8s resource types
+        // that create containers.
+    }
+
+    fn automount_service_account_token(&self) -> bool {
+        true
+    }
+
+    fn add_injected_sidecars(&mut self, _injection: &SidecarInjection) {
+        // Sidecars get injected just into the K8s resource types that
+        // create containers.
+    }
+}
+
+/// Expected sidecar containers and volumes that get injected by admission
+/// controllers (e.g., service mesh proxies), loaded from the file specified
+/// by the --sidecar-injection-file command line parameter.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SidecarInjection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub containers: Option<Vec<pod::Container>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volumes: Option<Vec<volume::Volume>>,
 }
 
 /// See Reference / Kubernetes API / Common Definitions / LabelSelector.
@@ -132,17 +243,66 @@ pub struct LabelSelectorRequirement {
     values: Option<Vec<String>>,
 }
 
+/// The current K8s API version of each supported resource kind. Used for
+/// warning about deprecated API versions - e.g., apps/v1beta1 - that K8s
+/// and/or genpolicy might parse differently from the current versions.
+static API_VERSION_MAP: phf::Map<&str, &str> = phf::phf_map! {
+    "ConfigMap" => "v1",
+    "CronJob" => "batch/v1",
+    "DaemonSet" => "apps/v1",
+    "Deployment" => "apps/v1",
+    "HorizontalPodAutoscaler" => "autoscaling/v2",
+    "Job" => "batch/v1",
+    "LimitRange" => "v1",
+    "List" => "v1",
+    "Namespace" => "v1",
+    "Pod" => "v1",
+    "ReplicaSet" => "apps/v1",
+    "ReplicationController" => "v1",
+    "Rollout" => "argoproj.io/v1alpha1",
+    "Secret" => "v1",
+    "StatefulSet" => "apps/v1",
+};
+
+/// Warn about deprecated or unexpected K8s API versions. Deprecated versions
+/// don't get rejected, but the values of the YAML fields that changed between
+/// API versions might end up being misinterpreted.
+fn check_api_version(header: &YamlHeader) {
+    if let Some(&current_version) = API_VERSION_MAP.get(&header.kind) {
+        if header.apiVersion != current_version {
+            warn!(
+                "{} uses API version {}, not the current {current_version} - \
+                the parsed fields may differ",
+                &header.kind, &header.apiVersion
+            );
+        }
+    }
+}
+
+/// The API group of an apiVersion value - e.g., "apps" for "apps/v1", or ""
+/// for the core group's "v1". The group identifies the resource type across
+/// its API versions, which check_api_version() warns about separately.
+fn api_group(api_version: &str) -> &str {
+    api_version
+        .rfind('/')
+        .map_or("", |index| &api_version[..index])
+}
+
 /// Creates one of the supported K8s objects from a YAML string.
 pub fn new_k8s_resource(
     yaml: &str,
     silent_unsupported_fields: bool,
 ) -> anyhow::Result<(boxed::Box<dyn K8sResource + Sync + Send>, String)> {
     let header = get_yaml_header(yaml)?;
+    check_api_version(&header);
     let kind: &str = &header.kind;
     let d = serde_yaml::Deserializer::from_str(yaml);
 
-    match kind {
-        "ConfigMap" => {
+    // Dispatch on the API group in addition to the kind, so that a custom
+    // resource reusing the name of a supported kind under a different API
+    // group doesn't get misinterpreted as that kind.
+    match (api_group(&header.apiVersion), kind) {
+        ("", "ConfigMap") => {
             let config_map: config_map::ConfigMap = serde_ignored::deserialize(d, |path| {
                 handle_unused_field(&path.to_string(), silent_unsupported_fields);
             })
@@ -150,7 +310,7 @@ pub fn new_k8s_resource(
             debug!("{:#?}", &config_map);
             Ok((boxed::Box::new(config_map), header.kind))
         }
-        "DaemonSet" => {
+        ("apps", "DaemonSet") => {
             let daemon: daemon_set::DaemonSet = serde_ignored::deserialize(d, |path| {
                 handle_unused_field(&path.to_string(), silent_unsupported_fields);
             })
@@ -158,7 +318,15 @@ pub fn new_k8s_resource(
             debug!("{:#?}", &daemon);
             Ok((boxed::Box::new(daemon), header.kind))
         }
-        "Deployment" => {
+        ("argoproj.io", "Rollout") => {
+            let rollout: argo_rollout::ArgoRollout = serde_ignored::deserialize(d, |path| {
+                handle_unused_field(&path.to_string(), silent_unsupported_fields);
+            })
+            .unwrap();
+            debug!("{:#?}", &rollout);
+            Ok((boxed::Box::new(rollout), header.kind))
+        }
+        ("apps", "Deployment") => {
             let deployment: deployment::Deployment = serde_ignored::deserialize(d, |path| {
                 handle_unused_field(&path.to_string(), silent_unsupported_fields);
             })
@@ -166,7 +334,15 @@ pub fn new_k8s_resource(
             debug!("{:#?}", &deployment);
             Ok((boxed::Box::new(deployment), header.kind))
         }
-        "Job" => {
+        ("autoscaling", "HorizontalPodAutoscaler") => {
+            let hpa: hpa::HPA = serde_ignored::deserialize(d, |path| {
+                handle_unused_field(&path.to_string(), silent_unsupported_fields);
+            })
+            .unwrap();
+            debug!("{:#?}", &hpa);
+            Ok((boxed::Box::new(hpa), header.kind))
+        }
+        ("batch", "Job") => {
             let job: job::Job = serde_ignored::deserialize(d, |path| {
                 handle_unused_field(&path.to_string(), silent_unsupported_fields);
             })
@@ -174,7 +350,7 @@ pub fn new_k8s_resource(
             debug!("{:#?}", &job);
             Ok((boxed::Box::new(job), header.kind))
         }
-        "CronJob" => {
+        ("batch", "CronJob") => {
             let cronJob: cronjob::CronJob = serde_ignored::deserialize(d, |path| {
                 handle_unused_field(&path.to_string(), silent_unsupported_fields);
             })
@@ -182,7 +358,15 @@ pub fn new_k8s_resource(
             debug!("{:#?}", &cronJob);
             Ok((boxed::Box::new(cronJob), header.kind))
         }
-        "List" => {
+        ("", "Namespace") => {
+            let namespace: namespace::Namespace = serde_ignored::deserialize(d, |path| {
+                handle_unused_field(&path.to_string(), silent_unsupported_fields);
+            })
+            .unwrap();
+            debug!("{:#?}", &namespace);
+            Ok((boxed::Box::new(namespace), header.kind))
+        }
+        ("", "List") => {
             let list: list::List = serde_ignored::deserialize(d, |path| {
                 handle_unused_field(&path.to_string(), silent_unsupported_fields);
             })
@@ -190,7 +374,7 @@ pub fn new_k8s_resource(
             debug!("{:#?}", &list);
             Ok((boxed::Box::new(list), header.kind))
         }
-        "Pod" => {
+        ("", "Pod") => {
             let pod: pod::Pod = serde_ignored::deserialize(d, |path| {
                 handle_unused_field(&path.to_string(), silent_unsupported_fields);
             })
@@ -198,7 +382,7 @@ pub fn new_k8s_resource(
             debug!("{:#?}", &pod);
             Ok((boxed::Box::new(pod), header.kind))
         }
-        "ReplicaSet" => {
+        ("apps", "ReplicaSet") => {
             let set: replica_set::ReplicaSet = serde_ignored::deserialize(d, |path| {
                 handle_unused_field(&path.to_string(), silent_unsupported_fields);
             })
@@ -206,7 +390,7 @@ pub fn new_k8s_resource(
             debug!("{:#?}", &set);
             Ok((boxed::Box::new(set), header.kind))
         }
-        "ReplicationController" => {
+        ("", "ReplicationController") => {
             let controller: replication_controller::ReplicationController =
                 serde_ignored::deserialize(d, |path| {
                     handle_unused_field(&path.to_string(), silent_unsupported_fields);
@@ -215,7 +399,7 @@ pub fn new_k8s_resource(
             debug!("{:#?}", &controller);
             Ok((boxed::Box::new(controller), header.kind))
         }
-        "Secret" => {
+        ("", "Secret") => {
             let secret: secret::Secret = serde_ignored::deserialize(d, |path| {
                 handle_unused_field(&path.to_string(), silent_unsupported_fields);
             })
@@ -223,7 +407,7 @@ pub fn new_k8s_resource(
             debug!("{:#?}", &secret);
             Ok((boxed::Box::new(secret), header.kind))
         }
-        "StatefulSet" => {
+        ("apps", "StatefulSet") => {
             let set: stateful_set::StatefulSet = serde_ignored::deserialize(d, |path| {
                 handle_unused_field(&path.to_string(), silent_unsupported_fields);
             })
@@ -231,29 +415,143 @@ pub fn new_k8s_resource(
             debug!("{:#?}", &set);
             Ok((boxed::Box::new(set), header.kind))
         }
-        "ClusterRole"
-        | "ClusterRoleBinding"
-        | "LimitRange"
-        | "Namespace"
-        | "PersistentVolume"
-        | "PersistentVolumeClaim"
-        | "PodDisruptionBudget"
-        | "PriorityClass"
-        | "ResourceQuota"
-        | "Role"
-        | "RoleBinding"
-        | "Service"
-        | "ServiceAccount" => {
+        ("", "LimitRange") => {
+            let limit_range: limit_range::LimitRange = serde_ignored::deserialize(d, |path| {
+                handle_unused_field(&path.to_string(), silent_unsupported_fields);
+            })
+            .unwrap();
+            debug!("{:#?}", &limit_range);
+            Ok((boxed::Box::new(limit_range), header.kind))
+        }
+        ("coordination.k8s.io", "Lease") => {
+            let lease: lease::Lease = serde_ignored::deserialize(d, |path| {
+                handle_unused_field(&path.to_string(), silent_unsupported_fields);
+            })
+            .unwrap();
+            debug!("{:#?}", &lease);
+            Ok((boxed::Box::new(lease), header.kind))
+        }
+        ("storage.k8s.io", "StorageClass") => {
+            let storage_class: storage_class::StorageClass =
+                serde_ignored::deserialize(d, |path| {
+                    handle_unused_field(&path.to_string(), silent_unsupported_fields);
+                })
+                .unwrap();
+            debug!("{:#?}", &storage_class);
+            Ok((boxed::Box::new(storage_class), header.kind))
+        }
+        ("rbac.authorization.k8s.io", "ClusterRole")
+        | ("rbac.authorization.k8s.io", "ClusterRoleBinding")
+        | ("", "PersistentVolume")
+        | ("", "PersistentVolumeClaim")
+        | ("policy", "PodDisruptionBudget")
+        | ("scheduling.k8s.io", "PriorityClass")
+        | ("", "ResourceQuota")
+        | ("rbac.authorization.k8s.io", "Role")
+        | ("rbac.authorization.k8s.io", "RoleBinding")
+        | ("", "Service")
+        | ("", "ServiceAccount")
+        | ("storage.k8s.io", "VolumeAttributesClass") => {
             let no_policy = no_policy::NoPolicyResource {
                 yaml: yaml.to_string(),
             };
             debug!("{:#?}", &no_policy);
             Ok((boxed::Box::new(no_policy), header.kind))
         }
-        _ => todo!("Unsupported YAML spec kind: {}", kind),
+        _ => todo!(
+            "Unsupported YAML spec apiVersion/kind: {}/{kind}",
+            &header.apiVersion
+        ),
+    }
+}
+
+/// One requirement from a kubectl-style equality-based label selector.
+#[derive(Clone, Debug)]
+enum LabelFilterRequirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    Exists(String),
+}
+
+/// Label selector using the kubectl equality-based syntax - e.g.,
+/// "app=my-service,tier!=backend,release", loaded from the --label-selector
+/// command line parameter.
+#[derive(Clone, Debug)]
+pub struct LabelFilter {
+    requirements: Vec<LabelFilterRequirement>,
+}
+
+impl LabelFilter {
+    pub fn new(selector: &str) -> Self {
+        let mut requirements = Vec::new();
+
+        for requirement in selector.split(',') {
+            let requirement = requirement.trim();
+            if let Some((key, value)) = requirement.split_once("!=") {
+                requirements.push(LabelFilterRequirement::NotEquals(
+                    key.to_string(),
+                    value.to_string(),
+                ));
+            } else if let Some((key, value)) = requirement.split_once("==") {
+                requirements.push(LabelFilterRequirement::Equals(
+                    key.to_string(),
+                    value.to_string(),
+                ));
+            } else if let Some((key, value)) = requirement.split_once('=') {
+                requirements.push(LabelFilterRequirement::Equals(
+                    key.to_string(),
+                    value.to_string(),
+                ));
+            } else if !requirement.is_empty() {
+                requirements.push(LabelFilterRequirement::Exists(requirement.to_string()));
+            } else {
+                panic!("Invalid label selector: {selector}");
+            }
+        }
+
+        Self { requirements }
+    }
+
+    /// Check whether the metadata.labels of a YAML document match all the
+    /// requirements of this selector.
+    pub fn matches(&self, doc_mapping: &serde_yaml::Value) -> bool {
+        let labels = &doc_mapping["metadata"]["labels"];
+        self.requirements
+            .iter()
+            .all(|requirement| match requirement {
+                LabelFilterRequirement::Equals(key, value) => {
+                    labels[key.as_str()].as_str() == Some(value)
+                }
+                LabelFilterRequirement::NotEquals(key, value) => {
+                    labels[key.as_str()].as_str() != Some(value)
+                }
+                LabelFilterRequirement::Exists(key) => !labels[key.as_str()].is_null(),
+            })
     }
 }
 
+/// Generate the input YAML by running "kustomize build" on the Kustomize
+/// directory specified by the --kustomize command line parameter.
+pub fn get_kustomize_yaml(
+    kustomize_dir: &str,
+    kustomize_args: &[String],
+) -> anyhow::Result<String> {
+    let output = std::process::Command::new("kustomize")
+        .arg("build")
+        .args(kustomize_args)
+        .arg(kustomize_dir)
+        .output()?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "kustomize build {kustomize_dir} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
 pub fn get_input_yaml(yaml_file: &Option<String>) -> anyhow::Result<String> {
     let yaml_string = if let Some(yaml) = yaml_file {
         read_to_string(yaml)?
@@ -269,6 +567,12 @@ pub fn get_yaml_header(yaml: &str) -> anyhow::Result<YamlHeader> {
 }
 
 pub async fn k8s_resource_init(spec: &mut pod::PodSpec, config: &Config) {
+    if let Some(os) = &spec.os {
+        if os.name.eq_ignore_ascii_case("windows") {
+            warn!("Windows pods are not supported - the generated policy rejects non-Linux containers");
+        }
+    }
+
     for container in &mut spec.containers {
         container.init(config).await;
     }
@@ -284,6 +588,48 @@ pub async fn k8s_resource_init(spec: &mut pod::PodSpec, config: &Config) {
     }
 }
 
+pub fn apply_limit_range_defaults(
+    spec: &mut pod::PodSpec,
+    limit_ranges: &[limit_range::LimitRange],
+) {
+    for container in &mut spec.containers {
+        container.apply_limit_range_defaults(limit_ranges);
+    }
+}
+
+/// Records into each persistentVolumeClaim volume of this pod spec the host
+/// path of the local PersistentVolume bound to its claim, if any.
+pub fn resolve_local_volumes(spec: &mut pod::PodSpec, local_pv_paths: &BTreeMap<String, String>) {
+    if let Some(volumes) = &mut spec.volumes {
+        for volume in volumes {
+            if let Some(claim) = &mut volume.persistentVolumeClaim {
+                if let Some(local_path) = local_pv_paths.get(&claim.claimName) {
+                    debug!(
+                        "resolve_local_volumes: volume {} uses local PV path {local_path}",
+                        &volume.name
+                    );
+                    claim.local_path = Some(local_path.clone());
+                }
+            }
+        }
+    }
+}
+
+pub fn automount_service_account_token(spec: &pod::PodSpec) -> bool {
+    spec.automountServiceAccountToken.unwrap_or(true)
+}
+
+pub fn add_injected_sidecars(spec: &mut pod::PodSpec, injection: &SidecarInjection) {
+    if let Some(containers) = &injection.containers {
+        spec.containers.extend(containers.iter().cloned());
+    }
+    if let Some(volumes) = &injection.volumes {
+        spec.volumes
+            .get_or_insert_with(Vec::new)
+            .extend(volumes.iter().cloned());
+    }
+}
+
 pub fn get_container_mounts_and_storages(
     policy_mounts: &mut Vec<policy::KataMount>,
     storages: &mut Vec<agent::Storage>,
@@ -300,6 +646,7 @@ pub fn get_container_mounts_and_storages(
                             settings,
                             policy_mounts,
                             storages,
+                            container,
                             volume,
                             volume_mount,
                         );
@@ -369,6 +716,161 @@ pub fn add_policy_annotation(
     }
 }
 
+/// Split a multi-document YAML string into its documents, keeping the text
+/// of each document - including its "---" separator line - byte-identical
+/// to the input. Comment and directive lines preceding the first "---" get
+/// attached to the first document, matching the YAML parser's view.
+pub fn split_documents(yaml_contents: &str) -> Vec<String> {
+    let mut documents: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for line in yaml_contents.split_inclusive('\n') {
+        if line.trim_end() == "---" || line.starts_with("--- ") {
+            let prologue_only = !current.is_empty()
+                && current.lines().all(|current_line| {
+                    let trimmed = current_line.trim();
+                    trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('%')
+                });
+            if !prologue_only && (!current.is_empty() || !documents.is_empty()) {
+                documents.push(current);
+                current = String::new();
+            }
+        }
+        current += line;
+    }
+    documents.push(current);
+    documents
+}
+
+/// Insert the [`KATA_ANNO_CFG_HYPERVISOR_INIT_DATA`] annotation into the raw
+/// YAML text of a resource, leaving all other lines byte-identical to the
+/// input. Re-serializing the doc_mapping instead can reorder map keys and
+/// change quoting throughout the whole document, burying the actual change
+/// in "git diff" noise. Returns None when the target metadata mapping can't
+/// be located in the text - e.g., for flow-style mappings - and the caller
+/// falls back to re-serialization.
+pub fn insert_policy_annotation(
+    raw_document: &str,
+    metadata_path: &str,
+    policy: &str,
+) -> Option<String> {
+    let mut target: Vec<&str> = if metadata_path.is_empty() {
+        Vec::new()
+    } else {
+        metadata_path.split('.').collect()
+    };
+    target.push("metadata");
+
+    let lines: Vec<&str> = raw_document.split_inclusive('\n').collect();
+    let mut stack: Vec<(usize, &str)> = Vec::new();
+    let mut block_scalar_indent: Option<usize> = None;
+    let mut metadata_line: Option<(usize, usize)> = None;
+    let mut annotations_line: Option<(usize, usize)> = None;
+    let mut annotations_child_indent: Option<usize> = None;
+    let mut policy_line: Option<usize> = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        let text = line.trim_end();
+        if text.is_empty() {
+            continue;
+        }
+        let indent = text.len() - text.trim_start_matches(' ').len();
+        if let Some(scalar_indent) = block_scalar_indent {
+            if indent > scalar_indent {
+                // Skip the content lines of a block scalar value.
+                continue;
+            }
+            block_scalar_indent = None;
+        }
+        let content = &text[indent..];
+        if content.starts_with('#') || content.starts_with('-') || content.starts_with('%') {
+            continue;
+        }
+        let Some((quoted_key, value)) = content.split_once(':') else {
+            continue;
+        };
+        let key = quoted_key.trim_matches(|quote| quote == '"' || quote == '\'');
+        let value = value.trim();
+        if value.starts_with('|') || value.starts_with('>') {
+            block_scalar_indent = Some(indent);
+        }
+
+        while stack.last().is_some_and(|(prev, _)| *prev >= indent) {
+            stack.pop();
+        }
+        stack.push((indent, key));
+
+        let on_target_path = stack
+            .iter()
+            .zip(&target)
+            .all(|((_, stack_key), target_key)| stack_key == target_key);
+        if stack.len() == target.len() && on_target_path {
+            if !value.is_empty() {
+                // The metadata is a flow-style mapping.
+                return None;
+            }
+            metadata_line = Some((index, indent));
+        } else if stack.len() == target.len() + 1 && on_target_path && key == "annotations" {
+            if !value.is_empty() {
+                return None;
+            }
+            annotations_line = Some((index, indent));
+        } else if stack.len() == target.len() + 2
+            && on_target_path
+            && stack[target.len()].1 == "annotations"
+        {
+            annotations_child_indent.get_or_insert(indent);
+            if key == KATA_ANNO_CFG_HYPERVISOR_INIT_DATA {
+                if value.starts_with('|') || value.starts_with('>') {
+                    // Replacing a multi-line value is not supported.
+                    return None;
+                }
+                policy_line = Some(index);
+            }
+        }
+    }
+
+    let mut output = String::new();
+    if let Some(index) = policy_line {
+        let indent = annotations_child_indent.unwrap();
+        for (i, line) in lines.iter().enumerate() {
+            if i == index {
+                output += &format!(
+                    "{}{KATA_ANNO_CFG_HYPERVISOR_INIT_DATA}: {policy}\n",
+                    " ".repeat(indent)
+                );
+            } else {
+                output += line;
+            }
+        }
+    } else if let Some((index, indent)) = annotations_line {
+        let child_indent = annotations_child_indent.unwrap_or(indent + 2);
+        for (i, line) in lines.iter().enumerate() {
+            output += line;
+            if i == index {
+                output += &format!(
+                    "{}{KATA_ANNO_CFG_HYPERVISOR_INIT_DATA}: {policy}\n",
+                    " ".repeat(child_indent)
+                );
+            }
+        }
+    } else if let Some((index, indent)) = metadata_line {
+        for (i, line) in lines.iter().enumerate() {
+            output += line;
+            if i == index {
+                output += &format!(
+                    "{}annotations:\n{}{KATA_ANNO_CFG_HYPERVISOR_INIT_DATA}: {policy}\n",
+                    " ".repeat(indent + 2),
+                    " ".repeat(indent + 4)
+                );
+            }
+        }
+    } else {
+        return None;
+    }
+    Some(output)
+}
+
 /// Remove [`KATA_ANNO_CFG_HYPERVISOR_INIT_DATA`] annotation
 pub fn remove_policy_annotation(annotations: &mut BTreeMap<String, String>) {
     annotations.remove(KATA_ANNO_CFG_HYPERVISOR_INIT_DATA);
@@ -385,6 +887,32 @@ fn handle_unused_field(path: &str, silent_unsupported_fields: bool) {
     }
 }
 
+/// Compute the final container process arguments from the image config's
+/// Entrypoint and Cmd and the K8s container's command and args fields: the
+/// K8s command replaces the image Entrypoint and the K8s args replace the
+/// image Cmd. The image Cmd applies only when neither command nor args are
+/// specified, matching the kubelet behavior.
+pub fn resolve_process_args(
+    container: &pod::Container,
+    image_config: &registry::DockerImageConfig,
+) -> Vec<String> {
+    let mut args = if let Some(command) = &container.command {
+        command.clone()
+    } else {
+        image_config.Entrypoint.clone().unwrap_or_default()
+    };
+
+    if let Some(yaml_args) = &container.args {
+        args.extend(yaml_args.iter().cloned());
+    } else if container.command.is_none() {
+        if let Some(cmd) = &image_config.Cmd {
+            args.extend(cmd.iter().cloned());
+        }
+    }
+
+    args
+}
+
 pub fn get_process_fields(
     process: &mut policy::KataProcess,
     security_context: &Option<pod::PodSecurityContext>,
@@ -450,11 +978,109 @@ pub fn get_sysctls(security_context: &Option<pod::PodSecurityContext>) -> Vec<po
 /// 3. Otherwise, return None. This object is not considered valid by the k8s API server!
 pub fn name_regex_from_meta(meta: &ObjectMeta) -> Option<String> {
     meta.name.clone().or_else(|| {
-        meta.generateName
-            .as_ref()
-            .map(|p| format!("{}{}", regex::escape(p), GENERATE_NAME_SUFFIX_REGEX))
+        meta.generateName.as_ref().map(|p| {
+            warn!(
+                "The actual name of a generate-name object is unknown at policy generation \
+                time, so the generated policy matches names starting with {p} by regex"
+            );
+            format!("{}{}", regex::escape(p), GENERATE_NAME_SUFFIX_REGEX)
+        })
     })
 }
 
 // https://github.com/kubernetes/kubernetes/blob/b35c5c0a301d326fdfa353943fca077778544ac6/staging/src/k8s.io/apimachinery/pkg/util/rand/rand.go#L81-L83
 pub const GENERATE_NAME_SUFFIX_REGEX: &str = "[bcdfghjklmnpqrstvwxz2456789]+";
+
+#[cfg(test)]
+mod tests {
+    use super::insert_policy_annotation;
+
+    #[test]
+    fn insert_policy_annotation_into_existing_annotations() {
+        let raw = "apiVersion: v1\n\
+            kind: Pod\n\
+            metadata:\n\
+            \x20 name: test\n\
+            \x20 annotations:\n\
+            \x20   existing: value\n\
+            spec:\n\
+            \x20 containers: []\n";
+        let output = insert_policy_annotation(raw, "", "encoded-policy").unwrap();
+        assert_eq!(
+            output,
+            "apiVersion: v1\n\
+            kind: Pod\n\
+            metadata:\n\
+            \x20 name: test\n\
+            \x20 annotations:\n\
+            \x20   io.katacontainers.config.hypervisor.cc_init_data: encoded-policy\n\
+            \x20   existing: value\n\
+            spec:\n\
+            \x20 containers: []\n"
+        );
+    }
+
+    #[test]
+    fn insert_policy_annotation_creates_annotations_mapping() {
+        let raw = "apiVersion: v1\n\
+            kind: Pod\n\
+            metadata:\n\
+            \x20 name: test\n\
+            spec:\n\
+            \x20 containers: []\n";
+        let output = insert_policy_annotation(raw, "", "encoded-policy").unwrap();
+        assert_eq!(
+            output,
+            "apiVersion: v1\n\
+            kind: Pod\n\
+            metadata:\n\
+            \x20 annotations:\n\
+            \x20   io.katacontainers.config.hypervisor.cc_init_data: encoded-policy\n\
+            \x20 name: test\n\
+            spec:\n\
+            \x20 containers: []\n"
+        );
+    }
+
+    #[test]
+    fn insert_policy_annotation_replaces_existing_policy() {
+        let raw = "metadata:\n\
+            \x20 annotations:\n\
+            \x20   io.katacontainers.config.hypervisor.cc_init_data: old-policy\n";
+        let output = insert_policy_annotation(raw, "", "new-policy").unwrap();
+        assert_eq!(
+            output,
+            "metadata:\n\
+            \x20 annotations:\n\
+            \x20   io.katacontainers.config.hypervisor.cc_init_data: new-policy\n"
+        );
+    }
+
+    #[test]
+    fn insert_policy_annotation_into_nested_metadata() {
+        let raw = "spec:\n\
+            \x20 template:\n\
+            \x20   metadata:\n\
+            \x20     labels:\n\
+            \x20       app: test\n";
+        let output = insert_policy_annotation(raw, "spec.template", "encoded-policy").unwrap();
+        assert_eq!(
+            output,
+            "spec:\n\
+            \x20 template:\n\
+            \x20   metadata:\n\
+            \x20     annotations:\n\
+            \x20       io.katacontainers.config.hypervisor.cc_init_data: encoded-policy\n\
+            \x20     labels:\n\
+            \x20       app: test\n"
+        );
+    }
+
+    #[test]
+    fn insert_policy_annotation_rejects_flow_style_metadata() {
+        let raw = "apiVersion: v1\n\
+            kind: Pod\n\
+            metadata: { name: test }\n";
+        assert!(insert_policy_annotation(raw, "", "encoded-policy").is_none());
+    }
+}
@@ -97,6 +97,10 @@ mod tests {
             containerd_socket_path: None, // Some(String::from("/var/run/containerd/containerd.sock")),
             insecure_registries: Vec::new(),
             layers_cache: genpolicy::layers_cache::ImageLayersCache::new(&None),
+            manifest_cache: genpolicy::registry::ManifestCache::default(),
+            limit_range_file: None,
+            pv_file: None,
+            sidecar_injection_file: None,
             raw_out: false,
             rego_rules_path: workdir.join("rules.rego").to_str().unwrap().to_string(),
             runtime_class_names: Vec::new(),
@@ -105,7 +109,27 @@ mod tests {
             ),
             silent_unsupported_fields: false,
             use_cache: false,
+            use_sbom: false,
+            generate_tests: false,
+            target_arch: genpolicy::registry::default_target_arch().to_string(),
+            docker_config: None,
+            verify_image_signatures: false,
+            print_settings_schema: false,
+            label_selector: None,
+            add_header: false,
+            deduplicate_rules: false,
+            dry_run: false,
+            plugin_dir: None,
+            progress: false,
+            kustomize: None,
+            kustomize_args: Vec::new(),
+            compare: None,
+            extract: None,
+            gen_test_input: None,
+            schema: false,
+            kinds: Vec::new(),
             version: false,
+            webhook: None,
             yaml_file: workdir.join("pod.yaml").to_str().map(|s| s.to_string()),
         };
 